@@ -0,0 +1,349 @@
+// src/aead.rs
+use crate::core::{pbkdf2_hmac_qosmic, HmacQosmicKey, MAX_VERIFY_ITERATIONS};
+use crate::hkdf::hkdf;
+use crate::utils;
+use crate::xof::QosmicXof;
+use log::debug;
+use rand::RngCore;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+pub const KEY_LEN: usize = 32;
+pub const NONCE_LEN: usize = 24;
+pub const TAG_LEN: usize = 32;
+pub const STREAM_MAGIC: &[u8; 4] = b"QENC";
+pub const STREAM_VERSION: u8 = 1;
+pub const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
+pub const MAX_CHUNK_SIZE: u32 = 1 << 24;
+/// Largest PBKDF2 iteration count accepted in a password header, the same limit password
+/// verification uses, so a crafted file cannot stall decryption before authentication.
+pub const MAX_PASSWORD_ITERATIONS: u32 = MAX_VERIFY_ITERATIONS;
+/// Random part of a chunk nonce; the rest is the chunk counter (u32) and the final-chunk flag.
+pub const NONCE_PREFIX_LEN: usize = NONCE_LEN - 5;
+const SUBKEY_INFO: &[u8] = b"qosmic-aead v1 subkeys";
+const KEYSTREAM_DOMAIN: &[u8] = b"qosmic-aead keystream";
+const KEY_SOURCE_RAW: u8 = 1;
+const KEY_SOURCE_PASSWORD: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AeadError {
+    AuthenticationFailed,
+    Truncated,
+    InvalidEncoding(&'static str),
+    UnsupportedVersion(u8),
+    InvalidParameter(String),
+    Io(String),}
+
+impl fmt::Display for AeadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AeadError::AuthenticationFailed => write!(f, "authentication failed (wrong key, wrong associated data or modified ciphertext)"),
+            AeadError::Truncated => write!(f, "ciphertext is truncated"),
+            AeadError::InvalidEncoding(what) => write!(f, "malformed {}", what),
+            AeadError::UnsupportedVersion(v) => write!(f, "unsupported encrypted file version {}", v),
+            AeadError::InvalidParameter(e) => write!(f, "invalid parameter: {}", e),
+            AeadError::Io(e) => write!(f, "I/O error: {}", e),}}}
+
+impl std::error::Error for AeadError {}
+
+impl From<io::Error> for AeadError {
+    fn from(e: io::Error) -> Self {
+        AeadError::Io(e.to_string())}}
+
+/// Per-message keys: `HKDF-Qosmic(salt = nonce, ikm = key)` split into a keystream key and a MAC key,
+/// so no two messages share a keystream unless they share a nonce.
+fn subkeys(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN]) -> (QosmicXof, HmacQosmicKey) {
+    let okm = hkdf(nonce, key, SUBKEY_INFO, 2 * KEY_LEN).expect("64 bytes is within the HKDF output limit");
+    (QosmicXof::new(KEYSTREAM_DOMAIN, &okm[..KEY_LEN]), HmacQosmicKey::new(&okm[KEY_LEN..]))}
+
+/// `HMAC-qosmic(aad || ciphertext || len(aad) (u64) || len(ciphertext) (u64))`, truncated to `TAG_LEN`.
+fn compute_tag(mac: &HmacQosmicKey, aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_LEN] {
+    let mut input = Vec::with_capacity(aad.len() + ciphertext.len() + 16);
+    input.extend_from_slice(aad);
+    input.extend_from_slice(ciphertext);
+    input.extend_from_slice(&(aad.len() as u64).to_be_bytes());
+    input.extend_from_slice(&(ciphertext.len() as u64).to_be_bytes());
+    let mut tag = [0u8; TAG_LEN];
+    tag.copy_from_slice(&mac.mac(&input)[..TAG_LEN]);
+    tag}
+
+fn apply_keystream(xof: &mut QosmicXof, data: &mut [u8]) {
+    let keystream = xof.squeeze(data.len());
+    for (byte, k) in data.iter_mut().zip(keystream) {
+        *byte ^= k;}}
+
+pub fn generate_key() -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    rand::rng().fill_bytes(&mut key);
+    key}
+
+pub fn generate_nonce() -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rng().fill_bytes(&mut nonce);
+    nonce}
+
+/// Encrypt-then-MAC: XORs `plaintext` with the qosmic XOF keystream and appends an HMAC-qosmic tag
+/// over `aad` and the ciphertext. Returns `ciphertext || tag`. A `(key, nonce)` pair must never be
+/// reused; 24-byte nonces are long enough to pick at random.
+///
+/// The keystream runs at about 2 MB/s, so this suits files and messages rather than bulk traffic.
+pub fn seal(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let (mut xof, mac) = subkeys(key, nonce);
+    let mut out = plaintext.to_vec();
+    apply_keystream(&mut xof, &mut out);
+    let tag = compute_tag(&mac, aad, &out);
+    out.extend_from_slice(&tag);
+    out}
+
+/// Checks the tag over `aad` and the ciphertext in constant time and only then decrypts.
+pub fn open(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, AeadError> {
+    if sealed.len() < TAG_LEN {
+        return Err(AeadError::Truncated);}
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - TAG_LEN);
+    let (mut xof, mac) = subkeys(key, nonce);
+    if !utils::constant_time_eq(&compute_tag(&mac, aad, ciphertext), tag) {
+        return Err(AeadError::AuthenticationFailed);}
+    let mut out = ciphertext.to_vec();
+    apply_keystream(&mut xof, &mut out);
+    Ok(out)}
+
+/// Where the key of an encrypted file comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    /// A 32-byte key held elsewhere, for example in an `aead` key file.
+    Raw,
+    /// `PBKDF2-HMAC-Qosmic(password, salt, iterations)`, with the parameters stored in the header.
+    Password { iterations: u32, salt: Vec<u8> },}
+
+/// Header of the chunked file format, which also serves as associated data for every chunk:
+/// `"QENC" || version (0x01) || key source (u8: 1 raw, 2 password)`, then for a password
+/// `iterations (u32) || salt_len (u8) || salt`, then `chunk_size (u32) || nonce prefix (19 bytes)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamHeader {
+    pub key_source: KeySource,
+    pub chunk_size: u32,
+    pub nonce_prefix: [u8; NONCE_PREFIX_LEN],}
+
+impl StreamHeader {
+    /// A header with a fresh random nonce prefix.
+    pub fn new(key_source: KeySource, chunk_size: u32) -> Result<Self, AeadError> {
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(AeadError::InvalidParameter(format!("chunk size {} is not in 1..={}", chunk_size, MAX_CHUNK_SIZE)));}
+        if let KeySource::Password { iterations, salt } = &key_source
+            && (*iterations == 0 || *iterations > MAX_PASSWORD_ITERATIONS || salt.is_empty() || salt.len() > u8::MAX as usize) {
+            return Err(AeadError::InvalidParameter(format!("password key needs 1..={} iterations and a 1-255 byte salt", MAX_PASSWORD_ITERATIONS)));}
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        rand::rng().fill_bytes(&mut nonce_prefix);
+        Ok(StreamHeader { key_source, chunk_size, nonce_prefix })}
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = STREAM_MAGIC.to_vec();
+        out.push(STREAM_VERSION);
+        match &self.key_source {
+            KeySource::Raw => out.push(KEY_SOURCE_RAW),
+            KeySource::Password { iterations, salt } => {
+                out.push(KEY_SOURCE_PASSWORD);
+                out.extend_from_slice(&iterations.to_be_bytes());
+                out.push(salt.len() as u8);
+                out.extend_from_slice(salt);},}
+        out.extend_from_slice(&self.chunk_size.to_be_bytes());
+        out.extend_from_slice(&self.nonce_prefix);
+        out}
+
+    /// Reads a header from the start of `reader`, leaving it positioned at the first chunk.
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, AeadError> {
+        let truncated = |e: io::Error| if e.kind() == io::ErrorKind::UnexpectedEof { AeadError::InvalidEncoding("encrypted file header") } else { e.into() };
+        let mut fixed = [0u8; 6];
+        reader.read_exact(&mut fixed).map_err(truncated)?;
+        if &fixed[..4] != STREAM_MAGIC {
+            return Err(AeadError::InvalidEncoding("encrypted file header"));}
+        if fixed[4] != STREAM_VERSION {
+            return Err(AeadError::UnsupportedVersion(fixed[4]));}
+        let key_source = match fixed[5] {
+            KEY_SOURCE_RAW => KeySource::Raw,
+            KEY_SOURCE_PASSWORD => {
+                let mut params = [0u8; 5];
+                reader.read_exact(&mut params).map_err(truncated)?;
+                let iterations = u32::from_be_bytes(params[..4].try_into().unwrap());
+                if iterations > MAX_PASSWORD_ITERATIONS {
+                    return Err(AeadError::InvalidParameter(format!("iterations={} exceeds {}", iterations, MAX_PASSWORD_ITERATIONS)));}
+                let mut salt = vec![0u8; params[4] as usize];
+                reader.read_exact(&mut salt).map_err(truncated)?;
+                if iterations == 0 || salt.is_empty() {
+                    return Err(AeadError::InvalidEncoding("encrypted file header"));}
+                KeySource::Password { iterations, salt }},
+            _ => return Err(AeadError::InvalidEncoding("encrypted file header")),};
+        let mut rest = [0u8; 4 + NONCE_PREFIX_LEN];
+        reader.read_exact(&mut rest).map_err(truncated)?;
+        let chunk_size = u32::from_be_bytes(rest[..4].try_into().unwrap());
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(AeadError::InvalidEncoding("encrypted file header"));}
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        nonce_prefix.copy_from_slice(&rest[4..]);
+        Ok(StreamHeader { key_source, chunk_size, nonce_prefix })}
+
+    /// The file key for a password-protected header.
+    pub fn password_key(&self, password: &[u8]) -> Result<[u8; KEY_LEN], AeadError> {
+        match &self.key_source {
+            KeySource::Password { iterations, salt } => {
+                let mut key = [0u8; KEY_LEN];
+                key.copy_from_slice(&pbkdf2_hmac_qosmic(password, salt, *iterations, KEY_LEN));
+                Ok(key)},
+            KeySource::Raw => Err(AeadError::InvalidParameter("file is encrypted with a key, not a password".to_string())),}}
+
+    fn chunk_nonce(&self, index: u32, last: bool) -> [u8; NONCE_LEN] {
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[..NONCE_PREFIX_LEN].copy_from_slice(&self.nonce_prefix);
+        nonce[NONCE_PREFIX_LEN..NONCE_LEN - 1].copy_from_slice(&index.to_be_bytes());
+        nonce[NONCE_LEN - 1] = last as u8;
+        nonce}}
+
+/// Reads until `buf` is full or the reader is exhausted, returning the number of bytes read.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(e),}}
+    Ok(filled)}
+
+/// Writes `header` and then `reader` as sealed chunks. Chunk `i` is sealed under the nonce
+/// `prefix || i (u32) || last (u8)` with `header || aad` as associated data. Every chunk but the
+/// last holds exactly `chunk_size` bytes and the last one is shorter (possibly empty), so chunks
+/// cannot be reordered, dropped or cut off without detection. Returns the plaintext length.
+pub fn encrypt_stream<R: Read, W: Write>(key: &[u8; KEY_LEN], header: &StreamHeader, aad: &[u8], reader: &mut R, writer: &mut W) -> Result<u64, AeadError> {
+    let header_bytes = header.to_bytes();
+    let mut chunk_aad = header_bytes.clone();
+    chunk_aad.extend_from_slice(aad);
+    writer.write_all(&header_bytes)?;
+    let mut buf = vec![0u8; header.chunk_size as usize];
+    let mut total = 0u64;
+    for index in 0u32.. {
+        let n = read_full(reader, &mut buf)?;
+        let last = n < buf.len();
+        writer.write_all(&seal(key, &header.chunk_nonce(index, last), &chunk_aad, &buf[..n]))?;
+        total += n as u64;
+        if last {
+            debug!("Encrypted {} bytes in {} chunk(s)", total, index as u64 + 1);
+            return Ok(total);}
+        if index == u32::MAX {
+            break;}}
+    Err(AeadError::InvalidParameter("input has too many chunks for the chunk size".to_string()))}
+
+/// Decrypts the chunks that follow `header` (already read from `reader`) into `writer`. Each chunk
+/// is written only after its tag checks, but an error in a later chunk leaves the earlier ones
+/// written, so callers should write to a temporary file and keep it only on success.
+pub fn decrypt_stream<R: Read, W: Write>(key: &[u8; KEY_LEN], header: &StreamHeader, aad: &[u8], reader: &mut R, writer: &mut W) -> Result<u64, AeadError> {
+    let mut chunk_aad = header.to_bytes();
+    chunk_aad.extend_from_slice(aad);
+    let mut buf = vec![0u8; header.chunk_size as usize + TAG_LEN];
+    let mut total = 0u64;
+    for index in 0u32.. {
+        let n = read_full(reader, &mut buf)?;
+        if n < TAG_LEN {
+            return Err(AeadError::Truncated);}
+        let last = n < buf.len();
+        let plaintext = open(key, &header.chunk_nonce(index, last), &chunk_aad, &buf[..n])?;
+        writer.write_all(&plaintext)?;
+        total += plaintext.len() as u64;
+        if last {
+            debug!("Decrypted {} bytes in {} chunk(s)", total, index as u64 + 1);
+            return Ok(total);}
+        if index == u32::MAX {
+            break;}}
+    Err(AeadError::InvalidEncoding("encrypted file (too many chunks)"))}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; KEY_LEN] = [7; KEY_LEN];
+    const CHUNK: u32 = 16;
+
+    fn header() -> StreamHeader {
+        StreamHeader::new(KeySource::Raw, CHUNK).unwrap()}
+
+    fn encrypt(header: &StreamHeader, plaintext: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        assert_eq!(encrypt_stream(&KEY, header, b"aad", &mut &plaintext[..], &mut out).unwrap(), plaintext.len() as u64);
+        out}
+
+    fn decrypt(bytes: &[u8]) -> Result<Vec<u8>, AeadError> {
+        let mut reader = bytes;
+        let header = StreamHeader::read_from(&mut reader)?;
+        let mut out = Vec::new();
+        decrypt_stream(&KEY, &header, b"aad", &mut reader, &mut out)?;
+        Ok(out)}
+
+    #[test]
+    fn seal_open_round_trip_and_rejections() {
+        let nonce = [1u8; NONCE_LEN];
+        let sealed = seal(&KEY, &nonce, b"header", b"attack at dawn");
+        assert_eq!(sealed.len(), 14 + TAG_LEN);
+        assert_eq!(open(&KEY, &nonce, b"header", &sealed).unwrap(), b"attack at dawn");
+        assert_eq!(open(&[8; KEY_LEN], &nonce, b"header", &sealed), Err(AeadError::AuthenticationFailed));
+        assert_eq!(open(&KEY, &[2; NONCE_LEN], b"header", &sealed), Err(AeadError::AuthenticationFailed));
+        assert_eq!(open(&KEY, &nonce, b"other", &sealed), Err(AeadError::AuthenticationFailed));
+        let mut flipped = sealed.clone();
+        flipped[0] ^= 1;
+        assert_eq!(open(&KEY, &nonce, b"header", &flipped), Err(AeadError::AuthenticationFailed));
+        assert_eq!(open(&KEY, &nonce, b"header", &sealed[..TAG_LEN - 1]), Err(AeadError::Truncated));
+        assert_eq!(open(&KEY, &nonce, b"", &seal(&KEY, &nonce, b"", b"")).unwrap(), b"");}
+
+    #[test]
+    fn stream_round_trips_at_chunk_boundaries() {
+        let header = header();
+        for len in [0, 1, 15, 16, 17, 32, 53] {
+            let plaintext: Vec<u8> = (0..len as u8).collect();
+            let encrypted = encrypt(&header, &plaintext);
+            let chunks = len / CHUNK as usize + 1;
+            assert_eq!(encrypted.len(), header.to_bytes().len() + len + chunks * TAG_LEN);
+            assert_eq!(decrypt(&encrypted).unwrap(), plaintext, "len = {}", len);}
+        let password = StreamHeader::new(KeySource::Password { iterations: 2, salt: b"salt".to_vec() }, CHUNK).unwrap();
+        let mut bytes = &password.to_bytes()[..];
+        assert_eq!(StreamHeader::read_from(&mut bytes).unwrap(), password);
+        assert!(password.password_key(b"pw").unwrap() != password.password_key(b"pW").unwrap());
+        assert!(header.password_key(b"pw").is_err());}
+
+    #[test]
+    fn excessive_password_iterations_are_rejected() {
+        let password = StreamHeader::new(KeySource::Password { iterations: 2, salt: b"salt".to_vec() }, CHUNK).unwrap();
+        let mut bytes = password.to_bytes();
+        bytes[6..10].copy_from_slice(&u32::MAX.to_be_bytes());
+        let expected = AeadError::InvalidParameter(format!("iterations={} exceeds {}", u32::MAX, MAX_PASSWORD_ITERATIONS));
+        assert_eq!(StreamHeader::read_from(&mut &bytes[..]), Err(expected));
+        bytes[6..10].copy_from_slice(&MAX_PASSWORD_ITERATIONS.to_be_bytes());
+        assert!(StreamHeader::read_from(&mut &bytes[..]).is_ok());
+        let too_many = KeySource::Password { iterations: MAX_PASSWORD_ITERATIONS + 1, salt: b"salt".to_vec() };
+        assert!(matches!(StreamHeader::new(too_many, CHUNK), Err(AeadError::InvalidParameter(_))));}
+
+    #[test]
+    fn reordered_truncated_and_extended_streams_are_rejected() {
+        let header = header();
+        let header_len = header.to_bytes().len();
+        let sealed_chunk = CHUNK as usize + TAG_LEN;
+        let plaintext: Vec<u8> = (0..53).collect();
+        let encrypted = encrypt(&header, &plaintext);
+
+        let mut reordered = encrypted.clone();
+        let (first, second) = (header_len..header_len + sealed_chunk, header_len + sealed_chunk..header_len + 2 * sealed_chunk);
+        let chunk0 = encrypted[first.clone()].to_vec();
+        reordered.copy_within(second.clone(), first.start);
+        reordered[second].copy_from_slice(&chunk0);
+        assert_eq!(decrypt(&reordered), Err(AeadError::AuthenticationFailed));
+
+        // Cut inside the final chunk, or cleanly after a full chunk so the final one is missing.
+        assert_eq!(decrypt(&encrypted[..encrypted.len() - 1]), Err(AeadError::AuthenticationFailed));
+        assert_eq!(decrypt(&encrypted[..header_len + 3 * sealed_chunk]), Err(AeadError::Truncated));
+        assert_eq!(decrypt(&encrypted[..header_len + 2 * sealed_chunk + 5]), Err(AeadError::Truncated));
+
+        // Bytes after the final chunk are read into it and break its tag.
+        let mut extended = encrypted.clone();
+        extended.push(0);
+        assert_eq!(decrypt(&extended), Err(AeadError::AuthenticationFailed));
+        let mut relabeled = encrypted.clone();
+        relabeled[header_len - 1] ^= 1;
+        assert_eq!(decrypt(&relabeled), Err(AeadError::AuthenticationFailed));
+        assert_eq!(decrypt(&encrypted[..header_len - 1]), Err(AeadError::InvalidEncoding("encrypted file header")));}}
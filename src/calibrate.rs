@@ -0,0 +1,136 @@
+// src/calibrate.rs
+use crate::core::{pbkdf2_hmac_qosmic, KdfParams, MAX_VERIFY_ITERATIONS, PBKDF2_ALGORITHM, PBKDF2_VERSION};
+use crate::memhard::{memory_hard_kdf, MemoryHardParams, MAX_VERIFY_MEMORY_KIB, MAX_VERIFY_TIME_COST, MEMHARD_ALGORITHM, MEMHARD_VERSION, MEMORY_KIB};
+use crate::phc::PasswordHashError;
+use log::{debug, info};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+const PROBE_PASSWORD: &[u8] = b"qosmic calibration password";
+const PROBE_SALT: &[u8] = b"qosmic-calibrate";
+const MIN_PROBE: Duration = Duration::from_millis(25);
+const PROBE_ITERATIONS: u32 = 1000;
+const MIN_ITERATIONS: u32 = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CalibratedParams {
+    Pbkdf2(KdfParams),
+    MemoryHard(MemoryHardParams),}
+
+impl CalibratedParams {
+    /// The matching `--password` flags, e.g. `--kdf pbkdf2 --iterations 120000 --length 64`.
+    pub fn cli_flags(&self) -> String {
+        match self {
+            CalibratedParams::Pbkdf2(p) => format!("--kdf pbkdf2 --iterations {} --length {}", p.iterations, p.output_len),
+            CalibratedParams::MemoryHard(p) => format!("--kdf mem --memory {} --time {} --parallelism {} --length {}",
+                p.memory_kib, p.time_cost, p.parallelism, p.output_len),}}}
+
+/// Formats as the parameter prefix of a PHC string, `$alg$v=N$k=v,...`, without salt or hash.
+impl fmt::Display for CalibratedParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (algorithm, version, params) = match self {
+            CalibratedParams::Pbkdf2(p) => (PBKDF2_ALGORITHM, PBKDF2_VERSION, p.phc_params()),
+            CalibratedParams::MemoryHard(p) => (MEMHARD_ALGORITHM, MEMHARD_VERSION, p.phc_params()),};
+        let joined: Vec<String> = params.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        write!(f, "${}$v={}${}", algorithm, version, joined.join(","))}}
+
+/// Recommended parameters plus the wall-clock time one derivation with them took on this machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Calibration {
+    pub params: CalibratedParams,
+    pub measured: Duration,}
+
+fn time_it<F: FnOnce()>(f: F) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()}
+
+fn scale(value: u64, measured: Duration, target: Duration) -> u64 {
+    let nanos = measured.as_nanos().max(1);
+    (value as u128 * target.as_nanos() / nanos).min(u64::MAX as u128) as u64}
+
+/// Rounds down to two significant digits, so recommendations read as 3500 or 120000 rather than 3561 or 123456.
+fn round_down_significant(n: u32) -> u32 {
+    let mut step = 1;
+    while n / step >= 100 {
+        step *= 10;}
+    n / step * step}
+
+/// Benchmarks PBKDF2-HMAC-Qosmic and returns the iteration count (rounded down to two significant
+/// digits, never below 1000 or above `MAX_VERIFY_ITERATIONS`) that takes about `target` with
+/// `base.output_len`. Other fields of `base` are carried over.
+pub fn calibrate_pbkdf2(target: Duration, base: &KdfParams) -> Result<Calibration, PasswordHashError> {
+    base.validate()?;
+    let mut probe = PROBE_ITERATIONS;
+    let mut elapsed = time_it(|| { pbkdf2_hmac_qosmic(PROBE_PASSWORD, PROBE_SALT, probe, base.output_len); });
+    while elapsed < MIN_PROBE && elapsed < target && probe < u32::MAX / 2 {
+        probe *= 2;
+        elapsed = time_it(|| { pbkdf2_hmac_qosmic(PROBE_PASSWORD, PROBE_SALT, probe, base.output_len); });}
+    debug!("PBKDF2 probe: {} iterations in {:?}", probe, elapsed);
+    let estimate = scale(probe as u64, elapsed, target).min(u32::MAX as u64) as u32;
+    let iterations = round_down_significant(estimate).clamp(MIN_ITERATIONS, MAX_VERIFY_ITERATIONS);
+    let params = KdfParams { iterations, ..base.clone() };
+    let measured = time_it(|| { pbkdf2_hmac_qosmic(PROBE_PASSWORD, PROBE_SALT, iterations, params.output_len); });
+    info!("PBKDF2 calibration: {} iterations took {:?} (target {:?})", iterations, measured, target);
+    Ok(Calibration { params: CalibratedParams::Pbkdf2(params), measured })}
+
+/// The memory ceiling for calibration: `max_memory_kib`, but never above `MAX_VERIFY_MEMORY_KIB`
+/// (so the recommended hash can be verified) or below the minimum for `parallelism` lanes.
+fn memory_ceiling(max_memory_kib: u32, parallelism: u32) -> u32 {
+    max_memory_kib.min(MAX_VERIFY_MEMORY_KIB).max(8 * parallelism)}
+
+/// Benchmarks the memory-hard KDF and picks the largest memory cost (at most `max_memory_kib`,
+/// rounded down to a multiple of 1024 KiB where possible) that fits `target` with
+/// `base.time_cost` passes. If the memory ceiling is reached first, extra passes are added instead,
+/// up to `MAX_VERIFY_TIME_COST`.
+pub fn calibrate_memory_hard(target: Duration, base: &MemoryHardParams, max_memory_kib: u32) -> Result<Calibration, PasswordHashError> {
+    base.validate()?;
+    base.check_verify_limit()?;
+    let min_memory = 8 * base.parallelism;
+    let max_memory = memory_ceiling(max_memory_kib, base.parallelism);
+    let mut probe = MemoryHardParams { memory_kib: MEMORY_KIB.clamp(min_memory, max_memory), time_cost: 1, salt: None, ..base.clone() };
+    let mut elapsed = time_it(|| { let _ = memory_hard_kdf(PROBE_PASSWORD, PROBE_SALT, &probe); });
+    while elapsed < MIN_PROBE && elapsed < target && probe.memory_kib < max_memory {
+        probe.memory_kib = probe.memory_kib.saturating_mul(2).min(max_memory);
+        elapsed = time_it(|| { let _ = memory_hard_kdf(PROBE_PASSWORD, PROBE_SALT, &probe); });}
+    debug!("Memory-hard probe: m={} KiB, t=1 in {:?}", probe.memory_kib, elapsed);
+    let kib_passes = scale(probe.memory_kib as u64, elapsed, target);
+    let time_cost = base.time_cost;
+    let capped = kib_passes / time_cost as u64 > max_memory as u64;
+    let mut memory_kib = (kib_passes / time_cost as u64).min(max_memory as u64) as u32;
+    let step = if memory_kib >= 1024 { 1024 } else { min_memory };
+    memory_kib = (memory_kib / step * step).max(min_memory);
+    let time_cost = if capped {
+        ((kib_passes / memory_kib as u64).min(MAX_VERIFY_TIME_COST as u64) as u32).max(time_cost)
+    } else {
+        time_cost};
+    let params = MemoryHardParams { memory_kib, time_cost, ..base.clone() };
+    params.validate()?;
+    let mut result = Ok(Vec::new());
+    let measured = time_it(|| { result = memory_hard_kdf(PROBE_PASSWORD, PROBE_SALT, &params); });
+    result?;
+    info!("Memory-hard calibration: m={} KiB, t={} took {:?} (target {:?})", memory_kib, time_cost, measured, target);
+    Ok(Calibration { params: CalibratedParams::MemoryHard(params), measured })}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::KdfError;
+
+    #[test]
+    fn memory_ceiling_is_clamped_to_the_verification_limit() {
+        assert_eq!(memory_ceiling(u32::MAX, 1), MAX_VERIFY_MEMORY_KIB);
+        assert_eq!(memory_ceiling(MAX_VERIFY_MEMORY_KIB + 1, 4), MAX_VERIFY_MEMORY_KIB);
+        assert_eq!(memory_ceiling(4096, 1), 4096);
+        assert_eq!(memory_ceiling(0, 4), 32);}
+
+    #[test]
+    fn memory_hard_calibration_stays_verifiable() {
+        let base = MemoryHardParams { time_cost: 1, ..MemoryHardParams::default() };
+        let calibration = calibrate_memory_hard(Duration::from_millis(1), &base, u32::MAX).unwrap();
+        let CalibratedParams::MemoryHard(params) = calibration.params else { panic!("expected memory-hard parameters") };
+        assert!(params.memory_kib <= MAX_VERIFY_MEMORY_KIB);
+        assert!(params.check_verify_limit().is_ok());
+        let base = MemoryHardParams { time_cost: MAX_VERIFY_TIME_COST + 1, ..MemoryHardParams::default() };
+        assert!(matches!(calibrate_memory_hard(Duration::from_millis(1), &base, 4096),
+            Err(PasswordHashError::Kdf(KdfError::CostLimitExceeded { param: "t", .. }))));}}
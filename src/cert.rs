@@ -0,0 +1,492 @@
+// src/cert.rs
+use crate::core::qosmic_digest;
+use crate::xmss::{self, SignatureError, XmssPrivateKey, XmssPublicKey, XmssSignature};
+use hex;
+use log::{debug, info};
+use rand::RngCore;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const CERT_MAGIC: &[u8; 4] = b"QCRT";
+pub const CERT_VERSION: u8 = 1;
+pub const KEY_ID_LEN: usize = 16;
+/// File extension of certificates picked up by `TrustStore::from_dir`.
+pub const CERT_FILE_EXTENSION: &str = "qcert";
+/// Longest chain `verify_chain` will build, counting the leaf and the root.
+pub const MAX_CHAIN_LEN: usize = 8;
+/// Extension marking a CA certificate. Its value is one byte: the number of intermediate CAs
+/// allowed below it, or `0xff` for no limit.
+pub const EXT_CA: &str = "ca";
+const UNLIMITED_PATH_LEN: u8 = 0xff;
+const PUBLIC_KEY_LEN: usize = 2 + 2 * xmss::N;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CertError {
+    InvalidEncoding(&'static str),
+    UnsupportedVersion(u8),
+    NotYetValid(String),
+    Expired(String),
+    BadSignature(String),
+    IssuerKeyMismatch,
+    NotCa(String),
+    PathLenExceeded(String),
+    UnknownCriticalExtension(String),
+    UntrustedIssuer(String),
+    ChainTooLong,
+    Signature(SignatureError),
+    Io(String),}
+
+impl fmt::Display for CertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertError::InvalidEncoding(what) => write!(f, "malformed {}", what),
+            CertError::UnsupportedVersion(v) => write!(f, "unsupported certificate version {}", v),
+            CertError::NotYetValid(subject) => write!(f, "certificate for '{}' is not yet valid", subject),
+            CertError::Expired(subject) => write!(f, "certificate for '{}' has expired", subject),
+            CertError::BadSignature(subject) => write!(f, "signature on certificate for '{}' does not verify", subject),
+            CertError::IssuerKeyMismatch => write!(f, "signing key does not match the certificate's issuer key id"),
+            CertError::NotCa(subject) => write!(f, "certificate for '{}' is not a CA certificate", subject),
+            CertError::PathLenExceeded(subject) => write!(f, "too many intermediate CAs below '{}'", subject),
+            CertError::UnknownCriticalExtension(id) => write!(f, "unknown critical extension '{}'", id),
+            CertError::UntrustedIssuer(issuer) => write!(f, "no trusted or supplied certificate for issuer '{}'", issuer),
+            CertError::ChainTooLong => write!(f, "certificate chain is longer than {}", MAX_CHAIN_LEN),
+            CertError::Signature(e) => write!(f, "signing failed: {}", e),
+            CertError::Io(e) => write!(f, "I/O error: {}", e),}}}
+
+impl std::error::Error for CertError {}
+
+impl From<SignatureError> for CertError {
+    fn from(e: SignatureError) -> Self {
+        CertError::Signature(e)}}
+
+impl From<std::io::Error> for CertError {
+    fn from(e: std::io::Error) -> Self {
+        CertError::Io(e.to_string())}}
+
+/// Identifies a public key: the first 16 bytes of the qosmic digest of its encoding.
+pub fn key_id(public_key: &XmssPublicKey) -> [u8; KEY_ID_LEN] {
+    let mut id = [0u8; KEY_ID_LEN];
+    id.copy_from_slice(&qosmic_digest(&public_key.to_bytes())[..KEY_ID_LEN]);
+    id}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    pub id: String,
+    /// A verifier that does not understand a critical extension must reject the certificate.
+    pub critical: bool,
+    pub value: Vec<u8>,}
+
+/// The signed part of a certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TbsCertificate {
+    pub serial: u64,
+    pub subject: String,
+    pub issuer: String,
+    pub issuer_key_id: [u8; KEY_ID_LEN],
+    /// Validity window in Unix seconds, both ends inclusive.
+    pub not_before: u64,
+    pub not_after: u64,
+    pub public_key: XmssPublicKey,
+    pub extensions: Vec<Extension>,}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    pub tbs: TbsCertificate,
+    pub signature: XmssSignature,}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    out.extend_from_slice(s.as_bytes());}
+
+/// Cursor over an encoded certificate; every read fails with `InvalidEncoding` past the end.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CertError> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len()).ok_or(CertError::InvalidEncoding("certificate"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)}
+
+    fn u8(&mut self) -> Result<u8, CertError> {
+        Ok(self.take(1)?[0])}
+
+    fn u16(&mut self) -> Result<u16, CertError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))}
+
+    fn u64(&mut self) -> Result<u64, CertError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))}
+
+    fn string(&mut self) -> Result<String, CertError> {
+        let len = self.u16()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| CertError::InvalidEncoding("certificate name"))}}
+
+impl TbsCertificate {
+    /// A self-signed certificate body for `public_key` with a random serial, valid from `not_before`
+    /// for `validity_secs`. Use `issued_by` to have another certificate's key sign it instead.
+    pub fn new(subject: &str, public_key: XmssPublicKey, not_before: u64, validity_secs: u64) -> Self {
+        TbsCertificate {
+            serial: rand::rng().next_u64(),
+            subject: subject.to_string(),
+            issuer: subject.to_string(),
+            issuer_key_id: key_id(&public_key),
+            not_before,
+            not_after: not_before.saturating_add(validity_secs),
+            public_key,
+            extensions: Vec::new(),}}
+
+    pub fn issued_by(mut self, issuer: &Certificate) -> Self {
+        self.issuer = issuer.tbs.subject.clone();
+        self.issuer_key_id = issuer.key_id();
+        self}
+
+    pub fn with_serial(mut self, serial: u64) -> Self {
+        self.serial = serial;
+        self}
+
+    /// Marks the certificate as a CA. `max_path_len` limits the intermediate CAs below it.
+    pub fn with_ca(self, max_path_len: Option<u8>) -> Self {
+        let value = max_path_len.map_or(UNLIMITED_PATH_LEN, |n| n.min(UNLIMITED_PATH_LEN - 1));
+        self.with_extension(EXT_CA, true, &[value])}
+
+    /// Adds an extension, replacing any existing one with the same id.
+    pub fn with_extension(mut self, id: &str, critical: bool, value: &[u8]) -> Self {
+        self.extensions.retain(|ext| ext.id != id);
+        self.extensions.push(Extension { id: id.to_string(), critical, value: value.to_vec() });
+        self}
+
+    /// `"QCRT"[version][serial u64][subject][issuer][issuer key id][not_before u64][not_after u64]`
+    /// `[public key][extension count u8]{[id len u8][id][critical u8][value len u16][value]}`,
+    /// with strings as `[len u16][UTF-8]` and all integers big-endian. Only meaningful for a body
+    /// that passes `validate`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(CERT_MAGIC);
+        out.push(CERT_VERSION);
+        out.extend_from_slice(&self.serial.to_be_bytes());
+        put_str(&mut out, &self.subject);
+        put_str(&mut out, &self.issuer);
+        out.extend_from_slice(&self.issuer_key_id);
+        out.extend_from_slice(&self.not_before.to_be_bytes());
+        out.extend_from_slice(&self.not_after.to_be_bytes());
+        out.extend_from_slice(&self.public_key.to_bytes());
+        out.push(self.extensions.len() as u8);
+        for ext in &self.extensions {
+            out.push(ext.id.len() as u8);
+            out.extend_from_slice(ext.id.as_bytes());
+            out.push(ext.critical as u8);
+            out.extend_from_slice(&(ext.value.len() as u16).to_be_bytes());
+            out.extend_from_slice(&ext.value);}
+        out}
+
+    fn read(reader: &mut Reader) -> Result<Self, CertError> {
+        if reader.take(4)? != CERT_MAGIC {
+            return Err(CertError::InvalidEncoding("certificate"));}
+        let version = reader.u8()?;
+        if version != CERT_VERSION {
+            return Err(CertError::UnsupportedVersion(version));}
+        let serial = reader.u64()?;
+        let subject = reader.string()?;
+        let issuer = reader.string()?;
+        let issuer_key_id = reader.take(KEY_ID_LEN)?.try_into().unwrap();
+        let not_before = reader.u64()?;
+        let not_after = reader.u64()?;
+        let public_key = XmssPublicKey::from_bytes(reader.take(PUBLIC_KEY_LEN)?).map_err(|_| CertError::InvalidEncoding("certificate public key"))?;
+        let count = reader.u8()?;
+        let mut extensions: Vec<Extension> = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let id_len = reader.u8()? as usize;
+            let id = String::from_utf8(reader.take(id_len)?.to_vec()).map_err(|_| CertError::InvalidEncoding("certificate extension"))?;
+            let critical = match reader.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(CertError::InvalidEncoding("certificate extension")),};
+            let value_len = reader.u16()? as usize;
+            let value = reader.take(value_len)?.to_vec();
+            if extensions.iter().any(|ext| ext.id == id) {
+                return Err(CertError::InvalidEncoding("certificate extension"));}
+            extensions.push(Extension { id, critical, value });}
+        Ok(TbsCertificate { serial, subject, issuer, issuer_key_id, not_before, not_after, public_key, extensions })}
+
+    /// The to-be-signed hash: the qosmic digest of the encoded body. The issuer's XMSS key signs this.
+    pub fn digest(&self) -> [u8; 64] {
+        qosmic_digest(&self.to_bytes())}
+
+    /// Checks that every field fits its length prefix: names up to 65535 bytes, at most 255
+    /// extensions, extension ids up to 255 bytes and values up to 65535 bytes. `to_bytes` would
+    /// otherwise truncate the prefixes, so `issue` runs this before anything is signed.
+    pub fn validate(&self) -> Result<(), CertError> {
+        if self.subject.len() > u16::MAX as usize || self.issuer.len() > u16::MAX as usize {
+            return Err(CertError::InvalidEncoding("certificate name (too long)"));}
+        if self.extensions.len() > u8::MAX as usize {
+            return Err(CertError::InvalidEncoding("certificate extensions (too many)"));}
+        if self.extensions.iter().any(|ext| ext.id.len() > u8::MAX as usize || ext.value.len() > u16::MAX as usize) {
+            return Err(CertError::InvalidEncoding("certificate extension (too long)"));}
+        Ok(())}
+
+    /// Checks that the body is encodable and that `signing_key` is the issuer's.
+    fn check_signing_key(&self, signing_key: &XmssPublicKey) -> Result<(), CertError> {
+        self.validate()?;
+        if key_id(signing_key) != self.issuer_key_id {
+            return Err(CertError::IssuerKeyMismatch);}
+        Ok(())}}
+
+/// Signs `tbs` with the issuer's in-memory key. The caller must persist the key's advanced state.
+pub fn issue(tbs: TbsCertificate, issuer_key: &mut XmssPrivateKey) -> Result<Certificate, CertError> {
+    tbs.check_signing_key(&issuer_key.public_key())?;
+    let signature = issuer_key.sign(&tbs.digest())?;
+    info!("Issued certificate serial {} for '{}' signed by '{}'", tbs.serial, tbs.subject, tbs.issuer);
+    Ok(Certificate { tbs, signature })}
+
+/// Like `issue`, but loads the issuer key from `key_path` and writes its advanced state back
+/// before returning the certificate, holding the key's `StateLock` throughout. Also returns the
+/// signatures left on the key.
+pub fn issue_with_state_file(tbs: TbsCertificate, key_path: &Path) -> Result<(Certificate, u32), CertError> {
+    let _lock = xmss::StateLock::acquire(key_path)?;
+    let mut key = XmssPrivateKey::from_bytes(&fs::read(key_path)?)?;
+    let certificate = issue(tbs, &mut key)?;
+    xmss::write_state_atomically(key_path, &key.to_bytes())?;
+    Ok((certificate, key.remaining_signatures()))}
+
+impl Certificate {
+    /// `[TBS][signature length u32][XMSS signature]`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.tbs.to_bytes();
+        let signature = self.signature.to_bytes();
+        out.extend_from_slice(&(signature.len() as u32).to_be_bytes());
+        out.extend_from_slice(&signature);
+        out}
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CertError> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let tbs = TbsCertificate::read(&mut reader)?;
+        let signature_len = u32::from_be_bytes(reader.take(4)?.try_into().unwrap()) as usize;
+        let signature = XmssSignature::from_bytes(reader.take(signature_len)?).map_err(|_| CertError::InvalidEncoding("certificate signature"))?;
+        if reader.pos != bytes.len() {
+            return Err(CertError::InvalidEncoding("certificate"));}
+        Ok(Certificate { tbs, signature })}
+
+    pub fn key_id(&self) -> [u8; KEY_ID_LEN] {
+        key_id(&self.tbs.public_key)}
+
+    pub fn extension(&self, id: &str) -> Option<&Extension> {
+        self.tbs.extensions.iter().find(|ext| ext.id == id)}
+
+    pub fn is_ca(&self) -> bool {
+        self.extension(EXT_CA).is_some()}
+
+    /// Intermediate CAs allowed below this CA, `None` when unlimited or not a CA.
+    pub fn max_path_len(&self) -> Option<u8> {
+        self.extension(EXT_CA).and_then(|ext| ext.value.first().copied()).filter(|&n| n != UNLIMITED_PATH_LEN)}
+
+    pub fn is_self_signed(&self) -> bool {
+        self.tbs.issuer == self.tbs.subject && self.tbs.issuer_key_id == self.key_id()}
+
+    pub fn verify_signature(&self, issuer_key: &XmssPublicKey) -> bool {
+        key_id(issuer_key) == self.tbs.issuer_key_id && issuer_key.verify(&self.tbs.digest(), &self.signature)}
+
+    pub fn check_validity(&self, now: u64) -> Result<(), CertError> {
+        if now < self.tbs.not_before {
+            return Err(CertError::NotYetValid(self.tbs.subject.clone()));}
+        if now > self.tbs.not_after {
+            return Err(CertError::Expired(self.tbs.subject.clone()));}
+        Ok(())}
+
+    fn check_extensions(&self) -> Result<(), CertError> {
+        match self.tbs.extensions.iter().find(|ext| ext.critical && ext.id != EXT_CA) {
+            Some(ext) => Err(CertError::UnknownCriticalExtension(ext.id.clone())),
+            None => Ok(()),}}
+
+    fn issued(&self, child: &Certificate) -> bool {
+        self.tbs.subject == child.tbs.issuer && self.key_id() == child.tbs.issuer_key_id}}
+
+/// Self-signed CA certificates trusted as chain anchors.
+#[derive(Debug, Clone, Default)]
+pub struct TrustStore {
+    roots: Vec<Certificate>,}
+
+impl TrustStore {
+    pub fn new() -> Self {
+        TrustStore { roots: Vec::new() }}
+
+    /// Adds a root. It must be a self-signed CA certificate whose signature verifies.
+    pub fn add(&mut self, root: Certificate) -> Result<(), CertError> {
+        if !root.is_self_signed() || !root.verify_signature(&root.tbs.public_key) {
+            return Err(CertError::BadSignature(root.tbs.subject.clone()));}
+        if !root.is_ca() {
+            return Err(CertError::NotCa(root.tbs.subject.clone()));}
+        if !self.roots.contains(&root) {
+            self.roots.push(root);}
+        Ok(())}
+
+    /// Loads every `*.qcert` file in `dir`. A file that is not a valid root is an error, so a
+    /// broken trust store is noticed rather than silently shrinking.
+    pub fn from_dir(dir: &Path) -> Result<Self, CertError> {
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == CERT_FILE_EXTENSION))
+            .collect();
+        paths.sort();
+        let mut store = TrustStore::new();
+        for path in paths {
+            debug!("Loading trusted root {}", path.display());
+            store.add(Certificate::from_bytes(&fs::read(&path)?)?)?;}
+        info!("Loaded {} trusted root(s) from {}", store.roots.len(), dir.display());
+        Ok(store)}
+
+    pub fn roots(&self) -> &[Certificate] {
+        &self.roots}
+
+    pub fn len(&self) -> usize {
+        self.roots.len()}
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()}}
+
+/// Builds and checks a chain from `leaf` to a root in `store`, drawing intermediate CA certificates
+/// from `intermediates`. Every certificate must be valid at `now` (Unix seconds), carry no unknown
+/// critical extension and be signed by the next one up; every issuer must be a CA whose path length
+/// allows the CAs below it. Returns the chain from the leaf to the root.
+pub fn verify_chain(leaf: &Certificate, intermediates: &[Certificate], store: &TrustStore, now: u64) -> Result<Vec<Certificate>, CertError> {
+    let mut chain = vec![leaf.clone()];
+    loop {
+        let current = chain.last().expect("chain starts with the leaf");
+        current.check_validity(now)?;
+        current.check_extensions()?;
+        if let Some(root) = store.roots.iter().find(|root| *root == current) {
+            debug!("Chain reached trusted root '{}'", root.tbs.subject);
+            return Ok(chain);}
+        if chain.len() == MAX_CHAIN_LEN {
+            return Err(CertError::ChainTooLong);}
+        let issuer = store.roots.iter().chain(intermediates.iter())
+            .find(|candidate| candidate.issued(current) && !chain.contains(candidate))
+            .ok_or_else(|| CertError::UntrustedIssuer(current.tbs.issuer.clone()))?;
+        if !current.verify_signature(&issuer.tbs.public_key) {
+            return Err(CertError::BadSignature(current.tbs.subject.clone()));}
+        if !issuer.is_ca() {
+            return Err(CertError::NotCa(issuer.tbs.subject.clone()));}
+        let cas_below = chain.len() - 1;
+        if issuer.max_path_len().is_some_and(|max| cas_below > max as usize) {
+            return Err(CertError::PathLenExceeded(issuer.tbs.subject.clone()));}
+        chain.push(issuer.clone());}}
+
+impl fmt::Display for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tbs = &self.tbs;
+        writeln!(f, "Serial:      {:016x}", tbs.serial)?;
+        writeln!(f, "Subject:     {}", tbs.subject)?;
+        writeln!(f, "Issuer:      {}", tbs.issuer)?;
+        writeln!(f, "Issuer key:  {}", hex::encode(tbs.issuer_key_id))?;
+        writeln!(f, "Not before:  {}", tbs.not_before)?;
+        writeln!(f, "Not after:   {}", tbs.not_after)?;
+        writeln!(f, "Public key:  XMSS height {}, key id {}", tbs.public_key.height, hex::encode(self.key_id()))?;
+        for ext in &tbs.extensions {
+            let value = match ext.id.as_str() {
+                EXT_CA => self.max_path_len().map_or("unlimited path length".to_string(), |n| format!("path length {}", n)),
+                _ => String::from_utf8(ext.value.clone()).unwrap_or_else(|_| hex::encode(&ext.value)),};
+            writeln!(f, "Extension:   {}{} = {}", ext.id, if ext.critical { " (critical)" } else { "" }, value)?;}
+        write!(f, "Signature:   XMSS one-time key {}, TBS digest {}", self.signature.index, hex::encode(&tbs.digest()[..16]))}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_500;
+
+    fn key(tag: u8) -> XmssPrivateKey {
+        XmssPrivateKey::from_seed(4, &[tag; 3 * xmss::N]).unwrap()}
+
+    fn self_signed_ca(name: &str, key: &mut XmssPrivateKey, max_path_len: Option<u8>) -> Certificate {
+        issue(TbsCertificate::new(name, key.public_key(), 1_000, 1_000).with_ca(max_path_len), key).unwrap()}
+
+    fn store_with(root: &Certificate) -> TrustStore {
+        let mut store = TrustStore::new();
+        store.add(root.clone()).unwrap();
+        store}
+
+    #[test]
+    fn chain_through_an_intermediate_verifies_and_round_trips() {
+        let (mut root_key, mut ca_key, leaf_key) = (key(1), key(2), key(3));
+        let root = self_signed_ca("root", &mut root_key, None);
+        let ca = issue(TbsCertificate::new("ca", ca_key.public_key(), 1_000, 1_000).issued_by(&root).with_ca(Some(0)), &mut root_key).unwrap();
+        let leaf = issue(TbsCertificate::new("leaf", leaf_key.public_key(), 1_000, 1_000).issued_by(&ca).with_extension("note", false, b"hi"), &mut ca_key).unwrap();
+        let chain = verify_chain(&leaf, std::slice::from_ref(&ca), &store_with(&root), NOW).unwrap();
+        let subjects: Vec<&str> = chain.iter().map(|c| c.tbs.subject.as_str()).collect();
+        assert_eq!(subjects, ["leaf", "ca", "root"]);
+        assert_eq!(Certificate::from_bytes(&leaf.to_bytes()).unwrap(), leaf);
+        let mut bytes = leaf.to_bytes();
+        bytes.push(0);
+        assert!(Certificate::from_bytes(&bytes).is_err());}
+
+    #[test]
+    fn expired_and_not_yet_valid_certificates_are_rejected() {
+        let (mut root_key, leaf_key) = (key(1), key(3));
+        let root = self_signed_ca("root", &mut root_key, None);
+        let store = store_with(&root);
+        let expired = issue(TbsCertificate::new("old", leaf_key.public_key(), 1_000, 100).issued_by(&root), &mut root_key).unwrap();
+        assert_eq!(verify_chain(&expired, &[], &store, NOW), Err(CertError::Expired("old".to_string())));
+        let future = issue(TbsCertificate::new("new", leaf_key.public_key(), 2_000, 100).issued_by(&root), &mut root_key).unwrap();
+        assert_eq!(verify_chain(&future, &[], &store, NOW), Err(CertError::NotYetValid("new".to_string())));
+        assert_eq!(verify_chain(&root, &[], &store, 3_000), Err(CertError::Expired("root".to_string())));}
+
+    #[test]
+    fn unknown_critical_extension_is_rejected() {
+        let (mut root_key, leaf_key) = (key(1), key(3));
+        let root = self_signed_ca("root", &mut root_key, None);
+        let store = store_with(&root);
+        let critical = issue(TbsCertificate::new("leaf", leaf_key.public_key(), 1_000, 1_000).issued_by(&root).with_extension("x-new", true, b""), &mut root_key).unwrap();
+        assert_eq!(verify_chain(&critical, &[], &store, NOW), Err(CertError::UnknownCriticalExtension("x-new".to_string())));
+        let optional = issue(TbsCertificate::new("leaf", leaf_key.public_key(), 1_000, 1_000).issued_by(&root).with_extension("x-new", false, b""), &mut root_key).unwrap();
+        assert!(verify_chain(&optional, &[], &store, NOW).is_ok());}
+
+    #[test]
+    fn path_length_and_ca_flag_are_enforced() {
+        let (mut root_key, mut ca1_key, mut ca2_key, mut leaf_key) = (key(1), key(2), key(4), key(3));
+        let root = self_signed_ca("root", &mut root_key, Some(1));
+        let ca1 = issue(TbsCertificate::new("ca1", ca1_key.public_key(), 1_000, 1_000).issued_by(&root).with_ca(None), &mut root_key).unwrap();
+        let ca2 = issue(TbsCertificate::new("ca2", ca2_key.public_key(), 1_000, 1_000).issued_by(&ca1).with_ca(None), &mut ca1_key).unwrap();
+        let leaf = issue(TbsCertificate::new("leaf", leaf_key.public_key(), 1_000, 1_000).issued_by(&ca2), &mut ca2_key).unwrap();
+        let store = store_with(&root);
+        assert_eq!(verify_chain(&leaf, &[ca1.clone(), ca2], &store, NOW), Err(CertError::PathLenExceeded("root".to_string())));
+        let one_level = issue(TbsCertificate::new("leaf", leaf_key.public_key(), 1_000, 1_000).issued_by(&ca1), &mut ca1_key).unwrap();
+        assert!(verify_chain(&one_level, std::slice::from_ref(&ca1), &store, NOW).is_ok());
+
+        let not_ca = issue(TbsCertificate::new("plain", ca2_key.public_key(), 1_000, 1_000).issued_by(&root), &mut root_key).unwrap();
+        let below_plain = issue(TbsCertificate::new("leaf", leaf_key.public_key(), 1_000, 1_000).issued_by(&not_ca), &mut ca2_key).unwrap();
+        assert_eq!(verify_chain(&below_plain, &[not_ca], &store, NOW), Err(CertError::NotCa("plain".to_string())));
+        let stranger = issue(TbsCertificate::new("stranger", leaf_key.public_key(), 1_000, 1_000), &mut leaf_key).unwrap();
+        assert_eq!(verify_chain(&stranger, &[], &store, NOW), Err(CertError::UntrustedIssuer("stranger".to_string())));}
+
+    #[test]
+    fn tampered_certificate_fails_signature_check() {
+        let (mut root_key, leaf_key) = (key(1), key(3));
+        let root = self_signed_ca("root", &mut root_key, None);
+        let mut leaf = issue(TbsCertificate::new("leaf", leaf_key.public_key(), 1_000, 1_000).issued_by(&root), &mut root_key).unwrap();
+        leaf.tbs.not_after += 1;
+        assert_eq!(verify_chain(&leaf, &[], &store_with(&root), NOW), Err(CertError::BadSignature("leaf".to_string())));}
+
+    #[test]
+    fn oversized_fields_are_rejected_before_signing() {
+        let mut issuer_key = key(1);
+        let pk = issuer_key.public_key();
+        let long_name = "n".repeat(u16::MAX as usize + 1);
+        let too_many = (0..256).fold(TbsCertificate::new("s", pk.clone(), 0, 1), |tbs, i| tbs.with_extension(&i.to_string(), false, b""));
+        let cases = [
+            TbsCertificate::new(&long_name, pk.clone(), 0, 1),
+            too_many,
+            TbsCertificate::new("s", pk.clone(), 0, 1).with_extension(&"i".repeat(256), false, b""),
+            TbsCertificate::new("s", pk.clone(), 0, 1).with_extension("big", false, &vec![0; u16::MAX as usize + 1]),];
+        for tbs in cases {
+            assert!(matches!(tbs.validate(), Err(CertError::InvalidEncoding(_))));
+            assert!(matches!(issue(tbs, &mut issuer_key), Err(CertError::InvalidEncoding(_))));}
+        assert_eq!(issuer_key.next_index(), 0);
+        let largest = TbsCertificate::new(&"n".repeat(u16::MAX as usize), pk, 0, 1).with_extension(&"i".repeat(255), false, &vec![7; u16::MAX as usize]);
+        let certificate = issue(largest, &mut issuer_key).unwrap();
+        assert_eq!(Certificate::from_bytes(&certificate.to_bytes()).unwrap(), certificate);}}
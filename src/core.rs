@@ -1,17 +1,32 @@
 // src/core.rs
 use crate::components::{d_func_internal, h_func_internal, permute_1_internal, permute_2_internal, v_func_internal, w_func_internal,};
 use crate::constants::*;
-use crate::primitives::{arx_internal, derive_internal, generate_sbox_internal};
+use crate::memhard;
+use crate::pepper::{self, Pepper};
+use crate::phc::{PasswordHashError, PhcString};
+use crate::primitives::{arx_internal, derive_internal, generate_sbox_internal, quantum_internal};
 use crate::utils::{self, key_as_u128, key_as_u64};
 use hex;
 use log::{debug, info};
 use std::convert::TryInto;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Instant;
 
 pub type SBoxType = Vec<u16>;
 
-const ITERATIONS: u32 = 10_000;
-const SALT_SIZE: usize = 16;
+pub const ITERATIONS: u32 = 10_000;
+pub const SALT_SIZE: usize = 16;
+pub const KEY_LEN: usize = 64;
+pub const MIN_SALT_SIZE: usize = 8;
+pub const MAX_KEY_LEN: usize = 1024;
+pub const MAX_SALT_SIZE: usize = 1024;
+/// Largest iteration count `verify_password` will run for a stored PBKDF2 hash, so a crafted hash
+/// cannot keep a verifier busy for hours. New hashes are held to the same limit.
+pub const MAX_VERIFY_ITERATIONS: u32 = 1_000_000;
+pub const PBKDF2_ALGORITHM: &str = "qosmic-pbkdf2";
+pub const PBKDF2_VERSION: u32 = 1;
+/// Names the lattice construction. Any change to `quantum_internal`'s output needs a new version.
+const LATTICE_DIGEST_DOMAIN: &[u8] = b"qosmic-lattice v1";
 
 lazy_static! {
     pub static ref SBOX: SBoxType = {
@@ -67,11 +82,20 @@ fn final_byte_transform(combined_byte: u8, sbox_output_u16: u16, internal_seed:
     transformed_val}
 
 pub fn qosmic_unkeyed(
+    input_data_bytes: Vec<u8>,
+    fs_char: char,
+    s_box_param: &SBoxType,
+    nonce: u64,
+) -> String {
+    hex::encode(qosmic_unkeyed_bytes(input_data_bytes, fs_char, s_box_param, nonce))}
+
+/// Same digest as `qosmic_unkeyed`, returned as raw bytes instead of hex.
+pub fn qosmic_unkeyed_bytes(
     mut input_data_bytes: Vec<u8>,
     _fs_char: char,
     _s_box_param: &SBoxType,
     nonce: u64,
-) -> String {
+) -> [u8; 64] {
     let total_hash_start_time = Instant::now();
     let split_len: usize = 64;
     let mut current_main_state: [u64; 8] = [0; 8];
@@ -233,7 +257,7 @@ pub fn qosmic_unkeyed(
     debug!("Salt generation and padding took: {:?}", salt_gen_duration);
     debug!("Padded salt bytes (first 16): {:?}", &salt_bytes_padded[..16]);
     let sbox_combine_start = Instant::now();
-    let mut final_qosmic_bytes: Vec<u8> = Vec::with_capacity(64);
+    let mut final_qosmic_bytes = [0u8; 64];
     for i in 0..64 {
         let hash_byte = final_hash_state_bytes[i];
         let salt_byte = salt_bytes_padded[i];
@@ -241,11 +265,10 @@ pub fn qosmic_unkeyed(
         let sbox_index = combined_byte as usize % 512;
         let sbox_output_u16 = SBOX[sbox_index];
         let transformed_byte = final_byte_transform(combined_byte, sbox_output_u16, &mut internal_seed);
-        final_qosmic_bytes.push(transformed_byte);}
+        final_qosmic_bytes[i] = transformed_byte;}
     let sbox_combine_duration = sbox_combine_start.elapsed();
     debug!("S-Box combination and transformation took: {:?}", sbox_combine_duration);
     debug!("Final qosmic bytes (first 16): {:?}", &final_qosmic_bytes[..16]);
-    let hex_result = hex::encode(final_qosmic_bytes);
     let finalization_total_duration = finalization_start_time.elapsed();
     let total_sub_timers_duration = state_bytes_conversion_duration
         .checked_add(salt_gen_duration)
@@ -257,56 +280,350 @@ pub fn qosmic_unkeyed(
     debug!("Finalization total took: {:?}", finalization_total_duration);
     let total_hash_duration = total_hash_start_time.elapsed();
     info!("--- qosmic hash time: {:?} ---", total_hash_duration);
-    hex_result}
+    final_qosmic_bytes}
+
+/// Unkeyed qosmic digest of `data` as bytes, with the nonce derived from the data itself.
+pub fn qosmic_digest(data: &[u8]) -> [u8; 64] {
+    let nonce = utils::derive_deterministic_nonce(data);
+    qosmic_unkeyed_bytes(data.to_vec(), 's', get_sbox(), nonce)}
+
+/// Lattice-hardened digest (`--mode lattice`): `qosmic("qosmic-lattice v1" || quantum_internal(data) || data)`.
+/// The Ring-LWE absorption costs about 3.5 ms per 4 KiB block, roughly ten times `qosmic_digest`.
+pub fn qosmic_lattice_digest(data: &[u8]) -> [u8; 64] {
+    let start = Instant::now();
+    let mut input = LATTICE_DIGEST_DOMAIN.to_vec();
+    input.extend_from_slice(&quantum_internal(data));
+    input.extend_from_slice(data);
+    let digest = qosmic_digest(&input);
+    info!("--- qosmic lattice hash time: {:?} ---", start.elapsed());
+    digest}
+
+pub const HMAC_BLOCK_SIZE: usize = 64;
+
+/// HMAC-qosmic key state. The over-long key hash and the inner/outer pads are computed once,
+/// so repeated `mac` calls (as in PBKDF2) only pay for the two qosmic compressions.
+#[derive(Clone)]
+pub struct HmacQosmicKey {
+    inner_pad: [u8; HMAC_BLOCK_SIZE],
+    outer_pad: [u8; HMAC_BLOCK_SIZE],}
+
+impl HmacQosmicKey {
+    pub fn new(key: &[u8]) -> Self {
+        let mut k_prime = [0u8; HMAC_BLOCK_SIZE];
+        if key.len() > HMAC_BLOCK_SIZE {
+            k_prime.copy_from_slice(&qosmic_digest(key));
+        } else {
+            k_prime[..key.len()].copy_from_slice(key);}
+        let mut inner_pad = [0u8; HMAC_BLOCK_SIZE];
+        let mut outer_pad = [0u8; HMAC_BLOCK_SIZE];
+        for (i, &k) in k_prime.iter().enumerate() {
+            inner_pad[i] = k ^ 0x36;
+            outer_pad[i] = k ^ 0x5C;}
+        HmacQosmicKey { inner_pad, outer_pad }}
+
+    pub fn mac(&self, message: &[u8]) -> [u8; 64] {
+        let mut inner_message = Vec::with_capacity(HMAC_BLOCK_SIZE + message.len());
+        inner_message.extend_from_slice(&self.inner_pad);
+        inner_message.extend_from_slice(message);
+        let inner_hash = qosmic_digest(&inner_message);
+        let mut outer_message = Vec::with_capacity(HMAC_BLOCK_SIZE + inner_hash.len());
+        outer_message.extend_from_slice(&self.outer_pad);
+        outer_message.extend_from_slice(&inner_hash);
+        qosmic_digest(&outer_message)}
+
+    /// Checks `tag` against the MAC of `message` in constant time. A tag shorter than 64 bytes is
+    /// compared with the same-length prefix of the MAC; tags shorter than `min_tag_len` (or empty)
+    /// and tags longer than 64 bytes are rejected.
+    pub fn verify(&self, message: &[u8], tag: &[u8], min_tag_len: usize) -> bool {
+        if tag.is_empty() || tag.len() < min_tag_len || tag.len() > 64 {
+            return false;}
+        let expected = self.mac(message);
+        utils::constant_time_eq(&expected[..tag.len()], tag)}}
+
+impl std::fmt::Debug for HmacQosmicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HmacQosmicKey { .. }")}}
+
+pub fn hmac_qosmic_bytes(key: &[u8], message: &[u8]) -> [u8; 64] {
+    HmacQosmicKey::new(key).mac(message)}
 
 pub fn hmac_qosmic(key: &[u8], message: &[u8]) -> String {
-    let block_size = 64;
-    let s_box = get_sbox();
-    let mut k_prime = Vec::new();
-    if key.len() > block_size {
-        let nonce = crate::utils::derive_deterministic_nonce(key);
-        let hashed_key = qosmic_unkeyed(key.to_vec(), 's', s_box, nonce);
-        k_prime.extend_from_slice(&hex::decode(&hashed_key).expect("Failed to decode hashed key"));
-    } else {
-        k_prime.extend_from_slice(key);}
-    k_prime.resize(block_size, 0x00);
-    let mut k_inner_pad = Vec::with_capacity(block_size);
-    let mut k_outer_pad = Vec::with_capacity(block_size);
-    for i in 0..block_size {
-        k_inner_pad.push(k_prime[i] ^ 0x36);
-        k_outer_pad.push(k_prime[i] ^ 0x5C);}
-    let mut inner_message = k_inner_pad;
-    inner_message.extend_from_slice(message);
-    let inner_nonce = crate::utils::derive_deterministic_nonce(&inner_message);
-    let inner_hash_hex = qosmic_unkeyed(inner_message, 's', s_box, inner_nonce);
-    let inner_hash_bytes = hex::decode(&inner_hash_hex).expect("Failed to decode inner hash");
-    let mut outer_message = k_outer_pad;
-    outer_message.extend_from_slice(&inner_hash_bytes);
-    let outer_nonce = crate::utils::derive_deterministic_nonce(&outer_message);
-    let hmac_result_hex = qosmic_unkeyed(outer_message, 's', s_box, outer_nonce);
-    hmac_result_hex}
+    hex::encode(hmac_qosmic_bytes(key, message))}
 
-pub fn pbkdf2_hmac_qosmic(password: &[u8], salt: &[u8], iterations: u32, output_len: usize) -> Vec<u8> {
+/// How many PBKDF2 iterations a worker runs between progress reports and cancellation checks.
+pub const PROGRESS_INTERVAL: u32 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfError {
+    Cancelled,
+    CostLimitExceeded { param: &'static str, value: u64, limit: u64 },}
+
+impl std::fmt::Display for KdfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KdfError::Cancelled => write!(f, "key derivation was cancelled"),
+            KdfError::CostLimitExceeded { param, value, limit } => write!(f, "cost {}={} exceeds the verification limit of {}", param, value, limit),}}}
+
+impl std::error::Error for KdfError {}
+
+type WorkerBlocks = Result<Vec<(usize, [u8; 64])>, KdfError>;
+
+fn pbkdf2_block<F>(prf: &HmacQosmicKey, salt: &[u8], block_index: u32, iterations: u32, tick: &F) -> Result<[u8; 64], KdfError>
+where F: Fn(u64) -> bool {
+    let mut salt_with_block_index = salt.to_vec();
+    salt_with_block_index.extend_from_slice(&block_index.to_be_bytes());
+    let mut u = prf.mac(&salt_with_block_index);
+    let mut block = u;
+    let mut since_report: u32 = 1;
+    for iter_num in 1..iterations {
+        debug!("Block {} iteration {}/{}", block_index, iter_num + 1, iterations);
+        u = prf.mac(&u);
+        for (b, x) in block.iter_mut().zip(u.iter()) {
+            *b ^= x;}
+        since_report += 1;
+        if since_report == PROGRESS_INTERVAL {
+            if !tick(since_report as u64) {
+                return Err(KdfError::Cancelled);}
+            since_report = 0;}}
+    if !tick(since_report as u64) {
+        return Err(KdfError::Cancelled);}
+    Ok(block)}
+
+/// PBKDF2-HMAC-Qosmic with progress reporting. `progress(completed, total)` counts HMAC iterations
+/// across all output blocks and is called from worker threads roughly every `PROGRESS_INTERVAL`
+/// iterations; returning `false` stops every worker and yields `Err(KdfError::Cancelled)`.
+/// Independent 64-byte output blocks are computed on separate threads.
+pub fn pbkdf2_hmac_qosmic_with_progress<F>(
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    output_len: usize,
+    progress: F,
+) -> Result<Vec<u8>, KdfError>
+where F: Fn(u64, u64) -> bool + Sync {
     let h_len = 64;
-    let num_blocks = (output_len + h_len - 1) / h_len;
-    let mut derived_key = Vec::with_capacity(output_len);
-    for i in 1..=num_blocks {
-        let mut salt_with_block_index = salt.to_vec();
-        salt_with_block_index.extend_from_slice(&(i as u32).to_be_bytes());
-        let mut u = hex::decode(hmac_qosmic(password, &salt_with_block_index))
-            .expect("HMAC hex decoding failed in PBKDF2");
-        let mut block = u.clone();
-        for iter_num in 1..iterations {
-            debug!("Iteration {}/{}", iter_num + 1, iterations);
-            u = hex::decode(hmac_qosmic(password, &u))
-                .expect("HMAC hex decoding failed in PBKDF2 loop");
-            for k in 0..h_len {
-                block[k] ^= u[k];}}
-        derived_key.extend_from_slice(&block);}
+    let num_blocks = output_len.div_ceil(h_len);
+    let prf = HmacQosmicKey::new(password);
+    let total = num_blocks as u64 * iterations.max(1) as u64;
+    let completed = AtomicU64::new(0);
+    let cancelled = AtomicBool::new(false);
+    let tick = |done: u64| -> bool {
+        if cancelled.load(Ordering::Relaxed) {
+            return false;}
+        let now = completed.fetch_add(done, Ordering::Relaxed) + done;
+        if !progress(now, total) {
+            cancelled.store(true, Ordering::Relaxed);
+            return false;}
+        true};
+    let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(num_blocks).max(1);
+    let mut blocks: Vec<Option<[u8; 64]>> = vec![None; num_blocks];
+    if workers == 1 {
+        for (idx, slot) in blocks.iter_mut().enumerate() {
+            *slot = Some(pbkdf2_block(&prf, salt, idx as u32 + 1, iterations, &tick)?);}
+    } else {
+        debug!("PBKDF2: computing {} blocks on {} threads", num_blocks, workers);
+        let results: Vec<WorkerBlocks> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|worker| {
+                    let (prf, tick) = (&prf, &tick);
+                    scope.spawn(move || {
+                        let mut done = Vec::new();
+                        for idx in (worker..num_blocks).step_by(workers) {
+                            done.push((idx, pbkdf2_block(prf, salt, idx as u32 + 1, iterations, tick)?));}
+                        Ok(done)})})
+                .collect();
+            handles.into_iter().map(|h| h.join().expect("PBKDF2 worker thread panicked")).collect()});
+        for result in results {
+            for (idx, block) in result? {
+                blocks[idx] = Some(block);}}}
+    let mut derived_key = Vec::with_capacity(num_blocks * h_len);
+    for block in blocks {
+        derived_key.extend_from_slice(&block.expect("Every PBKDF2 block is computed"));}
     derived_key.truncate(output_len);
-    derived_key}
+    Ok(derived_key)}
+
+pub fn pbkdf2_hmac_qosmic(password: &[u8], salt: &[u8], iterations: u32, output_len: usize) -> Vec<u8> {
+    pbkdf2_hmac_qosmic_with_progress(password, salt, iterations, output_len, |_, _| true)
+        .expect("PBKDF2 without a cancelling callback cannot be cancelled")}
+
+/// Cost and size parameters for PBKDF2-HMAC-Qosmic. When `salt` is set it is used as-is
+/// instead of generating `salt_len` random bytes, which makes the derivation deterministic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdfParams {
+    pub iterations: u32,
+    pub salt_len: usize,
+    pub output_len: usize,
+    pub salt: Option<Vec<u8>>,}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams { iterations: ITERATIONS, salt_len: SALT_SIZE, output_len: KEY_LEN, salt: None }}}
+
+impl KdfParams {
+    pub fn validate(&self) -> Result<(), PasswordHashError> {
+        if self.iterations == 0 {
+            return Err(PasswordHashError::InvalidParameter(format!("i={}", self.iterations)));}
+        if self.output_len == 0 || self.output_len > MAX_KEY_LEN {
+            return Err(PasswordHashError::InvalidParameter(format!("l={}", self.output_len)));}
+        match &self.salt {
+            Some(salt) if salt.is_empty() => Err(PasswordHashError::InvalidParameter("salt is empty".to_string())),
+            Some(salt) if salt.len() > MAX_SALT_SIZE => Err(PasswordHashError::InvalidParameter(format!("salt_len={}", salt.len()))),
+            None if self.salt_len < MIN_SALT_SIZE || self.salt_len > MAX_SALT_SIZE => Err(PasswordHashError::InvalidParameter(format!("salt_len={}", self.salt_len))),
+            _ => Ok(()),}}
+
+    /// Rejects iteration counts above `MAX_VERIFY_ITERATIONS` before any work is done.
+    pub fn check_verify_limit(&self) -> Result<(), KdfError> {
+        if self.iterations > MAX_VERIFY_ITERATIONS {
+            return Err(KdfError::CostLimitExceeded { param: "i", value: self.iterations as u64, limit: MAX_VERIFY_ITERATIONS as u64 });}
+        Ok(())}
+
+    /// Returns the supplied salt, or a fresh random one of `salt_len` bytes.
+    pub fn salt_or_generate(&self) -> Vec<u8> {
+        match &self.salt {
+            Some(salt) => salt.clone(),
+            None => utils::generate_salt(self.salt_len),}}
+
+    pub(crate) fn phc_params(&self) -> Vec<(String, String)> {
+        vec![
+            ("i".to_string(), self.iterations.to_string()),
+            ("l".to_string(), self.output_len.to_string()),]}}
+
+/// Runs PBKDF2-HMAC-Qosmic with `params`, returning `(salt, derived_key)`.
+pub fn derive_key(password: &[u8], params: &KdfParams) -> Result<(Vec<u8>, Vec<u8>), PasswordHashError> {
+    params.validate()?;
+    let salt = params.salt_or_generate();
+    let derived_key = pbkdf2_hmac_qosmic(password, &salt, params.iterations, params.output_len);
+    Ok((salt, derived_key))}
 
 pub fn hash_password(password: &[u8]) -> String {
-    let salt = utils::generate_salt(SALT_SIZE);
-    let derived_key = pbkdf2_hmac_qosmic(password, &salt, ITERATIONS, 64);
-    format!("{}${}", hex::encode(salt), hex::encode(derived_key))}
+    hash_password_with_params(password, &KdfParams::default()).expect("Default KDF parameters are valid")}
+
+pub fn hash_password_with_params(password: &[u8], params: &KdfParams) -> Result<String, PasswordHashError> {
+    hash_password_with_pepper(password, params, None)}
+
+/// Like `hash_password_with_params`, but runs PBKDF2 over `HMAC-Qosmic(pepper, password)` and
+/// records the pepper id as `pid=` so `verify_password_with_peppers` can pick the right secret.
+pub fn hash_password_with_pepper(password: &[u8], params: &KdfParams, pepper: Option<&Pepper>) -> Result<String, PasswordHashError> {
+    params.check_verify_limit()?;
+    let (kdf_password, pepper_param) = pepper::pepper_password(password, pepper);
+    let (salt, derived_key) = derive_key(&kdf_password, params)?;
+    let mut phc_params = params.phc_params();
+    phc_params.extend(pepper_param);
+    Ok(PhcString::new(PBKDF2_ALGORITHM, PBKDF2_VERSION, phc_params, salt, derived_key).to_string())}
+
+/// Parses a stored password hash, accepting PHC strings and the legacy `salt_hex$hash_hex` output.
+pub fn parse_password_hash(stored: &str) -> Result<PhcString, PasswordHashError> {
+    if stored.trim_start().starts_with('$') {
+        PhcString::parse(stored)
+    } else {
+        let iterations = ITERATIONS.to_string();
+        let key_len = KEY_LEN.to_string();
+        PhcString::parse_legacy(stored, PBKDF2_ALGORITHM, &[("i", &iterations), ("l", &key_len)])}}
+
+/// Checks `password` against a stored PBKDF2, memory-hard or legacy hash, comparing the derived key in constant time.
+/// Peppered hashes fail with `UnknownPepper`; use `verify_password_with_peppers` for those.
+pub fn verify_password(password: &[u8], stored: &str) -> Result<bool, PasswordHashError> {
+    verify_password_with_peppers(password, stored, &[])}
+
+/// Verifies a hash that may carry a `pid=` parameter, looking the pepper up by id in `peppers`.
+/// Unpeppered hashes are checked against the plain password, so a pepper can be introduced gradually.
+pub fn verify_password_with_peppers(password: &[u8], stored: &str, peppers: &[Pepper]) -> Result<bool, PasswordHashError> {
+    let phc = parse_password_hash(stored)?;
+    let password = &pepper::kdf_input(password, &phc, peppers)?;
+    if phc.algorithm == memhard::MEMHARD_ALGORITHM {
+        return memhard::verify_phc(password, &phc);}
+    if phc.algorithm != PBKDF2_ALGORITHM {
+        return Err(PasswordHashError::UnsupportedAlgorithm(phc.algorithm));}
+    if let Some(v) = phc.version && v != PBKDF2_VERSION {
+        return Err(PasswordHashError::UnsupportedVersion(v));}
+    phc.check_known_params(&["i", "l", pepper::PEPPER_PARAM])?;
+    let params = KdfParams {
+        iterations: phc.param_u32("i")?,
+        salt_len: phc.salt.len(),
+        output_len: phc.param_u32("l")? as usize,
+        salt: Some(phc.salt.clone()),};
+    params.check_verify_limit()?;
+    if params.output_len != phc.hash.len() {
+        return Err(PasswordHashError::InvalidParameter(format!("l={}", params.output_len)));}
+    let (_, derived_key) = derive_key(password, &params)?;
+    Ok(utils::constant_time_eq(&derived_key, &phc.hash))}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lattice_digest_known_answers() {
+        assert_eq!(hex::encode(qosmic_lattice_digest(b"")), "ab3d2200874fb680a993c9d850e95c5d71bbe57ea371418b080cd758b8ca8896caf40bea64c9421a3ecb072b487d530d31ce18566f32eed69093431d4af95f71");
+        assert_eq!(hex::encode(qosmic_lattice_digest(b"abc")), "626a24d3ed149d9517563dc6363b965fbf5dc301393541c434b5bc7b6fb25d5970530479055d264117409a2b74152dd8b8c6aaf8d8cc4f2039992bd5dc8d39ec");
+        assert_eq!(hex::encode(qosmic_lattice_digest(&[b'a'; 4096])), "534a5d924aea94e5888957ad86874b3f8512977a313ad126d40f6100ea484fe8562eddf87dc95932f4a5458918b34ae2ec44cb2d081ae5a21d4930664969a2e9");}
+
+    #[test]
+    fn lattice_digest_is_deterministic_and_distinct_from_standard() {
+        let data = vec![0x5a; 5000];
+        assert_eq!(quantum_internal(&data), quantum_internal(&data));
+        assert_eq!(qosmic_lattice_digest(&data), qosmic_lattice_digest(&data));
+        assert_ne!(qosmic_lattice_digest(&data), qosmic_lattice_digest(&data[..4999]));
+        assert_ne!(qosmic_lattice_digest(b"abc"), qosmic_digest(b"abc"));}
+
+    /// Outputs of the string-returning `hmac_qosmic` before it was rebuilt on `HmacQosmicKey`.
+    #[test]
+    fn hmac_key_matches_previous_hmac_qosmic_outputs() {
+        let message = b"The quick brown fox jumps over the lazy dog";
+        let long_key: Vec<u8> = (0..100).collect();
+        let cases: [(&[u8], &[u8], &str); 3] = [
+            (&long_key, message, "8dd862bf7347b902a53e1b703829b4a57d01a19acff997c6b9dbf6cb6baf4c357e89f1922de6dc9915d7bdcb02a91f321088634cec6095cc0cd75e17e72d30c3"),
+            (b"key", message, "c0653ffbda86530088b8ae1584515624620edb963a4fff29558bb5cb817123cc9ea113783470fc15c26696707da980b58883cca8c87e9ccf957e3fc6a2fb8e26"),
+            (&[0x0b; 64], b"", "ee2d675e30795bd36314380b06fb49072d1cdda83bd8efe519e24a6be1757ef783c1b51810a177250a36c5c715077005c6171a753a6637b16088519880794ca0"),];
+        for (key, message, expected) in cases {
+            assert_eq!(hex::encode(HmacQosmicKey::new(key).mac(message)), expected);
+            assert_eq!(hmac_qosmic(key, message), expected);}}
+
+    #[test]
+    fn multi_block_output_matches_sequential_blocks() {
+        let (password, salt, iterations) = (b"password".as_slice(), b"salt".as_slice(), 300);
+        let prf = HmacQosmicKey::new(password);
+        let mut expected = Vec::new();
+        for index in 1..=3 {
+            expected.extend_from_slice(&pbkdf2_block(&prf, salt, index, iterations, &|_| true).unwrap());}
+        expected.truncate(150);
+        assert_eq!(pbkdf2_hmac_qosmic(password, salt, iterations, 150), expected);
+        assert_eq!(pbkdf2_hmac_qosmic(password, salt, iterations, 64), expected[..64]);}
+
+    #[test]
+    fn progress_reaches_the_total() {
+        let last = std::sync::Mutex::new((0, 0));
+        let key = pbkdf2_hmac_qosmic_with_progress(b"pw", b"salt", 1000, 130, |done, total| {
+            let mut last = last.lock().unwrap();
+            assert!(done >= last.0 && done <= total);
+            *last = (done, total);
+            true}).unwrap();
+        assert_eq!(key.len(), 130);
+        assert_eq!(*last.lock().unwrap(), (3000, 3000));}
+
+    #[test]
+    fn cancelling_callback_stops_the_derivation() {
+        let calls = AtomicU64::new(0);
+        let result = pbkdf2_hmac_qosmic_with_progress(b"pw", b"salt", 100_000, 128, |_, _| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            false});
+        assert_eq!(result, Err(KdfError::Cancelled));
+        assert!(calls.load(Ordering::Relaxed) <= 2, "each of the two blocks stops at its first report");}
+
+    #[test]
+    fn verification_rejects_iterations_above_the_limit() {
+        let params = KdfParams { iterations: 2, ..KdfParams::default() };
+        let stored = hash_password_with_params(b"pw", &params).unwrap();
+        assert_eq!(verify_password(b"pw", &stored), Ok(true));
+        let crafted = stored.replace("i=2,", &format!("i={},", MAX_VERIFY_ITERATIONS + 1));
+        let expected = KdfError::CostLimitExceeded { param: "i", value: MAX_VERIFY_ITERATIONS as u64 + 1, limit: MAX_VERIFY_ITERATIONS as u64 };
+        assert_eq!(verify_password(b"pw", &crafted), Err(PasswordHashError::Kdf(expected)));
+        let params = KdfParams { iterations: MAX_VERIFY_ITERATIONS + 1, ..KdfParams::default() };
+        assert_eq!(hash_password_with_params(b"pw", &params), Err(PasswordHashError::Kdf(expected)));}
+
+    #[test]
+    fn salt_length_is_bounded() {
+        assert!(KdfParams { salt_len: MAX_SALT_SIZE, ..KdfParams::default() }.validate().is_ok());
+        assert!(KdfParams { salt_len: MAX_SALT_SIZE + 1, ..KdfParams::default() }.validate().is_err());
+        assert!(KdfParams { salt_len: usize::MAX, ..KdfParams::default() }.validate().is_err());
+        assert!(KdfParams { salt: Some(vec![0; MAX_SALT_SIZE + 1]), ..KdfParams::default() }.validate().is_err());}}
@@ -0,0 +1,257 @@
+// src/drbg.rs
+use crate::components::BitGenerator;
+use crate::core::HmacQosmicKey;
+use log::debug;
+use rand::{CryptoRng, RngCore};
+use std::fmt;
+
+/// HMAC-qosmic output length, the size of `K` and `V`.
+pub const OUTLEN: usize = 64;
+/// Security strength claimed for the generator, in bytes.
+pub const SECURITY_STRENGTH: usize = 32;
+/// Shortest entropy input accepted by `instantiate` and `reseed`.
+pub const MIN_ENTROPY_LEN: usize = SECURITY_STRENGTH;
+/// Longest single `generate` request (2^19 bits, the SP 800-90A limit).
+pub const MAX_REQUEST_LEN: usize = 1 << 16;
+/// Generate requests allowed between reseeds (2^48, the SP 800-90A limit).
+pub const RESEED_INTERVAL: u64 = 1 << 48;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrbgError {
+    InsufficientEntropy { len: usize, min: usize },
+    RequestTooLarge(usize),
+    ReseedRequired,
+    NoEntropySource,}
+
+impl fmt::Display for DrbgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrbgError::InsufficientEntropy { len, min } => write!(f, "entropy input of {} bytes is shorter than {} bytes", len, min),
+            DrbgError::RequestTooLarge(len) => write!(f, "request for {} bytes exceeds the {}-byte limit", len, MAX_REQUEST_LEN),
+            DrbgError::ReseedRequired => write!(f, "reseed interval reached; reseed before generating more output"),
+            DrbgError::NoEntropySource => write!(f, "prediction resistance or automatic reseeding needs an entropy source"),}}}
+
+impl std::error::Error for DrbgError {}
+
+/// Where fresh entropy comes from for reseeds. `HmacDrbg::new` uses the operating system's
+/// generator through `rand::rng()`.
+pub type EntropySource = Box<dyn FnMut(&mut [u8]) + Send>;
+
+/// HMAC_DRBG (NIST SP 800-90A, section 10.1.2) over HMAC-qosmic.
+///
+/// Instantiated from fixed inputs, the output is a pure function of the entropy, nonce,
+/// personalization string and the sequence of requests, which makes it usable for test vectors
+/// and reproducible simulations. With an entropy source it reseeds itself when the reseed interval
+/// runs out, and with prediction resistance enabled it reseeds before every request.
+///
+/// Through `RngCore`, each call is one `generate` request and costs three HMAC-qosmic calls plus
+/// one per 64 bytes of output: about 0.3 ms for a `next_u64` and 0.8 MB/s for large requests.
+pub struct HmacDrbg {
+    k: [u8; OUTLEN],
+    v: [u8; OUTLEN],
+    reseed_counter: u64,
+    prediction_resistance: bool,
+    entropy_source: Option<EntropySource>,}
+
+impl fmt::Debug for HmacDrbg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HmacDrbg")
+            .field("reseed_counter", &self.reseed_counter)
+            .field("prediction_resistance", &self.prediction_resistance)
+            .field("entropy_source", &self.entropy_source.is_some())
+            .finish_non_exhaustive()}}
+
+impl HmacDrbg {
+    /// `HMAC_DRBG_Instantiate`: `seed_material = entropy || nonce || personalization`.
+    pub fn instantiate(entropy: &[u8], nonce: &[u8], personalization: &[u8]) -> Result<Self, DrbgError> {
+        if entropy.len() < MIN_ENTROPY_LEN {
+            return Err(DrbgError::InsufficientEntropy { len: entropy.len(), min: MIN_ENTROPY_LEN });}
+        let mut drbg = HmacDrbg {
+            k: [0x00; OUTLEN],
+            v: [0x01; OUTLEN],
+            reseed_counter: 1,
+            prediction_resistance: false,
+            entropy_source: None,};
+        drbg.update(&[entropy, nonce, personalization]);
+        Ok(drbg)}
+
+    /// Instantiates from the operating system's generator, which also serves as the entropy source
+    /// for later reseeds.
+    pub fn new(personalization: &[u8]) -> Self {
+        let mut source: EntropySource = Box::new(|buf: &mut [u8]| rand::rng().fill_bytes(buf));
+        let mut entropy = [0u8; OUTLEN];
+        let mut nonce = [0u8; SECURITY_STRENGTH];
+        source(&mut entropy);
+        source(&mut nonce);
+        let mut drbg = HmacDrbg::instantiate(&entropy, &nonce, personalization).expect("64 bytes of entropy is enough");
+        drbg.entropy_source = Some(source);
+        drbg}
+
+    /// Uses `source` for automatic and prediction-resistance reseeds.
+    pub fn with_entropy_source(mut self, source: EntropySource) -> Self {
+        self.entropy_source = Some(source);
+        self}
+
+    /// Reseeds from the entropy source before every request. Fails without an entropy source.
+    pub fn with_prediction_resistance(mut self) -> Result<Self, DrbgError> {
+        if self.entropy_source.is_none() {
+            return Err(DrbgError::NoEntropySource);}
+        self.prediction_resistance = true;
+        Ok(self)}
+
+    pub fn prediction_resistance(&self) -> bool {
+        self.prediction_resistance}
+
+    pub fn reseed_counter(&self) -> u64 {
+        self.reseed_counter}
+
+    /// `HMAC_DRBG_Update` with `provided_data` given as concatenated parts.
+    fn update(&mut self, provided_data: &[&[u8]]) {
+        let provided_len: usize = provided_data.iter().map(|part| part.len()).sum();
+        for round in [0x00u8, 0x01] {
+            let mut input = Vec::with_capacity(OUTLEN + 1 + provided_len);
+            input.extend_from_slice(&self.v);
+            input.push(round);
+            for part in provided_data {
+                input.extend_from_slice(part);}
+            self.k = HmacQosmicKey::new(&self.k).mac(&input);
+            self.v = HmacQosmicKey::new(&self.k).mac(&self.v);
+            if provided_len == 0 {
+                break;}}}
+
+    /// `HMAC_DRBG_Reseed`: `seed_material = entropy || additional_input`.
+    pub fn reseed(&mut self, entropy: &[u8], additional_input: &[u8]) -> Result<(), DrbgError> {
+        if entropy.len() < MIN_ENTROPY_LEN {
+            return Err(DrbgError::InsufficientEntropy { len: entropy.len(), min: MIN_ENTROPY_LEN });}
+        self.update(&[entropy, additional_input]);
+        self.reseed_counter = 1;
+        debug!("HMAC_DRBG reseeded");
+        Ok(())}
+
+    fn reseed_from_source(&mut self, additional_input: &[u8]) -> Result<(), DrbgError> {
+        let source = self.entropy_source.as_mut().ok_or(DrbgError::NoEntropySource)?;
+        let mut entropy = [0u8; OUTLEN];
+        source(&mut entropy);
+        self.reseed(&entropy, additional_input)}
+
+    /// `HMAC_DRBG_Generate`: fills `out` (at most `MAX_REQUEST_LEN` bytes). With prediction
+    /// resistance, or once the reseed interval is reached and an entropy source is set, the
+    /// generator first reseeds with `additional_input`.
+    pub fn generate(&mut self, out: &mut [u8], additional_input: &[u8]) -> Result<(), DrbgError> {
+        if out.len() > MAX_REQUEST_LEN {
+            return Err(DrbgError::RequestTooLarge(out.len()));}
+        let mut additional_input = additional_input;
+        if self.prediction_resistance || self.reseed_counter > RESEED_INTERVAL {
+            if self.entropy_source.is_none() {
+                return Err(DrbgError::ReseedRequired);}
+            self.reseed_from_source(additional_input)?;
+            additional_input = &[];}
+        if !additional_input.is_empty() {
+            self.update(&[additional_input]);}
+        let key = HmacQosmicKey::new(&self.k);
+        for chunk in out.chunks_mut(OUTLEN) {
+            self.v = key.mac(&self.v);
+            chunk.copy_from_slice(&self.v[..chunk.len()]);}
+        self.update(&[additional_input]);
+        self.reseed_counter += 1;
+        Ok(())}
+
+    /// A `components::BitGenerator` that yields this generator's output byte by byte, drawing
+    /// 64 bytes per request, so its bytes match a sequence of 64-byte `generate` calls.
+    pub fn into_bit_generator(mut self) -> BitGenerator {
+        let mut block = [0u8; OUTLEN];
+        let mut pos = OUTLEN;
+        Box::new(move || {
+            if pos == OUTLEN {
+                self.fill_bytes(&mut block);
+                pos = 0;}
+            pos += 1;
+            block[pos - 1]})}}
+
+impl RngCore for HmacDrbg {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_be_bytes(bytes)}
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_be_bytes(bytes)}
+
+    /// One request per `MAX_REQUEST_LEN` bytes. `RngCore` cannot report errors, so when a reseed
+    /// is due (after 2^48 requests) and there is no entropy source, this reseeds from the operating
+    /// system's generator instead. Call `generate` to get `ReseedRequired` back.
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(MAX_REQUEST_LEN) {
+            if self.generate(chunk, &[]) == Err(DrbgError::ReseedRequired) {
+                let mut entropy = [0u8; OUTLEN];
+                rand::rng().fill_bytes(&mut entropy);
+                self.reseed(&entropy, &[]).expect("64 bytes of entropy is enough");
+                self.generate(chunk, &[]).expect("a freshly reseeded generator can generate");}}}}
+
+impl CryptoRng for HmacDrbg {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed() -> HmacDrbg {
+        let entropy: Vec<u8> = (0x00..0x40).collect();
+        let nonce: Vec<u8> = (0x40..0x60).collect();
+        HmacDrbg::instantiate(&entropy, &nonce, &[]).unwrap()}
+
+    #[test]
+    fn known_answers() {
+        let mut drbg = fixed();
+        let mut out = [0u8; 64];
+        drbg.generate(&mut out, &[]).unwrap();
+        drbg.generate(&mut out, &[]).unwrap();
+        assert_eq!(hex::encode(out), "8604e4088b6700046bd47c4771e96dbef488869d3cbc3f076652685f616283f96b26465fb1f3dd4a98b45f356ee2fc0757d3f4cc5cfe4e059ea4945ac00aef8d");
+        assert_eq!(drbg.reseed_counter(), 3);
+
+        let entropy: Vec<u8> = (0x00..0x40).collect();
+        let nonce: Vec<u8> = (0x40..0x60).collect();
+        let mut drbg = HmacDrbg::instantiate(&entropy, &nonce, b"qosmic test").unwrap();
+        drbg.generate(&mut out, b"extra").unwrap();
+        drbg.reseed(&[0xaa; 32], &[]).unwrap();
+        drbg.generate(&mut out, &[]).unwrap();
+        assert_eq!(hex::encode(out), "70bbcaf24025f92177ae81f20bb751bac5a5be564e681bcd4bae660a63c6da59ebac3e3d644122a430b4caea52252747371c975022172cffd2ee651b16c50213");}
+
+    #[test]
+    fn rng_core_and_bit_generator_follow_generate() {
+        let mut expected = [0u8; 128];
+        let mut drbg = fixed();
+        drbg.generate(&mut expected[..64], &[]).unwrap();
+        drbg.generate(&mut expected[64..], &[]).unwrap();
+        let mut bits = fixed().into_bit_generator();
+        let drawn: Vec<u8> = (0..128).map(|_| bits()).collect();
+        assert_eq!(drawn, expected);
+        let mut rng = fixed();
+        assert_eq!(rng.next_u64(), u64::from_be_bytes(expected[..8].try_into().unwrap()));}
+
+    #[test]
+    fn input_checks_and_reseeding() {
+        assert_eq!(HmacDrbg::instantiate(&[0; 31], &[], &[]).err(), Some(DrbgError::InsufficientEntropy { len: 31, min: 32 }));
+        let mut drbg = fixed();
+        assert_eq!(drbg.generate(&mut vec![0; MAX_REQUEST_LEN + 1], &[]), Err(DrbgError::RequestTooLarge(MAX_REQUEST_LEN + 1)));
+        assert_eq!(drbg.reseed(&[0; 8], &[]), Err(DrbgError::InsufficientEntropy { len: 8, min: 32 }));
+        assert_eq!(fixed().with_prediction_resistance().err(), Some(DrbgError::NoEntropySource));
+
+        drbg.reseed_counter = RESEED_INTERVAL + 1;
+        assert_eq!(drbg.generate(&mut [0u8; 8], &[]), Err(DrbgError::ReseedRequired));
+        let mut out = [0u8; 8];
+        drbg.fill_bytes(&mut out);
+        assert_eq!(drbg.reseed_counter(), 2);
+
+        let mut calls = 0;
+        let source: EntropySource = Box::new(move |buf: &mut [u8]| {
+            calls += 1;
+            buf.fill(calls);});
+        let mut resistant = fixed().with_entropy_source(source).with_prediction_resistance().unwrap();
+        let (mut a, mut b) = ([0u8; 32], [0u8; 32]);
+        resistant.generate(&mut a, &[]).unwrap();
+        assert_eq!(resistant.reseed_counter(), 2);
+        resistant.generate(&mut b, &[]).unwrap();
+        assert_eq!(resistant.reseed_counter(), 2);
+        assert_ne!(a, b);}}
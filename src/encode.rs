@@ -3,6 +3,8 @@ use hex;
 use base64::{engine::general_purpose, Engine as _};
 use bs58;
 use base36;
+use crate::core::qosmic_digest;
+use std::fmt;
 
 pub fn to_base36(hex_string: &str) -> String {
     let bytes = hex::decode(hex_string).expect("Failed to decode hex string to bytes for Base36 encoding");
@@ -37,3 +39,122 @@ pub fn to_base64(hex_string: &str) -> String {
 pub fn to_base58(hex_string: &str) -> String {
     let bytes = hex::decode(hex_string).expect("Failed to decode hex string to bytes for Base58 encoding");
     bs58::encode(&bytes).into_string()}
+
+pub const B58C_VERSION_HASH: u8 = 0x51;
+pub const B58C_VERSION_HMAC: u8 = 0x52;
+pub const B58C_VERSION_KEY_ID: u8 = 0x53;
+const B58C_CHECKSUM_LEN: usize = 4;
+const B58C_VERSIONS: [(u8, usize); 3] = [
+    (B58C_VERSION_HASH, 64),
+    (B58C_VERSION_HMAC, 64),
+    (B58C_VERSION_KEY_ID, 32),];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Base58CheckError {
+    InvalidBase58,
+    BadLength { version: Option<u8>, len: usize },
+    UnknownVersion(u8),
+    BadChecksum { expected: [u8; 4], found: [u8; 4] },}
+
+impl fmt::Display for Base58CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Base58CheckError::InvalidBase58 => write!(f, "input is not valid Base58"),
+            Base58CheckError::BadLength { version: Some(v), len } => write!(f, "payload length {} is invalid for version 0x{:02x}", len, v),
+            Base58CheckError::BadLength { version: None, len } => write!(f, "decoded length {} is too short for Base58Check", len),
+            Base58CheckError::UnknownVersion(v) => write!(f, "unknown version byte 0x{:02x}", v),
+            Base58CheckError::BadChecksum { expected, found } => write!(f, "checksum mismatch (expected {}, found {})", hex::encode(expected), hex::encode(found)),}}}
+
+impl std::error::Error for Base58CheckError {}
+
+/// First four bytes of the qosmic digest of `version || payload`.
+fn b58c_checksum(versioned: &[u8]) -> [u8; 4] {
+    let digest = qosmic_digest(versioned);
+    let mut checksum = [0u8; B58C_CHECKSUM_LEN];
+    checksum.copy_from_slice(&digest[..B58C_CHECKSUM_LEN]);
+    checksum}
+
+pub fn encode_base58check(version: u8, payload: &[u8]) -> String {
+    let mut data = Vec::with_capacity(1 + payload.len() + B58C_CHECKSUM_LEN);
+    data.push(version);
+    data.extend_from_slice(payload);
+    let checksum = b58c_checksum(&data);
+    data.extend_from_slice(&checksum);
+    bs58::encode(&data).into_string()}
+
+pub fn to_base58check(hex_string: &str, version: u8) -> String {
+    let bytes = hex::decode(hex_string).expect("Failed to decode hex string to bytes for Base58Check encoding");
+    encode_base58check(version, &bytes)}
+
+/// Decodes a Base58Check string, returning the version byte and payload.
+/// The checksum is checked before the version so a mistyped version byte reports as `BadChecksum`.
+pub fn from_base58check(encoded: &str) -> Result<(u8, Vec<u8>), Base58CheckError> {
+    let data = bs58::decode(encoded.trim()).into_vec().map_err(|_| Base58CheckError::InvalidBase58)?;
+    if data.len() < 1 + B58C_CHECKSUM_LEN {
+        return Err(Base58CheckError::BadLength { version: None, len: data.len() });}
+    let (versioned, found) = data.split_at(data.len() - B58C_CHECKSUM_LEN);
+    let expected = b58c_checksum(versioned);
+    if expected != found {
+        let mut found_arr = [0u8; B58C_CHECKSUM_LEN];
+        found_arr.copy_from_slice(found);
+        return Err(Base58CheckError::BadChecksum { expected, found: found_arr });}
+    let version = versioned[0];
+    let payload = &versioned[1..];
+    match B58C_VERSIONS.iter().find(|(v, _)| *v == version) {
+        None => Err(Base58CheckError::UnknownVersion(version)),
+        Some(&(_, len)) if len != payload.len() => Err(Base58CheckError::BadLength { version: Some(version), len: payload.len() }),
+        Some(_) => Ok((version, payload.to_vec())),}}
+
+/// Decodes a digest printed in any `-o` format except Base36 (whose padding is lossy). Formats are
+/// tried in a fixed order so the result never depends on guessing: Base58Check (the checksum rules
+/// out accidental matches), binary (any string of `0`/`1` whose length is a multiple of 8, so that
+/// truncated `-o bin` tags are not read as hex), hex, Base58, then Base64. Base58 is skipped when the string uses a character outside its alphabet (`0OIl+/=`),
+/// since every Base58 string is also valid Base64. Returns `None` if no format applies.
+pub fn decode_digest(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim();
+    if encoded.is_empty() {
+        return None;}
+    if let Ok((_, payload)) = from_base58check(encoded) {
+        return Some(payload);}
+    if encoded.len().is_multiple_of(8) && encoded.bytes().all(|b| b == b'0' || b == b'1') {
+        return encoded.as_bytes()
+            .chunks_exact(8)
+            .map(|bits| u8::from_str_radix(std::str::from_utf8(bits).ok()?, 2).ok())
+            .collect();}
+    if let Ok(bytes) = hex::decode(encoded) {
+        return Some(bytes);}
+    if let Ok(bytes) = bs58::decode(encoded).into_vec() {
+        return Some(bytes);}
+    general_purpose::STANDARD.decode(encoded)
+        .or_else(|_| general_purpose::STANDARD_NO_PAD.decode(encoded))
+        .ok()}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST_HEX: &str = "00ff10a5c3e7817e00ff10a5c3e7817e00ff10a5c3e7817e00ff10a5c3e7817e00ff10a5c3e7817e00ff10a5c3e7817e00ff10a5c3e7817e00ff10a5c3e7817e";
+
+    #[test]
+    fn decode_digest_round_trips_every_exact_format() {
+        let bytes = hex::decode(DIGEST_HEX).unwrap();
+        assert_eq!(decode_digest(DIGEST_HEX), Some(bytes.clone()));
+        assert_eq!(decode_digest(&to_binary(DIGEST_HEX)), Some(bytes.clone()));
+        assert_eq!(decode_digest(&to_base64(DIGEST_HEX)), Some(bytes.clone()));
+        assert_eq!(decode_digest(&to_base58(DIGEST_HEX)), Some(bytes.clone()));
+        assert_eq!(decode_digest(&to_base58check(DIGEST_HEX, 0x51)), Some(bytes));}
+
+    #[test]
+    fn truncated_binary_tags_are_not_read_as_hex() {
+        for len in [1, 2, 8, 16] {
+            let truncated = &DIGEST_HEX[..2 * len];
+            let bits = to_binary(truncated);
+            assert!(bits.len() <= 128);
+            assert_eq!(decode_digest(&bits), Some(hex::decode(truncated).unwrap()), "{} bytes", len);}}
+
+    #[test]
+    fn base58check_rejects_a_mistyped_character() {
+        let encoded = to_base58check(DIGEST_HEX, 0x51);
+        let mut chars: Vec<char> = encoded.chars().collect();
+        chars[5] = if chars[5] == 'a' { 'b' } else { 'a' };
+        assert!(from_base58check(&chars.into_iter().collect::<String>()).is_err());}}
@@ -0,0 +1,84 @@
+// src/hkdf.rs
+use crate::core::{hmac_qosmic_bytes, HmacQosmicKey};
+use std::fmt;
+
+pub const HASH_LEN: usize = 64;
+pub const MAX_OUTPUT_LEN: usize = 255 * HASH_LEN;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HkdfError {
+    OutputTooLong(usize),
+    PrkTooShort(usize),}
+
+impl fmt::Display for HkdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HkdfError::OutputTooLong(len) => write!(f, "requested {} bytes, HKDF-Qosmic can produce at most {}", len, MAX_OUTPUT_LEN),
+            HkdfError::PrkTooShort(len) => write!(f, "pseudorandom key is {} bytes, expected at least {}", len, HASH_LEN),}}}
+
+impl std::error::Error for HkdfError {}
+
+/// RFC 5869 extract step: `PRK = HMAC-Qosmic(salt, IKM)`. An empty salt is replaced by 64 zero bytes.
+pub fn hkdf_extract(salt: &[u8], ikm: &[u8]) -> Vec<u8> {
+    if salt.is_empty() {
+        hmac_qosmic_bytes(&[0u8; HASH_LEN], ikm).to_vec()
+    } else {
+        hmac_qosmic_bytes(salt, ikm).to_vec()}}
+
+/// RFC 5869 expand step: `T(i) = HMAC-Qosmic(PRK, T(i-1) || info || i)`, concatenated and truncated to `output_len`.
+pub fn hkdf_expand(prk: &[u8], info: &[u8], output_len: usize) -> Result<Vec<u8>, HkdfError> {
+    if output_len > MAX_OUTPUT_LEN {
+        return Err(HkdfError::OutputTooLong(output_len));}
+    if prk.len() < HASH_LEN {
+        return Err(HkdfError::PrkTooShort(prk.len()));}
+    let prf = HmacQosmicKey::new(prk);
+    let mut okm = Vec::with_capacity(output_len + HASH_LEN);
+    let mut previous: Vec<u8> = Vec::new();
+    let mut counter: u8 = 1;
+    while okm.len() < output_len {
+        let mut message = previous;
+        message.extend_from_slice(info);
+        message.push(counter);
+        previous = prf.mac(&message).to_vec();
+        okm.extend_from_slice(&previous);
+        counter = counter.wrapping_add(1);}
+    okm.truncate(output_len);
+    Ok(okm)}
+
+pub fn hkdf(salt: &[u8], ikm: &[u8], info: &[u8], output_len: usize) -> Result<Vec<u8>, HkdfError> {
+    let prk = hkdf_extract(salt, ikm);
+    hkdf_expand(&prk, info, output_len)}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IKM: [u8; 22] = [0x0b; 22];
+
+    #[test]
+    fn rfc5869_case_1_inputs() {
+        let salt = hex::decode("000102030405060708090a0b0c").unwrap();
+        let info = hex::decode("f0f1f2f3f4f5f6f7f8f9").unwrap();
+        let prk = hkdf_extract(&salt, &IKM);
+        assert_eq!(hex::encode(&prk), "f4a92b086e6980349374099ec00bd8d87bebd8cc3edc47d836a84b5d29b02feead42aaf807198ce7e7333d8b6a77ebc8d67ea5f0b9e6def364bbde1029811b95");
+        let okm = hkdf_expand(&prk, &info, 42).unwrap();
+        assert_eq!(hex::encode(&okm), "cec27ce3824bb94390eaad599ea600ae246e7f893707a1d50b3000d158ea34c90a68ae77ca8b4e7eae6b");
+        assert_eq!(hkdf(&salt, &IKM, &info, 42).unwrap(), okm);}
+
+    #[test]
+    fn rfc5869_case_3_inputs_empty_salt_and_info() {
+        let okm = hkdf(&[], &IKM, &[], 42).unwrap();
+        assert_eq!(hex::encode(okm), "180158d3ce4ec52a21ef7bac33a0f84acd4f8be268e6a3a1666291793f0c59d5a97b9ff874563f89a2d9");}
+
+    #[test]
+    fn expand_output_is_a_prefix_of_longer_output() {
+        let prk = hkdf_extract(b"salt", &IKM);
+        let long = hkdf_expand(&prk, b"ctx", 3 * HASH_LEN + 5).unwrap();
+        assert_eq!(hkdf_expand(&prk, b"ctx", 70).unwrap(), long[..70]);}
+
+    #[test]
+    fn expand_rejects_long_output_and_short_prk() {
+        let prk = hkdf_extract(b"salt", &IKM);
+        assert_eq!(hkdf_expand(&prk, b"", MAX_OUTPUT_LEN + 1), Err(HkdfError::OutputTooLong(MAX_OUTPUT_LEN + 1)));
+        assert_eq!(hkdf_expand(&prk, b"", MAX_OUTPUT_LEN).unwrap().len(), MAX_OUTPUT_LEN);
+        assert_eq!(hkdf_expand(&prk[..HASH_LEN - 1], b"", 32), Err(HkdfError::PrkTooShort(HASH_LEN - 1)));}}
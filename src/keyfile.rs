@@ -0,0 +1,428 @@
+// src/keyfile.rs
+use crate::aead;
+use crate::core::{pbkdf2_hmac_qosmic, qosmic_digest, HmacQosmicKey, KdfParams, MAX_VERIFY_ITERATIONS};
+use crate::lwe::kem::KemSecretKey;
+use crate::lwe::pke::{LwePublicKey, LweSecretKey};
+use crate::phc::PasswordHashError;
+use crate::xmss::{XmssPrivateKey, XmssPublicKey};
+use crate::xof::QosmicXof;
+use base64::{engine::general_purpose, Engine as _};
+use hex;
+use log::{debug, info};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+pub const KEY_FILE_MAGIC: &[u8; 4] = b"QKEY";
+pub const KEY_FILE_VERSION: u8 = 1;
+pub const CHECKSUM_LEN: usize = 16;
+pub const TAG_LEN: usize = 32;
+/// Largest PBKDF2 iteration count accepted in a key file header, the same limit password
+/// verification uses, so a crafted file cannot stall `load` before the password is checked.
+pub const MAX_ITERATIONS: u32 = MAX_VERIFY_ITERATIONS;
+const PROTECTION_NONE: u8 = 0;
+const PROTECTION_PBKDF2: u8 = 1;
+const KEYSTREAM_DOMAIN: &[u8] = b"qosmic-keyfile";
+const ARMOR_LINE_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyFileError {
+    InvalidEncoding(&'static str),
+    UnsupportedVersion(u8),
+    UnknownAlgorithm(String),
+    ChecksumMismatch,
+    PasswordRequired,
+    WrongPassword,
+    InvalidParameter(String),
+    Io(String),}
+
+impl fmt::Display for KeyFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyFileError::InvalidEncoding(what) => write!(f, "malformed {}", what),
+            KeyFileError::UnsupportedVersion(v) => write!(f, "unsupported key file version {}", v),
+            KeyFileError::UnknownAlgorithm(alg) => write!(f, "unknown key algorithm '{}'", alg),
+            KeyFileError::ChecksumMismatch => write!(f, "key file checksum does not match; the file is corrupted"),
+            KeyFileError::PasswordRequired => write!(f, "key file is password-protected"),
+            KeyFileError::WrongPassword => write!(f, "wrong password for key file"),
+            KeyFileError::InvalidParameter(e) => write!(f, "invalid protection parameter: {}", e),
+            KeyFileError::Io(e) => write!(f, "I/O error: {}", e),}}}
+
+impl std::error::Error for KeyFileError {}
+
+impl From<std::io::Error> for KeyFileError {
+    fn from(e: std::io::Error) -> Self {
+        KeyFileError::Io(e.to_string())}}
+
+impl From<PasswordHashError> for KeyFileError {
+    fn from(e: PasswordHashError) -> Self {
+        KeyFileError::InvalidParameter(e.to_string())}}
+
+/// What a key file holds. The payload is the key type's own encoding (`to_bytes`), or the raw
+/// secret for HMAC keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAlgorithm {
+    HmacQosmic,
+    XmssPrivate,
+    XmssPublic,
+    LwePublic,
+    LweSecret,
+    LweKemSecret,
+    Aead,}
+
+const ALGORITHMS: [KeyAlgorithm; 7] = [
+    KeyAlgorithm::HmacQosmic,
+    KeyAlgorithm::XmssPrivate,
+    KeyAlgorithm::XmssPublic,
+    KeyAlgorithm::LwePublic,
+    KeyAlgorithm::LweSecret,
+    KeyAlgorithm::LweKemSecret,
+    KeyAlgorithm::Aead,];
+
+impl KeyAlgorithm {
+    pub fn id(self) -> u8 {
+        match self {
+            KeyAlgorithm::HmacQosmic => 1,
+            KeyAlgorithm::XmssPrivate => 2,
+            KeyAlgorithm::XmssPublic => 3,
+            KeyAlgorithm::LwePublic => 4,
+            KeyAlgorithm::LweSecret => 5,
+            KeyAlgorithm::LweKemSecret => 6,
+            KeyAlgorithm::Aead => 7,}}
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        ALGORITHMS.into_iter().find(|alg| alg.id() == id)}
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyAlgorithm::HmacQosmic => "hmac-qosmic",
+            KeyAlgorithm::XmssPrivate => "xmss-private",
+            KeyAlgorithm::XmssPublic => "xmss-public",
+            KeyAlgorithm::LwePublic => "lwe-public",
+            KeyAlgorithm::LweSecret => "lwe-secret",
+            KeyAlgorithm::LweKemSecret => "lwe-kem-secret",
+            KeyAlgorithm::Aead => "aead",}}
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ALGORITHMS.into_iter().find(|alg| alg.name() == name)}
+
+    /// Label between the `-----BEGIN` and `-----END` armor lines.
+    pub fn armor_label(self) -> &'static str {
+        match self {
+            KeyAlgorithm::HmacQosmic => "QOSMIC HMAC KEY",
+            KeyAlgorithm::XmssPrivate => "QOSMIC XMSS PRIVATE KEY",
+            KeyAlgorithm::XmssPublic => "QOSMIC XMSS PUBLIC KEY",
+            KeyAlgorithm::LwePublic => "QOSMIC LWE PUBLIC KEY",
+            KeyAlgorithm::LweSecret => "QOSMIC LWE SECRET KEY",
+            KeyAlgorithm::LweKemSecret => "QOSMIC LWE KEM SECRET KEY",
+            KeyAlgorithm::Aead => "QOSMIC AEAD KEY",}}
+
+    pub fn is_secret(self) -> bool {
+        !matches!(self, KeyAlgorithm::XmssPublic | KeyAlgorithm::LwePublic)}
+
+    /// Parses `payload` as this kind of key and returns a one-line summary of it.
+    pub fn describe_payload(self, payload: &[u8]) -> Result<String, KeyFileError> {
+        let invalid = |_| KeyFileError::InvalidEncoding("key payload");
+        match self {
+            KeyAlgorithm::HmacQosmic if payload.is_empty() => Err(KeyFileError::InvalidEncoding("key payload")),
+            KeyAlgorithm::HmacQosmic => Ok(format!("{}-byte HMAC-qosmic key", payload.len())),
+            KeyAlgorithm::XmssPrivate => XmssPrivateKey::from_bytes(payload).map_err(invalid).map(|key| format!(
+                "XMSS height {}, root {}, {} signatures remaining", key.height(), hex::encode(key.public_key().root), key.remaining_signatures())),
+            KeyAlgorithm::XmssPublic => XmssPublicKey::from_bytes(payload).map_err(invalid).map(|key| format!(
+                "XMSS height {}, root {}", key.height, hex::encode(key.root))),
+            KeyAlgorithm::LwePublic => LwePublicKey::from_bytes(payload).map_err(|_| KeyFileError::InvalidEncoding("key payload")).map(|_| "LWE public key".to_string()),
+            KeyAlgorithm::LweSecret => LweSecretKey::from_bytes(payload).map_err(|_| KeyFileError::InvalidEncoding("key payload")).map(|_| "LWE secret key".to_string()),
+            KeyAlgorithm::LweKemSecret => KemSecretKey::from_bytes(payload).map_err(|_| KeyFileError::InvalidEncoding("key payload")).map(|_| "LWE KEM decapsulation key".to_string()),
+            KeyAlgorithm::Aead if payload.len() != aead::KEY_LEN => Err(KeyFileError::InvalidEncoding("key payload")),
+            KeyAlgorithm::Aead => Ok(format!("{}-byte AEAD key", aead::KEY_LEN)),}}}
+
+impl fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())}}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    Binary,
+    Armored,}
+
+/// Password protection recorded in a key file header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Protection {
+    None,
+    Pbkdf2 { iterations: u32, salt: Vec<u8> },}
+
+/// Everything `inspect` can tell about a key file without the password.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyFileInfo {
+    pub format: KeyFormat,
+    pub algorithm: KeyAlgorithm,
+    pub protection: Protection,
+    pub payload_len: usize,
+    pub checksum_ok: bool,}
+
+/// A key with its algorithm identifier.
+#[derive(Clone, PartialEq, Eq)]
+pub struct KeyFile {
+    pub algorithm: KeyAlgorithm,
+    pub key: Vec<u8>,}
+
+impl fmt::Debug for KeyFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyFile").field("algorithm", &self.algorithm).field("key_len", &self.key.len()).finish_non_exhaustive()}}
+
+/// `(encryption key, MAC key)` from PBKDF2-HMAC-Qosmic over the password.
+fn derive_protection_keys(password: &[u8], iterations: u32, salt: &[u8]) -> ([u8; 32], HmacQosmicKey) {
+    let derived = pbkdf2_hmac_qosmic(password, salt, iterations, 64);
+    let mut enc_key = [0u8; 32];
+    enc_key.copy_from_slice(&derived[..32]);
+    (enc_key, HmacQosmicKey::new(&derived[32..]))}
+
+fn apply_keystream(enc_key: &[u8; 32], data: &mut [u8]) {
+    let mut xof = QosmicXof::new(KEYSTREAM_DOMAIN, enc_key);
+    let mut keystream = vec![0u8; data.len()];
+    xof.read(&mut keystream);
+    for (byte, k) in data.iter_mut().zip(keystream.iter()) {
+        *byte ^= k;}}
+
+/// A parsed binary key file; the payload is still encrypted when protected.
+struct RawKeyFile<'a> {
+    algorithm: KeyAlgorithm,
+    protection: Protection,
+    payload: &'a [u8],
+    /// Everything the MAC covers: header and payload.
+    authenticated: &'a [u8],
+    tag: &'a [u8],
+    checksum_ok: bool,}
+
+fn parse_binary(bytes: &[u8]) -> Result<RawKeyFile<'_>, KeyFileError> {
+    let truncated = KeyFileError::InvalidEncoding("key file");
+    if bytes.len() < 7 + 4 + CHECKSUM_LEN || &bytes[..4] != KEY_FILE_MAGIC {
+        return Err(truncated);}
+    if bytes[4] != KEY_FILE_VERSION {
+        return Err(KeyFileError::UnsupportedVersion(bytes[4]));}
+    let algorithm = KeyAlgorithm::from_id(bytes[5]).ok_or_else(|| KeyFileError::UnknownAlgorithm(bytes[5].to_string()))?;
+    let mut pos = 7;
+    let protection = match bytes[6] {
+        PROTECTION_NONE => Protection::None,
+        PROTECTION_PBKDF2 => {
+            let iterations = u32::from_be_bytes(bytes.get(pos..pos + 4).ok_or(truncated.clone())?.try_into().unwrap());
+            if iterations > MAX_ITERATIONS {
+                return Err(KeyFileError::InvalidParameter(format!("iterations={} exceeds {}", iterations, MAX_ITERATIONS)));}
+            let salt_len = *bytes.get(pos + 4).ok_or(truncated.clone())? as usize;
+            let salt = bytes.get(pos + 5..pos + 5 + salt_len).ok_or(truncated.clone())?.to_vec();
+            pos += 5 + salt_len;
+            Protection::Pbkdf2 { iterations, salt }},
+        _ => return Err(KeyFileError::InvalidEncoding("key file protection")),};
+    let payload_len = u32::from_be_bytes(bytes.get(pos..pos + 4).ok_or(truncated.clone())?.try_into().unwrap()) as usize;
+    pos += 4;
+    let tag_len = if protection == Protection::None { 0 } else { TAG_LEN };
+    if bytes.len() != pos + payload_len + tag_len + CHECKSUM_LEN {
+        return Err(truncated);}
+    let body_end = pos + payload_len + tag_len;
+    let checksum = qosmic_digest(&bytes[..body_end]);
+    Ok(RawKeyFile {
+        algorithm,
+        protection,
+        payload: &bytes[pos..pos + payload_len],
+        authenticated: &bytes[..pos + payload_len],
+        tag: &bytes[pos + payload_len..body_end],
+        checksum_ok: checksum[..CHECKSUM_LEN] == bytes[body_end..],})}
+
+/// Strips the armor lines and decodes the Base64 body, checking the label against the algorithm.
+fn dearmor(text: &str) -> Result<Vec<u8>, KeyFileError> {
+    let invalid = KeyFileError::InvalidEncoding("key file armor");
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let label = lines.next()
+        .and_then(|line| line.strip_prefix("-----BEGIN "))
+        .and_then(|line| line.strip_suffix("-----"))
+        .ok_or(invalid.clone())?;
+    let mut body = String::new();
+    let mut ended = false;
+    for line in lines.by_ref() {
+        if line == format!("-----END {}-----", label) {
+            ended = true;
+            break;}
+        body.push_str(line);}
+    if !ended || lines.next().is_some() {
+        return Err(invalid);}
+    let bytes = general_purpose::STANDARD.decode(body).map_err(|_| invalid.clone())?;
+    let raw = parse_binary(&bytes)?;
+    if raw.algorithm.armor_label() != label {
+        return Err(invalid);}
+    Ok(bytes)}
+
+fn is_armored(bytes: &[u8]) -> bool {
+    bytes.trim_ascii_start().starts_with(b"-----BEGIN ")}
+
+/// Returns the binary key file inside `bytes`, removing the armor if present.
+fn binary_of(bytes: &[u8]) -> Result<(KeyFormat, Vec<u8>), KeyFileError> {
+    if is_armored(bytes) {
+        let text = std::str::from_utf8(bytes).map_err(|_| KeyFileError::InvalidEncoding("key file armor"))?;
+        Ok((KeyFormat::Armored, dearmor(text)?))
+    } else {
+        Ok((KeyFormat::Binary, bytes.to_vec()))}}
+
+/// Reads the header and checksum of a binary or armored key file without decrypting it.
+pub fn inspect(bytes: &[u8]) -> Result<KeyFileInfo, KeyFileError> {
+    let (format, binary) = binary_of(bytes)?;
+    let raw = parse_binary(&binary)?;
+    Ok(KeyFileInfo { format, algorithm: raw.algorithm, protection: raw.protection, payload_len: raw.payload.len(), checksum_ok: raw.checksum_ok })}
+
+impl KeyFile {
+    pub fn new(algorithm: KeyAlgorithm, key: &[u8]) -> Self {
+        KeyFile { algorithm, key: key.to_vec() }}
+
+    /// Binary key file: `"QKEY" || version (0x01) || algorithm (u8) || protection (u8)`, then for
+    /// protection 1 `iterations (u32) || salt_len (u8) || salt`, then `payload_len (u32) || payload`,
+    /// then for protection 1 a 32-byte HMAC-qosmic tag over everything before it, and finally the
+    /// first 16 bytes of the qosmic digest of everything before as a checksum.
+    ///
+    /// With a password, PBKDF2-HMAC-Qosmic (`params.iterations`, a fresh `params.salt_len`-byte salt
+    /// or `params.salt`) yields 64 bytes: the first half keys a qosmic XOF keystream that encrypts the
+    /// payload, the second half keys the tag, so a wrong password is detected before decryption.
+    pub fn to_binary(&self, password: Option<&[u8]>, params: &KdfParams) -> Result<Vec<u8>, KeyFileError> {
+        let mut out = KEY_FILE_MAGIC.to_vec();
+        out.push(KEY_FILE_VERSION);
+        out.push(self.algorithm.id());
+        let mut payload = self.key.clone();
+        let mut mac_key = None;
+        match password {
+            None => out.push(PROTECTION_NONE),
+            Some(password) => {
+                params.validate()?;
+                if params.iterations > MAX_ITERATIONS {
+                    return Err(KeyFileError::InvalidParameter(format!("iterations={} exceeds {}", params.iterations, MAX_ITERATIONS)));}
+                let salt = params.salt_or_generate();
+                if salt.len() > u8::MAX as usize {
+                    return Err(KeyFileError::InvalidParameter(format!("salt_len={}", salt.len())));}
+                out.push(PROTECTION_PBKDF2);
+                out.extend_from_slice(&params.iterations.to_be_bytes());
+                out.push(salt.len() as u8);
+                out.extend_from_slice(&salt);
+                let (enc_key, key) = derive_protection_keys(password, params.iterations, &salt);
+                apply_keystream(&enc_key, &mut payload);
+                mac_key = Some(key);},}
+        let payload_len = u32::try_from(payload.len()).map_err(|_| KeyFileError::InvalidEncoding("key payload (too long)"))?;
+        out.extend_from_slice(&payload_len.to_be_bytes());
+        out.extend_from_slice(&payload);
+        if let Some(mac_key) = mac_key {
+            let tag = mac_key.mac(&out);
+            out.extend_from_slice(&tag[..TAG_LEN]);}
+        let checksum = qosmic_digest(&out);
+        out.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+        debug!("Encoded {} key file: {} bytes, protected: {}", self.algorithm, out.len(), password.is_some());
+        Ok(out)}
+
+    /// The binary encoding as Base64 in 64-character lines between `-----BEGIN <label>-----` and
+    /// `-----END <label>-----`, where the label names the algorithm (e.g. `QOSMIC XMSS PRIVATE KEY`).
+    pub fn to_armored(&self, password: Option<&[u8]>, params: &KdfParams) -> Result<String, KeyFileError> {
+        let encoded = general_purpose::STANDARD.encode(self.to_binary(password, params)?);
+        let label = self.algorithm.armor_label();
+        let mut out = format!("-----BEGIN {}-----\n", label);
+        for line in encoded.as_bytes().chunks(ARMOR_LINE_LEN) {
+            out.push_str(std::str::from_utf8(line).expect("Base64 is ASCII"));
+            out.push('\n');}
+        out.push_str(&format!("-----END {}-----\n", label));
+        Ok(out)}
+
+    /// Parses a binary or armored key file. The checksum is checked first, then, for protected
+    /// files, the password.
+    pub fn from_bytes(bytes: &[u8], password: Option<&[u8]>) -> Result<Self, KeyFileError> {
+        let (_, binary) = binary_of(bytes)?;
+        let raw = parse_binary(&binary)?;
+        if !raw.checksum_ok {
+            return Err(KeyFileError::ChecksumMismatch);}
+        let mut key = raw.payload.to_vec();
+        if let Protection::Pbkdf2 { iterations, salt } = &raw.protection {
+            let password = password.ok_or(KeyFileError::PasswordRequired)?;
+            let (enc_key, mac_key) = derive_protection_keys(password, *iterations, salt);
+            if !mac_key.verify(raw.authenticated, raw.tag, TAG_LEN) {
+                return Err(KeyFileError::WrongPassword);}
+            apply_keystream(&enc_key, &mut key);}
+        Ok(KeyFile { algorithm: raw.algorithm, key })}
+
+    pub fn save(&self, path: &Path, format: KeyFormat, password: Option<&[u8]>, params: &KdfParams) -> Result<(), KeyFileError> {
+        let bytes = match format {
+            KeyFormat::Binary => self.to_binary(password, params)?,
+            KeyFormat::Armored => self.to_armored(password, params)?.into_bytes(),};
+        fs::write(path, bytes)?;
+        info!("Saved {} key to {}", self.algorithm, path.display());
+        Ok(())}
+
+    pub fn load(path: &Path, password: Option<&[u8]>) -> Result<Self, KeyFileError> {
+        KeyFile::from_bytes(&fs::read(path)?, password)}
+
+    /// First 16 bytes of the qosmic digest of the key, for telling keys apart.
+    pub fn fingerprint(&self) -> String {
+        hex::encode(&qosmic_digest(&self.key)[..16])}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> KdfParams {
+        KdfParams { iterations: 2, salt: Some(b"keyfile test salt".to_vec()), ..KdfParams::default() }}
+
+    fn sample() -> KeyFile {
+        KeyFile::new(KeyAlgorithm::HmacQosmic, b"a secret HMAC-qosmic key")}
+
+    #[test]
+    fn unprotected_binary_and_armored_round_trip() {
+        let key = sample();
+        let binary = key.to_binary(None, &params()).unwrap();
+        assert_eq!(&binary[..4], KEY_FILE_MAGIC);
+        assert_eq!(KeyFile::from_bytes(&binary, None).unwrap(), key);
+        let armored = key.to_armored(None, &params()).unwrap();
+        assert!(armored.starts_with("-----BEGIN QOSMIC HMAC KEY-----\n"));
+        assert!(armored.lines().all(|line| line.len() <= ARMOR_LINE_LEN || line.starts_with("-----")));
+        assert_eq!(KeyFile::from_bytes(armored.as_bytes(), None).unwrap(), key);
+        let info = inspect(armored.as_bytes()).unwrap();
+        assert_eq!((info.format, info.algorithm, info.protection, info.payload_len, info.checksum_ok),
+            (KeyFormat::Armored, KeyAlgorithm::HmacQosmic, Protection::None, key.key.len(), true));}
+
+    #[test]
+    fn protected_round_trip_and_wrong_password() {
+        let key = sample();
+        for bytes in [key.to_binary(Some(b"hunter2"), &params()).unwrap(), key.to_armored(Some(b"hunter2"), &params()).unwrap().into_bytes()] {
+            assert_eq!(KeyFile::from_bytes(&bytes, Some(b"hunter2")).unwrap(), key);
+            assert_eq!(KeyFile::from_bytes(&bytes, Some(b"hunter3")), Err(KeyFileError::WrongPassword));
+            assert_eq!(KeyFile::from_bytes(&bytes, None), Err(KeyFileError::PasswordRequired));
+            let info = inspect(&bytes).unwrap();
+            assert_eq!(info.protection, Protection::Pbkdf2 { iterations: 2, salt: b"keyfile test salt".to_vec() });}
+        let binary = key.to_binary(Some(b"hunter2"), &params()).unwrap();
+        assert!(!binary.windows(key.key.len()).any(|w| w == key.key.as_slice()));}
+
+    #[test]
+    fn corruption_and_bad_armor_are_detected() {
+        let key = sample();
+        let mut binary = key.to_binary(None, &params()).unwrap();
+        let last = binary.len() - CHECKSUM_LEN - 1;
+        binary[last] ^= 1;
+        assert_eq!(KeyFile::from_bytes(&binary, None), Err(KeyFileError::ChecksumMismatch));
+        assert!(!inspect(&binary).unwrap().checksum_ok);
+        binary.pop();
+        assert_eq!(KeyFile::from_bytes(&binary, None), Err(KeyFileError::InvalidEncoding("key file")));
+
+        let armored = key.to_armored(None, &params()).unwrap();
+        let relabeled = armored.replace("QOSMIC HMAC KEY", "QOSMIC AEAD KEY");
+        assert_eq!(KeyFile::from_bytes(relabeled.as_bytes(), None), Err(KeyFileError::InvalidEncoding("key file armor")));
+        let unterminated = armored.replace("-----END QOSMIC HMAC KEY-----\n", "");
+        assert_eq!(KeyFile::from_bytes(unterminated.as_bytes(), None), Err(KeyFileError::InvalidEncoding("key file armor")));}
+
+    #[test]
+    fn excessive_iterations_are_rejected_while_parsing() {
+        let mut binary = sample().to_binary(Some(b"hunter2"), &params()).unwrap();
+        binary[7..11].copy_from_slice(&(MAX_ITERATIONS + 1).to_be_bytes());
+        let expected = KeyFileError::InvalidParameter(format!("iterations={} exceeds {}", MAX_ITERATIONS + 1, MAX_ITERATIONS));
+        assert_eq!(inspect(&binary), Err(expected.clone()));
+        assert_eq!(KeyFile::from_bytes(&binary, Some(b"hunter2")), Err(expected.clone()));
+        let params = KdfParams { iterations: MAX_ITERATIONS + 1, ..params() };
+        assert_eq!(sample().to_binary(Some(b"hunter2"), &params), Err(expected));}
+
+    #[test]
+    fn algorithm_ids_and_names_round_trip() {
+        for alg in ALGORITHMS {
+            assert_eq!(KeyAlgorithm::from_id(alg.id()), Some(alg));
+            assert_eq!(KeyAlgorithm::from_name(alg.name()), Some(alg));}
+        assert_eq!(KeyAlgorithm::from_id(0), None);
+        assert_eq!(KeyAlgorithm::Aead.describe_payload(&[0; 5]), Err(KeyFileError::InvalidEncoding("key payload")));}}
@@ -8,13 +8,45 @@ pub mod primitives;
 pub mod components;
 pub mod core;
 pub mod encode;
+pub mod phc;
+pub mod memhard;
+pub mod hkdf;
+pub mod rehash;
+pub mod pepper;
+pub mod calibrate;
+pub mod mac;
+pub mod xmss;
+pub mod lwe;
+pub mod xof;
+pub mod cert;
+pub mod keyfile;
+pub mod merkle;
+pub mod transparency;
+pub mod smt;
+pub mod aead;
+pub mod drbg;
 
-pub use core::{get_sbox, hmac_qosmic, qosmic_unkeyed, SBoxType, hash_password, pbkdf2_hmac_qosmic};
+pub use core::{get_sbox, hmac_qosmic, hmac_qosmic_bytes, qosmic_unkeyed, qosmic_unkeyed_bytes, qosmic_digest, qosmic_lattice_digest, HmacQosmicKey, SBoxType, hash_password, hash_password_with_params, hash_password_with_pepper, verify_password, verify_password_with_peppers, derive_key, pbkdf2_hmac_qosmic, pbkdf2_hmac_qosmic_with_progress, KdfError, KdfParams};
+pub use phc::{PasswordHashError, PhcString};
+pub use memhard::{hash_password_memory_hard, hash_password_memory_hard_with_pepper, memory_hard_kdf, MemoryHardParams};
+pub use hkdf::{hkdf, hkdf_expand, hkdf_extract, HkdfError};
+pub use pepper::{parse_pepper_list, Pepper};
+pub use calibrate::{calibrate_memory_hard, calibrate_pbkdf2, CalibratedParams, Calibration};
+pub use mac::{verify_hmac, verify_hmac_bytes, MacKeyRing};
+pub use xmss::{sign_with_state_file, SignatureError, StateLock, XmssPrivateKey, XmssPublicKey, XmssSignature};
+pub use cert::{issue, issue_with_state_file, verify_chain, CertError, Certificate, TbsCertificate, TrustStore};
+pub use keyfile::{KeyAlgorithm, KeyFile, KeyFileError, KeyFormat};
+pub use merkle::{ConsistencyProof, InclusionProof, MerkleError, MerkleTree};
+pub use transparency::{HeadVerifier, LogError, SignedTreeHead, TransparencyLog, TreeHead};
+pub use smt::{FileStore, MemoryStore, SmtError, SmtProof, SmtStore, SparseMerkleTree};
+pub use aead::{decrypt_stream, encrypt_stream, AeadError, KeySource, StreamHeader};
+pub use drbg::{DrbgError, HmacDrbg};
+pub use rehash::{needs_rehash, rehash_reasons, RehashPolicy, RehashReason};
 pub use utils::is_prime;
 
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
-use libc::size_t as c_size_t;
+use libc::{c_int, size_t as c_size_t};
 use log::info;
 
 #[repr(C)]
@@ -23,7 +55,10 @@ pub enum QosmicErrorCode {
     NullInput = 1,
     CStringConversionError = 2,
     HexDecodingError = 3,
-    MemoryAllocationError = 4,}
+    MemoryAllocationError = 4,
+    InvalidParameter = 5,
+    InvalidHashFormat = 6,
+    CostLimitExceeded = 7,}
 
 /// @param input_ptr A pointer to the byte array to be hashed.
 /// @param input_len The length of the byte array.
@@ -124,6 +159,136 @@ pub unsafe extern "C" fn qosmic_pbkdf2_hmac_qosmic(
     *derived_key_actual_len_ptr = len as c_size_t;
     QosmicErrorCode::Success}
 
+/// @param password_ptr A pointer to the password byte array.
+/// @param password_len The length of the password byte array.
+/// @param iterations The number of PBKDF2 iterations, or 0 for the default (10,000).
+/// @param salt_len The length of the random salt in bytes, or 0 for the default (16).
+/// @param output_len The length of the derived key in bytes, or 0 for the default (64).
+/// @param output_hash_ptr A pointer to a `char*` where the PHC-format hash string will be stored.
+/// @return A `QosmicErrorCode` indicating success or the type of error.
+///
+/// # Safety
+/// `password_ptr` must point to `password_len` readable bytes and `output_hash_ptr` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qosmic_hash_password(
+    password_ptr: *const u8,
+    password_len: c_size_t,
+    iterations: u32,
+    salt_len: c_size_t,
+    output_len: c_size_t,
+    output_hash_ptr: *mut *mut c_char,
+) -> QosmicErrorCode {
+    unsafe { *output_hash_ptr = std::ptr::null_mut(); }
+    if password_ptr.is_null() {
+        info!("qosmic_hash_password: Null password_ptr.");
+        return QosmicErrorCode::NullInput;}
+    let password_slice = unsafe { std::slice::from_raw_parts(password_ptr, password_len) };
+    let defaults = core::KdfParams::default();
+    let params = core::KdfParams {
+        iterations: if iterations == 0 { defaults.iterations } else { iterations },
+        salt_len: if salt_len == 0 { defaults.salt_len } else { salt_len },
+        output_len: if output_len == 0 { defaults.output_len } else { output_len },
+        salt: None,};
+    info!("qosmic_hash_password: Received password_len={}, params={:?}", password_len, params);
+    let phc = match core::hash_password_with_params(password_slice, &params) {
+        Ok(phc) => phc,
+        Err(e) => {
+            info!("qosmic_hash_password: Invalid parameters: {}", e);
+            return QosmicErrorCode::InvalidParameter;}};
+    match CString::new(phc) {
+        Ok(c_string) => {
+            unsafe { *output_hash_ptr = c_string.into_raw(); }
+            QosmicErrorCode::Success},
+        Err(e) => {
+            info!("qosmic_hash_password: Failed to convert hash to CString: {}", e);
+            QosmicErrorCode::CStringConversionError}}}
+
+/// @param password_ptr A pointer to the password byte array.
+/// @param password_len The length of the password byte array.
+/// @param stored_hash_ptr A null-terminated PHC or legacy `salt$hash` string.
+/// @param result_ptr A pointer to an `int` set to 1 if the password matches and 0 otherwise.
+/// @return A `QosmicErrorCode` indicating success or the type of error. Hashes whose cost exceeds
+/// `MAX_VERIFY_ITERATIONS` (or the memory-hard limits) yield `CostLimitExceeded` without being checked.
+///
+/// # Safety
+/// `password_ptr` must point to `password_len` readable bytes, `stored_hash_ptr` must be a
+/// null-terminated string and `result_ptr` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qosmic_verify_password(
+    password_ptr: *const u8,
+    password_len: c_size_t,
+    stored_hash_ptr: *const c_char,
+    result_ptr: *mut c_int,
+) -> QosmicErrorCode {
+    unsafe { *result_ptr = 0; }
+    if password_ptr.is_null() || stored_hash_ptr.is_null() {
+        info!("qosmic_verify_password: Null password_ptr or stored_hash_ptr.");
+        return QosmicErrorCode::NullInput;}
+    let password_slice = unsafe { std::slice::from_raw_parts(password_ptr, password_len) };
+    let stored = match unsafe { CStr::from_ptr(stored_hash_ptr) }.to_str() {
+        Ok(s) => s,
+        Err(e) => {
+            info!("qosmic_verify_password: Stored hash is not valid UTF-8: {}", e);
+            return QosmicErrorCode::CStringConversionError;}};
+    match core::verify_password(password_slice, stored) {
+        Ok(matched) => {
+            unsafe { *result_ptr = matched as c_int; }
+            QosmicErrorCode::Success},
+        Err(e @ PasswordHashError::Kdf(KdfError::CostLimitExceeded { .. })) => {
+            info!("qosmic_verify_password: Refusing to verify: {}", e);
+            QosmicErrorCode::CostLimitExceeded},
+        Err(e) => {
+            info!("qosmic_verify_password: Could not verify: {}", e);
+            QosmicErrorCode::InvalidHashFormat}}}
+
+/// @param ikm_ptr A pointer to the input keying material.
+/// @param ikm_len The length of the input keying material.
+/// @param salt_ptr A pointer to the salt, or null for no salt.
+/// @param salt_len The length of the salt.
+/// @param info_ptr A pointer to the context/application info, or null for none.
+/// @param info_len The length of the info.
+/// @param output_len_requested The desired output length in bytes (at most 255 * 64).
+/// @param derived_key_ptr A pointer to a `uint8_t*` where the output bytes will be stored.
+/// @param derived_key_actual_len_ptr A pointer to a `size_t` where the actual output length will be stored.
+/// @return A `QosmicErrorCode` indicating success or the type of error.
+///
+/// # Safety
+/// Each non-null pointer must point to the stated number of readable bytes, and both output pointers must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qosmic_hkdf(
+    ikm_ptr: *const u8,
+    ikm_len: c_size_t,
+    salt_ptr: *const u8,
+    salt_len: c_size_t,
+    info_ptr: *const u8,
+    info_len: c_size_t,
+    output_len_requested: c_size_t,
+    derived_key_ptr: *mut *mut u8,
+    derived_key_actual_len_ptr: *mut c_size_t,
+) -> QosmicErrorCode {
+    unsafe {
+        *derived_key_ptr = std::ptr::null_mut();
+        *derived_key_actual_len_ptr = 0;}
+    if ikm_ptr.is_null() {
+        info!("qosmic_hkdf: Null ikm_ptr.");
+        return QosmicErrorCode::NullInput;}
+    let ikm_slice = unsafe { std::slice::from_raw_parts(ikm_ptr, ikm_len) };
+    let salt_slice: &[u8] = if salt_ptr.is_null() { &[] } else { unsafe { std::slice::from_raw_parts(salt_ptr, salt_len) } };
+    let info_slice: &[u8] = if info_ptr.is_null() { &[] } else { unsafe { std::slice::from_raw_parts(info_ptr, info_len) } };
+    info!("qosmic_hkdf: Received ikm_len={}, salt_len={}, info_len={}, output_len_requested={}",
+        ikm_len, salt_slice.len(), info_slice.len(), output_len_requested);
+    let okm = match hkdf::hkdf(salt_slice, ikm_slice, info_slice, output_len_requested) {
+        Ok(okm) => okm,
+        Err(e) => {
+            info!("qosmic_hkdf: {}", e);
+            return QosmicErrorCode::InvalidParameter;}};
+    let buffer = okm.into_boxed_slice();
+    let len = buffer.len();
+    unsafe {
+        *derived_key_ptr = Box::into_raw(buffer) as *mut u8;
+        *derived_key_actual_len_ptr = len;}
+    QosmicErrorCode::Success}
+
 /// @param ptr A pointer to the byte array to be freed.
 /// @param len The length of the byte array. This *must* be the same length as returned by the allocation function.
 #[unsafe(no_mangle)]
@@ -0,0 +1,146 @@
+// src/lwe/kem.rs
+use crate::core::qosmic_digest;
+use crate::hkdf::hkdf;
+use crate::lwe::pke::{self, LweCiphertext, LwePublicKey, LweSecretKey, MESSAGE_BYTES, SEED_BYTES};
+use crate::lwe::LweError;
+use crate::utils;
+use log::debug;
+use rand::RngCore;
+use std::fmt;
+
+pub const KEM_VERSION: u8 = 1;
+pub const SHARED_SECRET_BYTES: usize = 32;
+pub const KEM_SEED_BYTES: usize = SEED_BYTES + 32;
+pub const KEM_SECRET_KEY_BYTES: usize = 1 + pke::SECRET_KEY_BYTES + pke::PUBLIC_KEY_BYTES + 32;
+const DOMAIN_COINS: &[u8] = b"qosmic-lwe-kem G";
+const KDF_INFO: &[u8] = b"qosmic-lwe-kem shared secret";
+
+/// Decapsulation key: the PKE secret key, a copy of the public key for the re-encryption check,
+/// and the implicit-rejection secret `z`.
+#[derive(Clone)]
+pub struct KemSecretKey {
+    sk: LweSecretKey,
+    pk: LwePublicKey,
+    z: [u8; 32],}
+
+impl fmt::Debug for KemSecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("KemSecretKey { .. }")}}
+
+impl KemSecretKey {
+    pub fn public_key(&self) -> &LwePublicKey {
+        &self.pk}
+
+    /// `[version][PKE secret key][PKE public key][z]`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(KEM_SECRET_KEY_BYTES);
+        out.push(KEM_VERSION);
+        out.extend_from_slice(&self.sk.to_bytes());
+        out.extend_from_slice(&self.pk.to_bytes());
+        out.extend_from_slice(&self.z);
+        out}
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LweError> {
+        match bytes.first() {
+            Some(&v) if v != KEM_VERSION => return Err(LweError::UnsupportedVersion(v)),
+            Some(_) if bytes.len() == KEM_SECRET_KEY_BYTES => {},
+            _ => return Err(LweError::InvalidEncoding("KEM secret key")),}
+        let pk_start = 1 + pke::SECRET_KEY_BYTES;
+        let z_start = pk_start + pke::PUBLIC_KEY_BYTES;
+        let mut z = [0u8; 32];
+        z.copy_from_slice(&bytes[z_start..]);
+        Ok(KemSecretKey {
+            sk: LweSecretKey::from_bytes(&bytes[1..pk_start])?,
+            pk: LwePublicKey::from_bytes(&bytes[pk_start..z_start])?,
+            z,})}}
+
+/// Encryption coins `G(m)`: the ciphertext is a deterministic function of the message.
+fn derive_coins(message: &[u8; MESSAGE_BYTES]) -> [u8; SEED_BYTES] {
+    let mut input = DOMAIN_COINS.to_vec();
+    input.extend_from_slice(message);
+    let mut coins = [0u8; SEED_BYTES];
+    coins.copy_from_slice(&qosmic_digest(&input)[..SEED_BYTES]);
+    coins}
+
+/// `HKDF-Qosmic(ikm = secret || qosmic(ciphertext))`, so the shared secret depends on the whole ciphertext.
+fn derive_shared_secret(secret: &[u8; 32], ciphertext: &[u8]) -> [u8; SHARED_SECRET_BYTES] {
+    let mut ikm = secret.to_vec();
+    ikm.extend_from_slice(&qosmic_digest(ciphertext)[..32]);
+    let okm = hkdf(&[], &ikm, KDF_INFO, SHARED_SECRET_BYTES).expect("32 bytes is within the HKDF output limit");
+    let mut shared = [0u8; SHARED_SECRET_BYTES];
+    shared.copy_from_slice(&okm);
+    shared}
+
+pub fn keygen() -> (LwePublicKey, KemSecretKey) {
+    let (pk, sk) = pke::keygen();
+    let mut z = [0u8; 32];
+    rand::rng().fill_bytes(&mut z);
+    (pk.clone(), KemSecretKey { sk, pk, z })}
+
+/// Deterministic key generation from `d || z` for known-answer tests. Slow, see `pke::keygen_from_seed`.
+pub fn keygen_from_seed(seed: &[u8; KEM_SEED_BYTES]) -> (LwePublicKey, KemSecretKey) {
+    let mut d = [0u8; SEED_BYTES];
+    d.copy_from_slice(&seed[..SEED_BYTES]);
+    let (pk, sk) = pke::keygen_from_seed(&d);
+    let mut z = [0u8; 32];
+    z.copy_from_slice(&seed[SEED_BYTES..]);
+    (pk.clone(), KemSecretKey { sk, pk, z })}
+
+pub fn encapsulate(pk: &LwePublicKey) -> (LweCiphertext, [u8; SHARED_SECRET_BYTES]) {
+    let mut message = [0u8; MESSAGE_BYTES];
+    rand::rng().fill_bytes(&mut message);
+    encapsulate_derand(pk, &message)}
+
+/// Encapsulation with a caller-chosen message `m`, for known-answer tests. `m` must be uniformly
+/// random and secret in real use.
+pub fn encapsulate_derand(pk: &LwePublicKey, message: &[u8; MESSAGE_BYTES]) -> (LweCiphertext, [u8; SHARED_SECRET_BYTES]) {
+    let ciphertext = pke::encrypt_with_coins(pk, message, &derive_coins(message));
+    let shared = derive_shared_secret(message, &ciphertext.to_bytes());
+    (ciphertext, shared)}
+
+/// Fujisaki–Okamoto decapsulation with implicit rejection: the recovered message is re-encrypted,
+/// and if the result differs from `ciphertext` the shared secret is derived from `z` instead, so
+/// a tampered ciphertext yields an unrelated key rather than an error. The choice is made without
+/// branching on the comparison.
+pub fn decapsulate(sk: &KemSecretKey, ciphertext: &LweCiphertext) -> [u8; SHARED_SECRET_BYTES] {
+    let message = pke::decrypt(&sk.sk, ciphertext);
+    let received = ciphertext.to_bytes();
+    let reencrypted = pke::encrypt_with_coins(&sk.pk, &message, &derive_coins(&message)).to_bytes();
+    let mask = (utils::constant_time_eq(&received, &reencrypted) as u8).wrapping_neg();
+    let mut secret = [0u8; 32];
+    for ((out, &m), &z) in secret.iter_mut().zip(message.iter()).zip(sk.z.iter()) {
+        *out = (m & mask) | (z & !mask);}
+    debug!("LWE KEM decapsulation complete");
+    derive_shared_secret(&secret, &received)}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::Q_MOD;
+
+    #[test]
+    fn known_answer_and_implicit_rejection() {
+        let seed: [u8; KEM_SEED_BYTES] = std::array::from_fn(|i| i as u8);
+        let message: [u8; MESSAGE_BYTES] = std::array::from_fn(|i| 0x80 + i as u8);
+        let (pk, sk) = keygen_from_seed(&seed);
+        assert_eq!(hex::encode(&qosmic_digest(&pk.to_bytes())[..16]), "69765ea36bd460a12176b9d69ab3f35c");
+        let (ct, shared) = encapsulate_derand(&pk, &message);
+        assert_eq!(hex::encode(&qosmic_digest(&ct.to_bytes())[..16]), "23cc2d57465b268260c104caaa0b7a51");
+        assert_eq!(hex::encode(shared), "be4658c4696a42b39192b8d0ab238115a6ef221e582d24306e86011a7a8d4969");
+        assert_eq!(decapsulate(&sk, &ct), shared);
+
+        let mut tampered = ct.clone();
+        tampered.u[0] = (tampered.u[0] + 1) % Q_MOD;
+        let rejected = decapsulate(&sk, &tampered);
+        assert_ne!(rejected, shared);
+        assert_eq!(rejected, derive_shared_secret(&sk.z, &tampered.to_bytes()));}
+
+    #[test]
+    fn random_encapsulation_round_trip_and_key_encoding() {
+        let (pk, sk) = keygen();
+        let (ct, shared) = encapsulate(&pk);
+        let decoded = KemSecretKey::from_bytes(&sk.to_bytes()).unwrap();
+        assert_eq!(decoded.public_key(), &pk);
+        assert_eq!(decapsulate(&decoded, &ct), shared);
+        assert_eq!(KEM_SECRET_KEY_BYTES, 1 + pke::SECRET_KEY_BYTES + pke::PUBLIC_KEY_BYTES + 32);
+        assert!(KemSecretKey::from_bytes(&sk.to_bytes()[..KEM_SECRET_KEY_BYTES - 1]).is_err());}}
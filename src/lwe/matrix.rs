@@ -0,0 +1,132 @@
+// src/lwe/matrix.rs
+use crate::constants::Q_MOD;
+use crate::xof::QosmicXof;
+use std::ops::Range;
+
+/// Bytes of XOF output per candidate coefficient.
+const WORD_BYTES: usize = 6;
+/// Largest multiple of `Q_MOD` below 2^48. Words at or above it are rejected so that reducing the
+/// rest mod `Q_MOD` stays uniform; that happens for fewer than one word in a thousand.
+const WORD_LIMIT: u64 = (1 << 48) / Q_MOD * Q_MOD;
+
+/// Fills `out` with uniform values mod `Q_MOD` from 48-bit big-endian XOF words.
+fn fill_uniform(xof: &mut QosmicXof, out: &mut [u64]) {
+    let mut word = [0u8; 8];
+    let mut filled = 0;
+    while filled < out.len() {
+        xof.read(&mut word[8 - WORD_BYTES..]);
+        let x = u64::from_be_bytes(word);
+        if x < WORD_LIMIT {
+            out[filled] = x % Q_MOD;
+            filled += 1;}}}
+
+/// A `rows × cols` matrix of uniform values mod `Q_MOD` that is never stored. Row `i` is expanded
+/// on demand from `QosmicXof(domain, seed || i (u32))`, the way Kyber and FrodoKEM expand their
+/// public matrices, so only the seed has to be kept or sent. Rows are independent: any one can
+/// be regenerated alone, and rows can be expanded on several cores at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededMatrix {
+    domain: &'static [u8],
+    seed: Vec<u8>,
+    rows: usize,
+    cols: usize,}
+
+impl SeededMatrix {
+    pub fn new(domain: &'static [u8], seed: &[u8], rows: usize, cols: usize) -> Self {
+        assert!(rows <= u32::MAX as usize, "row indices must fit in a u32");
+        SeededMatrix { domain, seed: seed.to_vec(), rows, cols }}
+
+    pub fn rows(&self) -> usize {
+        self.rows}
+
+    pub fn cols(&self) -> usize {
+        self.cols}
+
+    pub fn seed(&self) -> &[u8] {
+        &self.seed}
+
+    /// Expands row `index` into `out`, which must hold exactly `cols` values.
+    pub fn row_into(&self, index: usize, out: &mut [u64]) {
+        assert!(index < self.rows, "row {} out of range for {} rows", index, self.rows);
+        assert_eq!(out.len(), self.cols, "row buffer must hold {} values", self.cols);
+        let mut row_seed = Vec::with_capacity(self.seed.len() + 4);
+        row_seed.extend_from_slice(&self.seed);
+        row_seed.extend_from_slice(&(index as u32).to_be_bytes());
+        fill_uniform(&mut QosmicXof::new(self.domain, &row_seed), out);}
+
+    pub fn row(&self, index: usize) -> Vec<u64> {
+        let mut out = vec![0u64; self.cols];
+        self.row_into(index, &mut out);
+        out}
+
+    /// Contiguous row ranges, one per core.
+    fn worker_ranges(&self) -> Vec<Range<usize>> {
+        let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(self.rows).max(1);
+        let per_worker = self.rows.div_ceil(workers).max(1);
+        (0..self.rows).step_by(per_worker).map(|start| start..(start + per_worker).min(self.rows)).collect()}
+
+    /// Runs `work` on each worker's row range on its own thread and returns the results in row order.
+    fn on_workers<T, W>(&self, work: W) -> Vec<T>
+    where
+        T: Send,
+        W: Fn(Range<usize>, &mut [u64]) -> T + Sync,{
+        let work = &work;
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = self.worker_ranges().into_iter()
+                .map(|range| scope.spawn(move || work(range, &mut vec![0u64; self.cols])))
+                .collect();
+            handles.into_iter().map(|h| h.join().expect("matrix row worker panicked")).collect()})}
+
+    /// Returns `f(i, row i)` for every row, in row order. Each worker reuses a single row buffer,
+    /// so memory stays at one row per core however large the matrix is.
+    pub fn map_rows<T, F>(&self, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(usize, &[u64]) -> T + Sync,{
+        self.on_workers(|range, row| range.map(|i| {
+            self.row_into(i, row);
+            f(i, row)}).collect::<Vec<T>>())
+            .into_iter().flatten().collect()}
+
+    /// Folds every row into a per-worker accumulator, `f(&mut acc, i, row i)` with `acc` starting at
+    /// `init()`, and returns one accumulator per worker for the caller to combine. This computes
+    /// products such as `A^T r` without holding more than one row per core.
+    pub fn fold_rows<T, I, F>(&self, init: I, f: F) -> Vec<T>
+    where
+        T: Send,
+        I: Fn() -> T + Sync,
+        F: Fn(&mut T, usize, &[u64]) + Sync,{
+        self.on_workers(|range, row| {
+            let mut acc = init();
+            for i in range {
+                self.row_into(i, row);
+                f(&mut acc, i, row);}
+            acc})}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOMAIN: &[u8] = b"qosmic-matrix test";
+
+    #[test]
+    fn rows_are_uniform_mod_q_and_reproducible() {
+        let m = SeededMatrix::new(DOMAIN, b"seed", 5, 300);
+        let row = m.row(3);
+        assert!(row.iter().all(|&x| x < Q_MOD));
+        assert_eq!(row, SeededMatrix::new(DOMAIN, b"seed", 5, 300).row(3));
+        assert_ne!(row, m.row(2));
+        assert_ne!(row, SeededMatrix::new(DOMAIN, b"seef", 5, 300).row(3));
+        assert_eq!(m.row(0)[..4], [68809241581, 195666014628, 195098744990, 32168298884]);}
+
+    #[test]
+    fn map_and_fold_visit_every_row_once() {
+        let m = SeededMatrix::new(DOMAIN, b"fold", 37, 8);
+        let rows = m.map_rows(|i, row| (i, row.to_vec()));
+        assert_eq!(rows.len(), 37);
+        for (i, (index, row)) in rows.iter().enumerate() {
+            assert_eq!((*index, row), (i, &m.row(i)));}
+        let sums = m.fold_rows(|| vec![0u64; 8], |acc, _, row| acc.iter_mut().zip(row).for_each(|(a, &x)| *a += x));
+        let total: Vec<u64> = (0..8).map(|j| sums.iter().map(|s| s[j]).sum()).collect();
+        let expected: Vec<u64> = (0..8).map(|j| rows.iter().map(|(_, row)| row[j]).sum()).collect();
+        assert_eq!(total, expected);}}
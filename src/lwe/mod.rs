@@ -0,0 +1,21 @@
+// src/lwe/mod.rs
+use std::fmt;
+
+pub mod pke;
+pub mod kem;
+pub mod matrix;
+pub mod sampler;
+pub mod ring;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LweError {
+    InvalidEncoding(&'static str),
+    UnsupportedVersion(u8),}
+
+impl fmt::Display for LweError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LweError::InvalidEncoding(what) => write!(f, "malformed {}", what),
+            LweError::UnsupportedVersion(v) => write!(f, "unsupported LWE encoding version {}", v),}}}
+
+impl std::error::Error for LweError {}
@@ -0,0 +1,331 @@
+// src/lwe/pke.rs
+use crate::constants::{DIM, MAGNITUDE, Q_MOD};
+use crate::lwe::matrix::SeededMatrix;
+use crate::lwe::LweError;
+use crate::xof::QosmicXof;
+use log::{debug, info};
+use ndarray::{Array1, Array2};
+use rand::{Rng, RngCore};
+use std::fmt;
+use std::time::Instant;
+
+/// Secret dimension `n`.
+pub const LWE_N: usize = DIM;
+/// Number of LWE samples (rows of `A`) in a public key.
+pub const LWE_M: usize = DIM;
+/// Message bits per ciphertext; the secret is an `n × l` matrix so all bits share one `A^T r`.
+pub const MESSAGE_BITS: usize = 256;
+pub const MESSAGE_BYTES: usize = MESSAGE_BITS / 8;
+/// Worst-case decryption noise `|E^T r + e2 - S^T e1|` for coefficients in `[-MAGNITUDE, MAGNITUDE]`.
+/// It is far below `Q_MOD / 4`, so decryption never fails with these parameters.
+pub const MAX_NOISE: u64 = (LWE_M + LWE_N) as u64 * (MAGNITUDE * MAGNITUDE) as u64 + MAGNITUDE as u64;
+pub const SEED_BYTES: usize = 32;
+pub const PKE_VERSION: u8 = 1;
+/// Coefficients mod `Q_MOD` (< 2^39) are serialized as 5 big-endian bytes.
+const COEFF_BYTES: usize = 5;
+/// Only the seed of `A` is stored, so the key is dominated by the `m × l` matrix `B`.
+pub const PUBLIC_KEY_BYTES: usize = 1 + SEED_BYTES + LWE_M * MESSAGE_BITS * COEFF_BYTES;
+pub const SECRET_KEY_BYTES: usize = 1 + LWE_N * MESSAGE_BITS;
+pub const CIPHERTEXT_BYTES: usize = 1 + (LWE_N + MESSAGE_BITS) * COEFF_BYTES;
+const DOMAIN_MATRIX: &[u8] = b"qosmic-lwe-matrix";
+const DOMAIN_SECRET: &[u8] = b"qosmic-lwe-secret";
+const DOMAIN_COINS: &[u8] = b"qosmic-lwe-encrypt";
+
+/// Regev-style secret key: `S` with small coefficients in `[-MAGNITUDE, MAGNITUDE]`.
+#[derive(Clone)]
+pub struct LweSecretKey {
+    pub(crate) s: Array2<i64>,}
+
+impl fmt::Debug for LweSecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LweSecretKey { .. }")}}
+
+/// Public key `(A, B = A S + E mod q)`, with `A` uniform mod `Q_MOD`. `A` is a `SeededMatrix`:
+/// only its 32-byte seed is kept, and its rows are expanded again whenever they are needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LwePublicKey {
+    pub(crate) a: SeededMatrix,
+    pub(crate) b: Array2<u64>,}
+
+/// Ciphertext `(u = A^T r + e1, v = B^T r + e2 + floor(q/2) * bits)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LweCiphertext {
+    pub(crate) u: Array1<u64>,
+    pub(crate) v: Array1<u64>,}
+
+fn sample_small<R: Rng + ?Sized>(rng: &mut R, len: usize) -> Vec<i64> {
+    (0..len).map(|_| rng.random_range(-MAGNITUDE..=MAGNITUDE)).collect()}
+
+/// Uniform values in `[-MAGNITUDE, MAGNITUDE]` from XOF nibbles, rejecting nibbles >= 13.
+pub(crate) fn sample_small_xof(xof: &mut QosmicXof, len: usize) -> Vec<i64> {
+    let span = (2 * MAGNITUDE + 1) as u8;
+    let mut out = Vec::with_capacity(len);
+    while out.len() < len {
+        let byte = xof.read_byte();
+        for nibble in [byte >> 4, byte & 0x0F] {
+            if nibble < span && out.len() < len {
+                out.push(nibble as i64 - MAGNITUDE);}}}
+    out}
+
+fn public_matrix(seed: &[u8]) -> SeededMatrix {
+    SeededMatrix::new(DOMAIN_MATRIX, seed, LWE_M, LWE_N)}
+
+fn reduce(x: i64) -> u64 {
+    x.rem_euclid(Q_MOD as i64) as u64}
+
+/// Adds `entry * xi` for each entry of `row` into `acc`.
+fn add_scaled_row(acc: &mut [i64], row: impl IntoIterator<Item = i64>, xi: i64) {
+    for (a, entry) in acc.iter_mut().zip(row) {
+        *a += entry * xi;}}
+
+/// `M^T x mod q` for `M` with entries below `Q_MOD` and small `x`. Partial sums stay below 2^52,
+/// so plain `i64` arithmetic is exact.
+fn transpose_mul_small(m: &Array2<u64>, x: &[i64]) -> Array1<u64> {
+    let mut acc = vec![0i64; m.ncols()];
+    for (row, &xi) in m.rows().into_iter().zip(x.iter()) {
+        add_scaled_row(&mut acc, row.iter().map(|&entry| entry as i64), xi);}
+    acc.into_iter().map(reduce).collect()}
+
+/// `A^T x mod q` for the seeded `A`, folding one expanded row at a time into per-core sums.
+fn seeded_transpose_mul_small(a: &SeededMatrix, x: &[i64]) -> Array1<u64> {
+    let partial = a.fold_rows(|| vec![0i64; a.cols()], |acc, i, row| add_scaled_row(acc, row.iter().map(|&entry| entry as i64), x[i]));
+    let mut acc = vec![0i64; a.cols()];
+    for sums in partial {
+        acc.iter_mut().zip(sums).for_each(|(a, s)| *a += s);}
+    acc.into_iter().map(reduce).collect()}
+
+/// `A S + E mod q`, computed one expanded row of `A` at a time so the inner loop runs over
+/// contiguous columns of `S`.
+fn mul_small_add(a: &SeededMatrix, s: &Array2<i64>, e: &Array2<i64>) -> Array2<u64> {
+    let rows = a.map_rows(|i, a_row| {
+        let mut acc: Vec<i64> = e.row(i).to_vec();
+        for (&a_ij, s_row) in a_row.iter().zip(s.rows()) {
+            add_scaled_row(&mut acc, s_row.iter().copied(), a_ij as i64);}
+        acc.into_iter().map(reduce).collect::<Vec<u64>>()});
+    Array2::from_shape_vec((a.rows(), s.ncols()), rows.concat()).expect("Every row of B has one entry per column of S")}
+
+pub fn keygen() -> (LwePublicKey, LweSecretKey) {
+    keygen_with_rng(&mut rand::rng())}
+
+/// Draws the seed of `A`, `S` and `E` from `rng`. Expanding `A` to compute `B` takes a few seconds.
+pub fn keygen_with_rng<R: Rng + ?Sized>(rng: &mut R) -> (LwePublicKey, LweSecretKey) {
+    let start = Instant::now();
+    let mut matrix_seed = [0u8; SEED_BYTES];
+    rng.fill_bytes(&mut matrix_seed);
+    let a = public_matrix(&matrix_seed);
+    let s = Array2::from_shape_vec((LWE_N, MESSAGE_BITS), sample_small(rng, LWE_N * MESSAGE_BITS)).expect("Failed to reshape LWE secret S");
+    let e = Array2::from_shape_vec((LWE_M, MESSAGE_BITS), sample_small(rng, LWE_M * MESSAGE_BITS)).expect("Failed to reshape LWE error E");
+    let b = mul_small_add(&a, &s, &e);
+    info!("LWE key generation ({}x{} A, {} message bits) took: {:?}", LWE_M, LWE_N, MESSAGE_BITS, start.elapsed());
+    (LwePublicKey { a, b }, LweSecretKey { s })}
+
+/// Deterministic key generation for reproducible keys and known-answer tests: the seed of `A`,
+/// then `S` and `E`, are read from `QosmicXof("qosmic-lwe-secret", seed)`.
+pub fn keygen_from_seed(seed: &[u8; SEED_BYTES]) -> (LwePublicKey, LweSecretKey) {
+    let start = Instant::now();
+    let mut secret_xof = QosmicXof::new(DOMAIN_SECRET, seed);
+    let a = public_matrix(&secret_xof.squeeze(SEED_BYTES));
+    let s = Array2::from_shape_vec((LWE_N, MESSAGE_BITS), sample_small_xof(&mut secret_xof, LWE_N * MESSAGE_BITS)).expect("Failed to reshape LWE secret S");
+    let e = Array2::from_shape_vec((LWE_M, MESSAGE_BITS), sample_small_xof(&mut secret_xof, LWE_M * MESSAGE_BITS)).expect("Failed to reshape LWE error E");
+    let b = mul_small_add(&a, &s, &e);
+    info!("Seeded LWE key generation took: {:?}", start.elapsed());
+    (LwePublicKey { a, b }, LweSecretKey { s })}
+
+pub fn encrypt(pk: &LwePublicKey, message: &[u8; MESSAGE_BYTES]) -> LweCiphertext {
+    let mut coins = [0u8; SEED_BYTES];
+    rand::rng().fill_bytes(&mut coins);
+    encrypt_with_coins(pk, message, &coins)}
+
+/// Encrypts with `r`, `e1`, `e2` expanded from `coins` by the qosmic XOF, so the same coins always
+/// give the same ciphertext. The KEM's re-encryption check relies on this.
+pub fn encrypt_with_coins(pk: &LwePublicKey, message: &[u8; MESSAGE_BYTES], coins: &[u8; SEED_BYTES]) -> LweCiphertext {
+    let mut xof = QosmicXof::new(DOMAIN_COINS, coins);
+    let r = sample_small_xof(&mut xof, LWE_M);
+    let e1 = sample_small_xof(&mut xof, LWE_N);
+    let e2 = sample_small_xof(&mut xof, MESSAGE_BITS);
+    let mut u = seeded_transpose_mul_small(&pk.a, &r);
+    for (u_i, &e) in u.iter_mut().zip(e1.iter()) {
+        *u_i = reduce(*u_i as i64 + e);}
+    let mut v = transpose_mul_small(&pk.b, &r);
+    for (i, v_i) in v.iter_mut().enumerate() {
+        let bit = (message[i / 8] >> (7 - i % 8)) & 1;
+        *v_i = reduce(*v_i as i64 + e2[i] + (bit as u64 * (Q_MOD / 2)) as i64);}
+    debug!("LWE encrypt: u[0..4]={:?}, v[0..4]={:?}", &u.as_slice().unwrap()[..4], &v.as_slice().unwrap()[..4]);
+    LweCiphertext { u, v }}
+
+/// Recovers the message bits: `v - S^T u = noise + floor(q/2) * bits`, and a coefficient decodes
+/// to 1 when it lies closer to `q/2` than to 0.
+pub fn decrypt(sk: &LweSecretKey, ct: &LweCiphertext) -> [u8; MESSAGE_BYTES] {
+    let mut message = [0u8; MESSAGE_BYTES];
+    for (i, column) in sk.s.columns().into_iter().enumerate() {
+        let mut acc: i64 = 0;
+        for (&u, &s) in ct.u.iter().zip(column.iter()) {
+            acc += u as i64 * s;}
+        let d = reduce(ct.v[i] as i64 - acc);
+        let bit = (d > Q_MOD / 4 && d < 3 * (Q_MOD / 4)) as u8;
+        message[i / 8] |= bit << (7 - i % 8);}
+    message}
+
+fn put_coeffs<'a>(out: &mut Vec<u8>, coeffs: impl Iterator<Item = &'a u64>) {
+    for &c in coeffs {
+        out.extend_from_slice(&c.to_be_bytes()[8 - COEFF_BYTES..]);}}
+
+fn get_coeffs(bytes: &[u8], what: &'static str) -> Result<Vec<u64>, LweError> {
+    bytes.chunks_exact(COEFF_BYTES)
+        .map(|chunk| {
+            let mut word = [0u8; 8];
+            word[8 - COEFF_BYTES..].copy_from_slice(chunk);
+            let c = u64::from_be_bytes(word);
+            if c < Q_MOD { Ok(c) } else { Err(LweError::InvalidEncoding(what)) }})
+        .collect()}
+
+fn check_header(bytes: &[u8], expected_len: usize, what: &'static str) -> Result<(), LweError> {
+    match bytes.first() {
+        None => Err(LweError::InvalidEncoding(what)),
+        Some(&v) if v != PKE_VERSION => Err(LweError::UnsupportedVersion(v)),
+        Some(_) if bytes.len() != expected_len => Err(LweError::InvalidEncoding(what)),
+        Some(_) => Ok(()),}}
+
+impl LwePublicKey {
+    /// `[version][seed of A (32 bytes)][B row-major]`, coefficients as 5-byte big-endian integers.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(PUBLIC_KEY_BYTES);
+        out.push(PKE_VERSION);
+        out.extend_from_slice(self.a.seed());
+        put_coeffs(&mut out, self.b.iter());
+        out}
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LweError> {
+        check_header(bytes, PUBLIC_KEY_BYTES, "LWE public key")?;
+        let split = 1 + SEED_BYTES;
+        let b = Array2::from_shape_vec((LWE_M, MESSAGE_BITS), get_coeffs(&bytes[split..], "LWE public key")?).expect("Length checked above");
+        Ok(LwePublicKey { a: public_matrix(&bytes[1..split]), b })}}
+
+impl LweSecretKey {
+    /// `[version][S row-major]`, one signed byte per coefficient.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SECRET_KEY_BYTES);
+        out.push(PKE_VERSION);
+        out.extend(self.s.iter().map(|&x| x as i8 as u8));
+        out}
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LweError> {
+        check_header(bytes, SECRET_KEY_BYTES, "LWE secret key")?;
+        let coeffs: Vec<i64> = bytes[1..].iter().map(|&b| b as i8 as i64).collect();
+        if coeffs.iter().any(|c| c.abs() > MAGNITUDE) {
+            return Err(LweError::InvalidEncoding("LWE secret key"));}
+        Ok(LweSecretKey { s: Array2::from_shape_vec((LWE_N, MESSAGE_BITS), coeffs).expect("Length checked above") })}}
+
+impl LweCiphertext {
+    /// `[version][u][v]`, coefficients as 5-byte big-endian integers.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(CIPHERTEXT_BYTES);
+        out.push(PKE_VERSION);
+        put_coeffs(&mut out, self.u.iter());
+        put_coeffs(&mut out, self.v.iter());
+        out}
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LweError> {
+        check_header(bytes, CIPHERTEXT_BYTES, "LWE ciphertext")?;
+        let split = 1 + LWE_N * COEFF_BYTES;
+        Ok(LweCiphertext {
+            u: Array1::from_vec(get_coeffs(&bytes[1..split], "LWE ciphertext")?),
+            v: Array1::from_vec(get_coeffs(&bytes[split..], "LWE ciphertext")?),})}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::sync::OnceLock;
+
+    const COINS: [u8; SEED_BYTES] = [7; SEED_BYTES];
+
+    // Every encryption expands all of `A`, so the tests share one key pair and one ciphertext.
+    fn key_pair() -> &'static (LwePublicKey, LweSecretKey) {
+        static KEYS: OnceLock<(LwePublicKey, LweSecretKey)> = OnceLock::new();
+        KEYS.get_or_init(|| keygen_with_rng(&mut StdRng::seed_from_u64(38)))}
+
+    fn ciphertext() -> &'static LweCiphertext {
+        static CT: OnceLock<LweCiphertext> = OnceLock::new();
+        CT.get_or_init(|| encrypt_with_coins(&key_pair().0, &[0x3c; MESSAGE_BYTES], &COINS))}
+
+    #[test]
+    fn decrypt_recovers_random_messages() {
+        let (pk, sk) = key_pair();
+        let mut random = [0u8; MESSAGE_BYTES];
+        StdRng::seed_from_u64(1).fill_bytes(&mut random);
+        for message in [[0x00; MESSAGE_BYTES], [0xff; MESSAGE_BYTES], random] {
+            assert_eq!(decrypt(sk, &encrypt(pk, &message)), message);}
+        assert_eq!(decrypt(sk, ciphertext()), [0x3c; MESSAGE_BYTES]);}
+
+    #[test]
+    fn same_coins_give_the_same_ciphertext() {
+        let (pk, _) = key_pair();
+        let message = [0x3c; MESSAGE_BYTES];
+        assert_eq!(&encrypt_with_coins(pk, &message, &COINS), ciphertext());
+        assert_ne!(&encrypt_with_coins(pk, &message, &[8; SEED_BYTES]), ciphertext());}
+
+    #[test]
+    fn seeded_matrix_matches_the_public_key() {
+        let (pk, sk) = key_pair();
+        assert_eq!((pk.a.rows(), pk.a.cols(), pk.a.seed().len()), (LWE_M, LWE_N, SEED_BYTES));
+        // Row 5 of B is row 5 of A times S plus a small error.
+        let row = pk.a.row(5);
+        for k in [0, 100, MESSAGE_BITS - 1] {
+            let dot: i64 = row.iter().zip(sk.s.column(k)).map(|(&a, &s)| a as i64 * s).sum();
+            let error = reduce(pk.b[[5, k]] as i64 - dot);
+            assert!(error <= MAGNITUDE as u64 || error >= Q_MOD - MAGNITUDE as u64, "error {} in column {}", error, k);}}
+
+    #[test]
+    fn public_key_holds_only_the_seed_and_b() {
+        let (pk, _) = key_pair();
+        assert_eq!(PUBLIC_KEY_BYTES, 1 + SEED_BYTES + LWE_M * MESSAGE_BITS * COEFF_BYTES);
+        // Storing A as well would take another LWE_M * LWE_N * COEFF_BYTES = 5,242,880 bytes.
+        assert_eq!(PUBLIC_KEY_BYTES, 1_310_753);
+        let bytes = pk.to_bytes();
+        assert_eq!(bytes.len(), PUBLIC_KEY_BYTES);
+        assert_eq!(&bytes[1..1 + SEED_BYTES], pk.a.seed());
+        assert_eq!(pk.b.len(), LWE_M * MESSAGE_BITS);}
+
+    #[test]
+    fn encodings_round_trip() {
+        let (pk, sk) = key_pair();
+        let ct = ciphertext();
+        let pk_bytes = pk.to_bytes();
+        let sk_bytes = sk.to_bytes();
+        let ct_bytes = ct.to_bytes();
+        assert_eq!((pk_bytes.len(), sk_bytes.len(), ct_bytes.len()), (PUBLIC_KEY_BYTES, SECRET_KEY_BYTES, CIPHERTEXT_BYTES));
+        assert_eq!(&LwePublicKey::from_bytes(&pk_bytes).unwrap(), pk);
+        assert_eq!(LweSecretKey::from_bytes(&sk_bytes).unwrap().s, sk.s);
+        assert_eq!(&LweCiphertext::from_bytes(&ct_bytes).unwrap(), ct);}
+
+    #[test]
+    fn wrong_length_and_version_are_rejected() {
+        let (pk, sk) = key_pair();
+        let mut ct_bytes = ciphertext().to_bytes();
+        assert!(LweCiphertext::from_bytes(&ct_bytes[..CIPHERTEXT_BYTES - 1]).is_err());
+        assert!(LweCiphertext::from_bytes(&[]).is_err());
+        ct_bytes.push(0);
+        assert!(LweCiphertext::from_bytes(&ct_bytes).is_err());
+        let mut pk_bytes = pk.to_bytes();
+        assert!(LwePublicKey::from_bytes(&pk_bytes[..PUBLIC_KEY_BYTES - 1]).is_err());
+        pk_bytes.push(0);
+        assert!(LwePublicKey::from_bytes(&pk_bytes).is_err());
+        let mut sk_bytes = sk.to_bytes();
+        assert!(LweSecretKey::from_bytes(&sk_bytes[..SECRET_KEY_BYTES - 1]).is_err());
+        sk_bytes[0] = PKE_VERSION + 1;
+        assert!(matches!(LweSecretKey::from_bytes(&sk_bytes), Err(LweError::UnsupportedVersion(_))));}
+
+    #[test]
+    fn out_of_range_coefficients_are_rejected() {
+        let (pk, sk) = key_pair();
+        let mut ct_bytes = ciphertext().to_bytes();
+        ct_bytes[1..1 + COEFF_BYTES].fill(0xff);
+        assert_eq!(LweCiphertext::from_bytes(&ct_bytes), Err(LweError::InvalidEncoding("LWE ciphertext")));
+        let mut pk_bytes = pk.to_bytes();
+        pk_bytes[1 + SEED_BYTES..1 + SEED_BYTES + COEFF_BYTES].fill(0xff);
+        assert_eq!(LwePublicKey::from_bytes(&pk_bytes), Err(LweError::InvalidEncoding("LWE public key")));
+        let mut sk_bytes = sk.to_bytes();
+        sk_bytes[1] = (MAGNITUDE + 1) as u8;
+        assert!(LweSecretKey::from_bytes(&sk_bytes).is_err());}}
@@ -0,0 +1,287 @@
+// src/lwe/ring.rs
+use crate::lwe::sampler::{ErrorDistribution, XofBits};
+use crate::xof::QosmicXof;
+use std::fmt;
+
+/// Ring degree: polynomials live in `Z_q[x] / (x^1024 + 1)`.
+pub const RING_N: usize = 1024;
+/// NTT-friendly modulus: `q = 12289 = 3 * 2^12 + 1`, so `2 * RING_N` divides `q - 1` (as in NewHope).
+pub const RING_Q: u16 = 12289;
+/// Primitive `2 * RING_N`-th root of unity mod `q` (`7^1024 = -1`).
+pub const RING_PSI: u16 = 7;
+/// `round(q / 2)`, the encoding of a 1 bit.
+pub const HALF_Q: u16 = RING_Q.div_ceil(2);
+/// Coefficients are drawn as 14-bit words, rejecting values >= `q` (about a quarter).
+const UNIFORM_MASK: u16 = (1 << 14) - 1;
+const Q: u32 = RING_Q as u32;
+const LOG_N: u32 = RING_N.trailing_zeros();
+
+const fn pow_mod(mut base: u32, mut exp: u32) -> u32 {
+    let mut acc = 1;
+    base %= Q;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            acc = acc * base % Q;}
+        base = base * base % Q;
+        exp >>= 1;}
+    acc}
+
+const fn bit_reverse(x: usize) -> usize {
+    x.reverse_bits() >> (usize::BITS - LOG_N)}
+
+/// `ZETAS[i] = psi^bitrev(i)`, the twiddle factors in the order the butterflies use them.
+const ZETAS: [u16; RING_N] = {
+    let mut table = [0u16; RING_N];
+    let mut i = 0;
+    while i < RING_N {
+        table[i] = pow_mod(RING_PSI as u32, bit_reverse(i) as u32) as u16;
+        i += 1;}
+    table};
+
+/// `ZETAS_INV[i] = ZETAS[i]^-1`.
+const ZETAS_INV: [u16; RING_N] = {
+    let mut table = [0u16; RING_N];
+    let mut i = 0;
+    while i < RING_N {
+        table[i] = pow_mod(ZETAS[i] as u32, Q - 2) as u16;
+        i += 1;}
+    table};
+
+/// `RING_N^-1 mod q`, applied once at the end of the inverse transform.
+const N_INV: u32 = pow_mod(RING_N as u32, Q - 2);
+
+/// A polynomial in `R_q = Z_q[x] / (x^n + 1)` with coefficients in `[0, q)`, lowest degree first.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Poly {
+    coeffs: [u16; RING_N],}
+
+/// A polynomial in the NTT domain, where multiplication is pointwise. Keeping it a separate type
+/// lets a fixed operand such as a public `a` be transformed once and reused.
+#[derive(Clone, PartialEq, Eq)]
+pub struct NttPoly {
+    coeffs: [u16; RING_N],}
+
+impl fmt::Debug for Poly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Poly({:?}, ..)", &self.coeffs[..8])}}
+
+impl fmt::Debug for NttPoly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NttPoly({:?}, ..)", &self.coeffs[..8])}}
+
+fn reduce_signed(x: i64) -> u16 {
+    x.rem_euclid(Q as i64) as u16}
+
+fn add_mod(a: u16, b: u16) -> u16 {
+    ((a as u32 + b as u32) % Q) as u16}
+
+fn sub_mod(a: u16, b: u16) -> u16 {
+    ((a as u32 + Q - b as u32) % Q) as u16}
+
+fn mul_mod(a: u16, b: u16) -> u16 {
+    (a as u32 * b as u32 % Q) as u16}
+
+impl Poly {
+    pub fn zero() -> Self {
+        Poly { coeffs: [0; RING_N] }}
+
+    /// Reduces signed coefficients mod `q`. Panics unless there are exactly `RING_N` of them.
+    pub fn from_signed(coeffs: &[i64]) -> Self {
+        assert_eq!(coeffs.len(), RING_N, "a ring element has {} coefficients", RING_N);
+        let mut poly = Poly::zero();
+        for (c, &x) in poly.coeffs.iter_mut().zip(coeffs) {
+            *c = reduce_signed(x);}
+        poly}
+
+    /// Encodes up to `RING_N` bits (each 0 or 1) as `round(q / 2) * bit`.
+    pub fn from_message_bits(bits: &[u8]) -> Self {
+        assert!(bits.len() <= RING_N, "at most {} message bits fit in a ring element", RING_N);
+        let mut poly = Poly::zero();
+        for (c, &bit) in poly.coeffs.iter_mut().zip(bits) {
+            *c = HALF_Q * (bit & 1) as u16;}
+        poly}
+
+    /// Uniform coefficients mod `q` from 14-bit big-endian XOF words, with rejection.
+    pub fn uniform(xof: &mut QosmicXof) -> Self {
+        let mut poly = Poly::zero();
+        let mut filled = 0;
+        let mut word = [0u8; 2];
+        while filled < RING_N {
+            xof.read(&mut word);
+            let x = u16::from_be_bytes(word) & UNIFORM_MASK;
+            if x < RING_Q {
+                poly.coeffs[filled] = x;
+                filled += 1;}}
+        poly}
+
+    /// Small coefficients drawn from `dist`, for secrets and errors.
+    pub fn sample<D: ErrorDistribution>(dist: &D, bits: &mut XofBits) -> Self {
+        Poly::from_signed(&dist.sample_vec(bits, RING_N))}
+
+    pub fn coeffs(&self) -> &[u16; RING_N] {
+        &self.coeffs}
+
+    /// Coefficients as representatives in `(-q/2, q/2]`.
+    pub fn centered(&self) -> Vec<i64> {
+        self.coeffs.iter().map(|&c| if c > RING_Q / 2 { c as i64 - Q as i64 } else { c as i64 }).collect()}
+
+    pub fn add(&self, other: &Poly) -> Poly {
+        let mut out = self.clone();
+        for (c, &o) in out.coeffs.iter_mut().zip(other.coeffs.iter()) {
+            *c = add_mod(*c, o);}
+        out}
+
+    pub fn sub(&self, other: &Poly) -> Poly {
+        let mut out = self.clone();
+        for (c, &o) in out.coeffs.iter_mut().zip(other.coeffs.iter()) {
+            *c = sub_mod(*c, o);}
+        out}
+
+    /// Forward negacyclic NTT: Cooley–Tukey butterflies with the powers of `psi` merged in, so no
+    /// separate pre-multiplication is needed. The output is in bit-reversed order, which only
+    /// matters to code that inspects NTT coefficients directly.
+    pub fn ntt(&self) -> NttPoly {
+        let mut a = self.coeffs;
+        let mut k = 1;
+        let mut len = RING_N / 2;
+        while len >= 1 {
+            for start in (0..RING_N).step_by(2 * len) {
+                let zeta = ZETAS[k];
+                k += 1;
+                for j in start..start + len {
+                    let t = mul_mod(zeta, a[j + len]);
+                    a[j + len] = sub_mod(a[j], t);
+                    a[j] = add_mod(a[j], t);}}
+            len /= 2;}
+        NttPoly { coeffs: a }}
+
+    /// Product in `R_q` through the NTT: `O(n log n)`.
+    pub fn mul(&self, other: &Poly) -> Poly {
+        self.ntt().pointwise_mul(&other.ntt()).inverse_ntt()}
+
+    /// Product in `R_q` by the `O(n^2)` schoolbook method, reducing `x^n = -1`. Reference for
+    /// cross-checking `mul`; about 20 times slower at this degree.
+    pub fn mul_schoolbook(&self, other: &Poly) -> Poly {
+        let mut acc = [0i64; RING_N];
+        for (i, &a) in self.coeffs.iter().enumerate() {
+            for (j, &b) in other.coeffs.iter().enumerate() {
+                let product = a as i64 * b as i64;
+                if i + j < RING_N {
+                    acc[i + j] += product;} else {
+                    acc[i + j - RING_N] -= product;}}}
+        Poly::from_signed(&acc)}
+
+    /// Lossy compression to `d` bits per coefficient (`1 <= d <= 13`): `round(2^d * x / q) mod 2^d`.
+    /// `decompress` maps back to within `q / 2^(d + 1)` of the original.
+    pub fn compress(&self, d: u32) -> Vec<u16> {
+        assert!((1..=13).contains(&d), "compression keeps 1 to 13 bits per coefficient");
+        self.coeffs.iter().map(|&c| ((((c as u32) << d) + Q / 2) / Q) as u16 & ((1 << d) - 1) as u16).collect()}
+
+    pub fn decompress(values: &[u16], d: u32) -> Poly {
+        assert!((1..=13).contains(&d), "compression keeps 1 to 13 bits per coefficient");
+        assert_eq!(values.len(), RING_N, "a ring element has {} coefficients", RING_N);
+        let mut poly = Poly::zero();
+        for (c, &y) in poly.coeffs.iter_mut().zip(values) {
+            *c = (((y as u32 & ((1 << d) - 1)) * Q + (1 << (d - 1))) >> d) as u16;}
+        poly}
+
+    /// Decodes each coefficient to 1 when it is closer to `q / 2` than to 0, inverting
+    /// `from_message_bits` plus small noise.
+    pub fn to_message_bits(&self) -> Vec<u8> {
+        self.compress(1).into_iter().map(|b| b as u8).collect()}}
+
+impl NttPoly {
+    pub fn coeffs(&self) -> &[u16; RING_N] {
+        &self.coeffs}
+
+    pub fn add(&self, other: &NttPoly) -> NttPoly {
+        let mut out = self.clone();
+        for (c, &o) in out.coeffs.iter_mut().zip(other.coeffs.iter()) {
+            *c = add_mod(*c, o);}
+        out}
+
+    pub fn pointwise_mul(&self, other: &NttPoly) -> NttPoly {
+        let mut out = self.clone();
+        for (c, &o) in out.coeffs.iter_mut().zip(other.coeffs.iter()) {
+            *c = mul_mod(*c, o);}
+        out}
+
+    /// Inverse NTT: Gentleman–Sande butterflies with the inverse twiddles in reverse order, then
+    /// a final scaling by `n^-1`.
+    pub fn inverse_ntt(&self) -> Poly {
+        let mut a = self.coeffs;
+        let mut k = RING_N;
+        let mut len = 1;
+        while len < RING_N {
+            for start in (0..RING_N).step_by(2 * len).rev() {
+                k -= 1;
+                let zeta_inv = ZETAS_INV[k];
+                for j in start..start + len {
+                    let t = a[j];
+                    a[j] = add_mod(t, a[j + len]);
+                    a[j + len] = mul_mod(zeta_inv, sub_mod(t, a[j + len]));}}
+            len *= 2;}
+        for c in a.iter_mut() {
+            *c = (*c as u32 * N_INV % Q) as u16;}
+        Poly { coeffs: a }}}
+
+/// Inner product `sum a_i * b_i` of two module vectors in the NTT domain, the core operation of
+/// Module-LWE (`A s` is one inner product per row of `A`).
+pub fn inner_product(a: &[NttPoly], b: &[NttPoly]) -> NttPoly {
+    assert_eq!(a.len(), b.len(), "module vectors must have the same rank");
+    a.iter().zip(b).fold(NttPoly { coeffs: [0; RING_N] }, |acc, (x, y)| acc.add(&x.pointwise_mul(y)))}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lwe::sampler::CenteredBinomial;
+
+    fn monomial(degree: usize) -> Poly {
+        let mut poly = Poly::zero();
+        poly.coeffs[degree] = 1;
+        poly}
+
+    #[test]
+    fn ntt_multiplication_matches_schoolbook() {
+        let cbd = CenteredBinomial::new(8).unwrap();
+        for seed in 0u8..6 {
+            let mut xof = QosmicXof::new(b"qosmic-ring test", &[seed]);
+            let (u, v) = (Poly::uniform(&mut xof), Poly::uniform(&mut xof));
+            let small = Poly::sample(&cbd, &mut XofBits::from_seed(&[seed]));
+            assert_eq!(u.ntt().inverse_ntt(), u, "seed {}", seed);
+            assert_eq!(u.mul(&v), u.mul_schoolbook(&v), "seed {}", seed);
+            assert_eq!(u.mul(&small), u.mul_schoolbook(&small), "seed {}", seed);
+            assert_eq!(u.mul(&v.add(&small)), u.mul(&v).add(&u.mul(&small)));
+            let product = inner_product(&[u.ntt(), v.ntt()], &[small.ntt(), u.ntt()]).inverse_ntt();
+            assert_eq!(product, u.mul_schoolbook(&small).add(&v.mul_schoolbook(&u)));}}
+
+    #[test]
+    fn reduction_wraps_negacyclically() {
+        let wrapped = monomial(1).mul(&monomial(RING_N - 1));
+        assert_eq!(wrapped.coeffs()[0], RING_Q - 1);
+        assert!(wrapped.coeffs()[1..].iter().all(|&c| c == 0));
+        assert_eq!(wrapped, monomial(1).mul_schoolbook(&monomial(RING_N - 1)));
+        assert_eq!(monomial(3).mul(&monomial(5)), monomial(8));
+        assert_eq!(Poly::from_signed(&[-1; RING_N]).centered(), vec![-1; RING_N]);}
+
+    #[test]
+    fn compression_error_is_bounded() {
+        let mut xof = QosmicXof::new(b"qosmic-ring test", b"compress");
+        let poly = Poly::uniform(&mut xof);
+        for d in 1..=13 {
+            let restored = Poly::decompress(&poly.compress(d), d);
+            let bound = (Q as i64 + (1 << (d + 1)) - 1) >> (d + 1);
+            let error = restored.sub(&poly).centered().into_iter().map(i64::abs).max().unwrap();
+            assert!(error <= bound, "d = {}: error {} exceeds {}", d, error, bound);}
+        let bits: Vec<u8> = (0..RING_N).map(|i| (i % 3 == 0) as u8).collect();
+        let noise = Poly::from_signed(&(0..RING_N as i64).map(|i| i % 7 - 3).collect::<Vec<_>>());
+        assert_eq!(Poly::from_message_bits(&bits).add(&noise).to_message_bits(), bits);}
+
+    #[test]
+    fn known_answer() {
+        let mut xof = QosmicXof::new(b"qosmic-ring kat", b"");
+        let (u, v) = (Poly::uniform(&mut xof), Poly::uniform(&mut xof));
+        assert_eq!(u.coeffs()[..8], [12267, 5470, 10738, 3303, 6054, 4628, 10756, 10022]);
+        assert_eq!(v.coeffs()[..8], [3096, 4597, 11220, 8224, 10623, 6802, 2608, 10804]);
+        assert_eq!(u.mul(&v).coeffs()[..8], [4683, 8955, 3635, 2840, 2460, 1103, 3007, 4131]);
+        assert_eq!(u.compress(4)[..8], [0, 7, 14, 4, 8, 6, 14, 13]);}}
@@ -0,0 +1,256 @@
+// src/lwe/sampler.rs
+use crate::xof::QosmicXof;
+use std::fmt;
+
+/// Largest centered binomial parameter; a sample reads `2 * eta` bits, at most one word.
+pub const MAX_ETA: u32 = 32;
+/// Largest Gaussian width accepted by `CdtGaussian::new`.
+pub const MAX_SIGMA: f64 = 256.0;
+/// Gaussian tables stop `TAIL_CUT * sigma` from the center. Beyond that the mass is below 2^-64
+/// and rounds to zero at the table's 63-bit precision anyway.
+pub const TAIL_CUT: f64 = 10.0;
+const SAMPLER_DOMAIN: &[u8] = b"qosmic-lwe-sampler";
+const CDT_ONE: u64 = 1 << 63;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplerError {
+    InvalidEta(u32),
+    InvalidSigma(f64),}
+
+impl fmt::Display for SamplerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SamplerError::InvalidEta(eta) => write!(f, "centered binomial parameter {} is outside 1..={}", eta, MAX_ETA),
+            SamplerError::InvalidSigma(sigma) => write!(f, "Gaussian width {} is outside (0, {}]", sigma, MAX_SIGMA),}}}
+
+impl std::error::Error for SamplerError {}
+
+/// A stream of bits read from a qosmic XOF, least significant bit of each byte first (the order
+/// Kyber's CBD uses). Samplers take exactly the bits they need, so a seed always gives the same
+/// sequence of samples whatever mix of requests produced them.
+#[derive(Debug, Clone)]
+pub struct XofBits {
+    xof: QosmicXof,
+    buf: u64,
+    avail: u32,}
+
+impl XofBits {
+    pub(crate) fn new(xof: QosmicXof) -> Self {
+        XofBits { xof, buf: 0, avail: 0 }}
+
+    /// Bits from `QosmicXof("qosmic-lwe-sampler", seed)`.
+    pub fn from_seed(seed: &[u8]) -> Self {
+        XofBits::new(QosmicXof::new(SAMPLER_DOMAIN, seed))}
+
+    /// The next `n` bits (at most 64), the first one in bit 0.
+    pub fn take(&mut self, n: u32) -> u64 {
+        assert!(n <= 64, "at most 64 bits per read");
+        let mut out = 0u64;
+        let mut got = 0;
+        while got < n {
+            if self.avail == 0 {
+                let mut word = [0u8; 8];
+                self.xof.read(&mut word);
+                self.buf = u64::from_le_bytes(word);
+                self.avail = 64;}
+            let k = (n - got).min(self.avail);
+            let chunk = if k == 64 { self.buf } else { self.buf & ((1 << k) - 1) };
+            out |= chunk << got;
+            self.buf = if k == 64 { 0 } else { self.buf >> k };
+            self.avail -= k;
+            got += k;}
+        out}}
+
+/// A distribution of small signed integers for LWE secrets and errors.
+pub trait ErrorDistribution {
+    /// Draws one sample. The bits consumed and the instructions executed do not depend on the
+    /// value drawn.
+    fn sample(&self, bits: &mut XofBits) -> i64;
+
+    /// Largest `|x|` the sampler can return.
+    fn max_abs(&self) -> i64;
+
+    /// Exact probability of drawing `x`, as realized by the sampler.
+    fn probability(&self, x: i64) -> f64;
+
+    fn variance(&self) -> f64 {
+        (-self.max_abs()..=self.max_abs()).map(|x| (x * x) as f64 * self.probability(x)).sum()}
+
+    fn sample_vec(&self, bits: &mut XofBits, len: usize) -> Vec<i64> {
+        (0..len).map(|_| self.sample(bits)).collect()}}
+
+/// Centered binomial distribution `CBD_eta`: `sum(a_i) - sum(b_i)` over `2 * eta` uniform bits,
+/// as in Kyber. Support `[-eta, eta]`, variance `eta / 2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CenteredBinomial {
+    eta: u32,}
+
+impl CenteredBinomial {
+    pub fn new(eta: u32) -> Result<Self, SamplerError> {
+        if eta == 0 || eta > MAX_ETA {
+            return Err(SamplerError::InvalidEta(eta));}
+        Ok(CenteredBinomial { eta })}
+
+    pub fn eta(&self) -> u32 {
+        self.eta}}
+
+impl ErrorDistribution for CenteredBinomial {
+    fn sample(&self, bits: &mut XofBits) -> i64 {
+        let x = bits.take(2 * self.eta);
+        let mask = if self.eta == 32 { u32::MAX as u64 } else { (1u64 << self.eta) - 1 };
+        (x & mask).count_ones() as i64 - ((x >> self.eta) & mask).count_ones() as i64}
+
+    fn max_abs(&self) -> i64 {
+        self.eta as i64}
+
+    fn probability(&self, x: i64) -> f64 {
+        let n = 2 * self.eta as i64;
+        let k = self.eta as i64 + x;
+        if !(0..=n).contains(&k) {
+            return 0.0;}
+        let k = k.min(n - k);
+        let binomial = (0..k).fold(1.0f64, |acc, i| acc * (n - i) as f64 / (i + 1) as f64);
+        binomial / 2f64.powi(n as i32)}
+
+    fn variance(&self) -> f64 {
+        self.eta as f64 / 2.0}}
+
+/// Discrete Gaussian with width `sigma` (probability proportional to `exp(-x^2 / (2 sigma^2))`),
+/// sampled by cumulative distribution table inversion.
+///
+/// The table holds `P(|X| <= k)` for `k = 0, 1, ..` as 63-bit fixed-point values. A sample reads
+/// 64 bits: 63 for a uniform `r`, compared against every table entry to give `|x|`, and one for the
+/// sign, applied by masking. Every sample scans the whole table without branching on secret data,
+/// so the running time depends only on `sigma`. Probabilities are exact to within 2^-63.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CdtGaussian {
+    sigma: f64,
+    table: Vec<u64>,}
+
+impl CdtGaussian {
+    pub fn new(sigma: f64) -> Result<Self, SamplerError> {
+        if !(sigma > 0.0 && sigma <= MAX_SIGMA) {
+            return Err(SamplerError::InvalidSigma(sigma));}
+        let cut = (TAIL_CUT * sigma).ceil() as usize;
+        // Folded weights: |x| = 0 keeps its weight, every k > 0 stands for both +k and -k.
+        let weights: Vec<f64> = (0..=cut)
+            .map(|k| {
+                let w = (-((k * k) as f64) / (2.0 * sigma * sigma)).exp();
+                if k == 0 { w } else { 2.0 * w }})
+            .collect();
+        let total: f64 = weights.iter().sum();
+        // Entries are 2^63 minus the remaining tail, summed from the far end, so small tail
+        // probabilities keep full relative precision instead of vanishing into the rounding of
+        // values near 2^63.
+        let mut tail = 0.0f64;
+        let mut table = vec![0u64; cut + 1];
+        for k in (0..=cut).rev() {
+            table[k] = CDT_ONE - (tail / total * CDT_ONE as f64).round() as u64;
+            tail += weights[k];}
+        // Entries equal to 2^63 can never be exceeded by a 63-bit `r`; drop them.
+        let len = table.iter().position(|&c| c == CDT_ONE).unwrap_or(table.len());
+        table.truncate(len);
+        Ok(CdtGaussian { sigma, table })}
+
+    pub fn sigma(&self) -> f64 {
+        self.sigma}
+
+    /// The cumulative table: entry `k` is `2^63 * P(|X| <= k)`.
+    pub fn table(&self) -> &[u64] {
+        &self.table}}
+
+impl ErrorDistribution for CdtGaussian {
+    fn sample(&self, bits: &mut XofBits) -> i64 {
+        let word = bits.take(64);
+        let r = word >> 1;
+        let mut magnitude = 0i64;
+        for &c in &self.table {
+            // 1 exactly when `c <= r`: `c - r - 1` wraps to a value with the top bit set.
+            magnitude += (c.wrapping_sub(r).wrapping_sub(1) >> 63) as i64;}
+        let negate = -((word & 1) as i64);
+        (magnitude ^ negate) - negate}
+
+    fn max_abs(&self) -> i64 {
+        self.table.len() as i64}
+
+    fn probability(&self, x: i64) -> f64 {
+        let k = x.unsigned_abs() as usize;
+        if k > self.table.len() {
+            return 0.0;}
+        let upper = self.table.get(k).copied().unwrap_or(CDT_ONE);
+        let lower = if k == 0 { 0 } else { self.table[k - 1] };
+        let folded = (upper - lower) as f64 / CDT_ONE as f64;
+        if k == 0 { folded } else { folded / 2.0 }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: usize = 100_000;
+
+    /// Checks the sample mean, the variance and a χ² goodness of fit against `probability`,
+    /// merging tail values whose expected count is below 5.
+    fn check_distribution<D: ErrorDistribution>(dist: &D, seed: &[u8]) {
+        let samples = dist.sample_vec(&mut XofBits::from_seed(seed), SAMPLES);
+        let n = SAMPLES as f64;
+        let mean = samples.iter().sum::<i64>() as f64 / n;
+        let variance = samples.iter().map(|&x| (x as f64 - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        assert!(mean.abs() < 5.0 * (dist.variance() / n).sqrt(), "mean {}", mean);
+        assert!((variance / dist.variance() - 1.0).abs() < 0.03, "variance {} against {}", variance, dist.variance());
+        assert!(samples.iter().all(|x| x.abs() <= dist.max_abs()));
+
+        let max = dist.max_abs();
+        let mut edge = max;
+        while n * dist.probability(edge) < 5.0 {
+            edge -= 1;}
+        let bin = |x: i64| x.clamp(-edge, edge);
+        let mut observed = vec![0f64; (2 * edge + 1) as usize];
+        for &x in &samples {
+            observed[(bin(x) + edge) as usize] += 1.0;}
+        let tail: f64 = (edge..=max).map(|x| dist.probability(x)).sum();
+        let chi2: f64 = (-edge..=edge).map(|x| {
+            let p = if x.abs() == edge { tail } else { dist.probability(x) };
+            let expected = n * p;
+            (observed[(x + edge) as usize] - expected).powi(2) / expected}).sum();
+        let df = (2 * edge) as f64;
+        assert!(chi2 < df + 5.0 * (2.0 * df).sqrt(), "chi2 {} with {} degrees of freedom", chi2, df);}
+
+    #[test]
+    fn centered_binomial_matches_its_distribution() {
+        for eta in [1, 2, 3, 8, 32] {
+            let cbd = CenteredBinomial::new(eta).unwrap();
+            let total: f64 = (-cbd.max_abs()..=cbd.max_abs()).map(|x| cbd.probability(x)).sum();
+            assert!((total - 1.0).abs() < 1e-12);
+            check_distribution(&cbd, &[eta as u8]);}
+        assert_eq!(CenteredBinomial::new(0), Err(SamplerError::InvalidEta(0)));
+        assert_eq!(CenteredBinomial::new(33), Err(SamplerError::InvalidEta(33)));}
+
+    #[test]
+    fn gaussian_matches_its_distribution() {
+        let gaussian = CdtGaussian::new(3.2).unwrap();
+        let total: f64 = (-gaussian.max_abs()..=gaussian.max_abs()).map(|x| gaussian.probability(x)).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+        assert!((gaussian.variance() - 3.2 * 3.2).abs() < 0.01);
+        check_distribution(&gaussian, b"gaussian");
+        check_distribution(&CdtGaussian::new(0.8).unwrap(), b"narrow");
+        assert!(CdtGaussian::new(0.0).is_err());
+        assert!(CdtGaussian::new(f64::NAN).is_err());
+        assert!(CdtGaussian::new(MAX_SIGMA * 2.0).is_err());}
+
+    #[test]
+    fn known_answers() {
+        let cbd = CenteredBinomial::new(2).unwrap();
+        assert_eq!(cbd.sample_vec(&mut XofBits::from_seed(b"x"), 16), [0, 1, 0, 0, 0, 0, 0, -2, 2, 1, -1, 2, 0, -1, 1, 0]);
+        let gaussian = CdtGaussian::new(3.2).unwrap();
+        assert_eq!(gaussian.sample_vec(&mut XofBits::from_seed(b"x"), 16), [2, -5, -3, -5, 5, -3, 3, -1, 1, 4, 1, 4, 0, 2, 7, 2]);}
+
+    #[test]
+    fn bit_reads_do_not_depend_on_how_they_are_split() {
+        let mut whole = XofBits::from_seed(b"split");
+        let mut parts = XofBits::from_seed(b"split");
+        let expected = [whole.take(64), whole.take(64)];
+        let low = parts.take(13);
+        let middle = parts.take(64);
+        let high = parts.take(51);
+        assert_eq!(low | middle << 13, expected[0]);
+        assert_eq!(middle >> 51 | high << 13, expected[1]);}}
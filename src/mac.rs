@@ -0,0 +1,108 @@
+// src/mac.rs
+use crate::core::HmacQosmicKey;
+use crate::encode::decode_digest;
+use std::fmt;
+
+pub const TAG_LEN: usize = 64;
+/// Shortest truncated tag accepted by default (128 bits).
+pub const MIN_TAG_LEN: usize = 16;
+
+pub fn verify_hmac_bytes(key: &[u8], message: &[u8], tag: &[u8], min_tag_len: usize) -> bool {
+    HmacQosmicKey::new(key).verify(message, tag, min_tag_len)}
+
+/// Verifies an HMAC-qosmic tag given in any encoding `decode_digest` understands (hex, Base58Check,
+/// Base64, ...). Truncated tags down to `MIN_TAG_LEN` bytes are accepted.
+pub fn verify_hmac(key: &[u8], message: &[u8], tag: &str) -> bool {
+    match decode_digest(tag) {
+        Some(tag_bytes) => verify_hmac_bytes(key, message, &tag_bytes, MIN_TAG_LEN),
+        None => false,}}
+
+/// A set of named HMAC keys for rotation. New tags are made with the primary key (the first one);
+/// tags from any key still in the ring verify, so a new key can be rolled out before the old one
+/// is retired.
+#[derive(Clone)]
+pub struct MacKeyRing {
+    keys: Vec<(String, HmacQosmicKey)>,
+    min_tag_len: usize,}
+
+impl fmt::Debug for MacKeyRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids: Vec<&str> = self.keys.iter().map(|(id, _)| id.as_str()).collect();
+        f.debug_struct("MacKeyRing").field("key_ids", &ids).field("min_tag_len", &self.min_tag_len).finish()}}
+
+impl Default for MacKeyRing {
+    fn default() -> Self {
+        MacKeyRing::new()}}
+
+impl MacKeyRing {
+    pub fn new() -> Self {
+        MacKeyRing { keys: Vec::new(), min_tag_len: MIN_TAG_LEN }}
+
+    pub fn with_min_tag_len(mut self, min_tag_len: usize) -> Self {
+        self.min_tag_len = min_tag_len;
+        self}
+
+    /// Makes `key` the primary signing key. An existing key with the same id is replaced.
+    pub fn insert_primary(&mut self, id: &str, key: &[u8]) {
+        self.retire(id);
+        self.keys.insert(0, (id.to_string(), HmacQosmicKey::new(key)));}
+
+    /// Adds `key` for verification only, behind the current primary.
+    pub fn add_key(&mut self, id: &str, key: &[u8]) {
+        self.retire(id);
+        self.keys.push((id.to_string(), HmacQosmicKey::new(key)));}
+
+    /// Removes the key with `id`, returning whether it was present.
+    pub fn retire(&mut self, id: &str) -> bool {
+        let before = self.keys.len();
+        self.keys.retain(|(key_id, _)| key_id != id);
+        self.keys.len() != before}
+
+    pub fn primary_id(&self) -> Option<&str> {
+        self.keys.first().map(|(id, _)| id.as_str())}
+
+    pub fn key_ids(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(|(id, _)| id.as_str())}
+
+    /// Tags `message` with the primary key, returning its id alongside the full 64-byte tag.
+    pub fn sign(&self, message: &[u8]) -> Option<(&str, [u8; TAG_LEN])> {
+        self.keys.first().map(|(id, key)| (id.as_str(), key.mac(message)))}
+
+    /// Returns the id of the key that produced `tag`. Every key is tried, even after a match,
+    /// so timing does not reveal which key verified.
+    pub fn verify_bytes(&self, message: &[u8], tag: &[u8]) -> Option<&str> {
+        let mut matched = None;
+        for (id, key) in &self.keys {
+            if key.verify(message, tag, self.min_tag_len) && matched.is_none() {
+                matched = Some(id.as_str());}}
+        matched}
+
+    pub fn verify(&self, message: &[u8], tag: &str) -> Option<&str> {
+        self.verify_bytes(message, &decode_digest(tag)?)}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::hmac_qosmic_bytes;
+    use crate::encode::to_binary;
+
+    #[test]
+    fn verify_accepts_full_and_truncated_tags_in_any_encoding() {
+        let tag = hex::encode(hmac_qosmic_bytes(b"key", b"message"));
+        assert!(verify_hmac(b"key", b"message", &tag));
+        assert!(verify_hmac(b"key", b"message", &tag[..2 * MIN_TAG_LEN]));
+        assert!(verify_hmac(b"key", b"message", &to_binary(&tag[..2 * MIN_TAG_LEN])));
+        assert!(!verify_hmac(b"key", b"message", &tag[..2 * MIN_TAG_LEN - 2]));
+        assert!(!verify_hmac(b"key", b"other message", &tag));
+        assert!(!verify_hmac(b"other key", b"message", &tag));}
+
+    #[test]
+    fn key_ring_verifies_tags_from_retired_keys_until_removed() {
+        let mut ring = MacKeyRing::new();
+        ring.insert_primary("old", b"old key");
+        let (_, old_tag) = ring.sign(b"msg").unwrap();
+        ring.insert_primary("new", b"new key");
+        assert_eq!(ring.primary_id(), Some("new"));
+        assert_eq!(ring.verify_bytes(b"msg", &old_tag), Some("old"));
+        assert!(ring.retire("old"));
+        assert_eq!(ring.verify_bytes(b"msg", &old_tag), None);}}
@@ -1,5 +1,13 @@
 // src/main.rs
-use qosmic_lib::{encode, get_sbox, hash_password, hmac_qosmic, qosmic_unkeyed};
+use qosmic_lib::{encode, get_sbox, hmac_qosmic, qosmic_unkeyed, qosmic_lattice_digest, derive_key, KdfParams};
+use qosmic_lib::{calibrate_memory_hard, calibrate_pbkdf2, MacKeyRing};
+use qosmic_lib::{sign_with_state_file, xmss, XmssPrivateKey, XmssPublicKey, XmssSignature};
+use qosmic_lib::{merkle, InclusionProof, MerkleTree};
+use qosmic_lib::{transparency, ConsistencyProof, HeadVerifier, SignedTreeHead, TransparencyLog};
+use qosmic_lib::{keyfile, KeyAlgorithm, KeyFile, KeyFormat};
+use qosmic_lib::{aead, AeadError, KeySource, StreamHeader};
+use qosmic_lib::{cert, issue_with_state_file, verify_chain, Certificate, TbsCertificate, TrustStore};
+use qosmic_lib::{hash_password_memory_hard_with_pepper, hash_password_with_pepper, hkdf, parse_pepper_list, rehash_reasons, verify_password_with_peppers, MemoryHardParams, Pepper, RehashPolicy};
 use qosmic_lib::utils::derive_deterministic_nonce;
 use std::io::BufRead;
 use log::{LevelFilter, debug, info, error};
@@ -37,11 +45,64 @@ fn main() {
         logger_builder.target(Target::Stderr);}
     logger_builder.filter_level(log_level).init();
     debug!("Application started with arguments: {:?}", args);
+    if subcommand(&args) == Some("kdf") {
+        info!("Running in key derivation mode.");
+        run_kdf_mode(args);
+        process::exit(0);}
+    if subcommand(&args) == Some("kdf-calibrate") {
+        info!("Running in KDF calibration mode.");
+        run_kdf_calibrate_mode(args);
+        process::exit(0);}
+    if subcommand(&args) == Some("hmac") {
+        info!("Running in HMAC mode.");
+        run_hmac_mode(args);
+        process::exit(0);}
+    if subcommand(&args) == Some("xmss") {
+        info!("Running in XMSS signature mode.");
+        run_xmss_mode(args);
+        process::exit(0);}
+    if subcommand(&args) == Some("cert") {
+        info!("Running in certificate mode.");
+        run_cert_mode(args);
+        process::exit(0);}
+    if subcommand(&args) == Some("key") {
+        info!("Running in key file mode.");
+        run_key_mode(args);
+        process::exit(0);}
+    if subcommand(&args) == Some("merkle") {
+        info!("Running in Merkle tree mode.");
+        run_merkle_mode(args);
+        process::exit(0);}
+    if subcommand(&args) == Some("log") {
+        info!("Running in transparency log mode.");
+        run_log_mode(args);
+        process::exit(0);}
+    if let Some(mode @ ("encrypt" | "decrypt")) = subcommand(&args) {
+        info!("Running in {} mode.", mode);
+        let mode = mode.to_string();
+        run_aead_mode(args, &mode);
+        process::exit(0);}
+    if subcommand(&args) == Some("hkdf") {
+        info!("Running in HKDF mode.");
+        run_hkdf_mode(args);
+        process::exit(0);}
+    if subcommand(&args) == Some("rehash-check") {
+        info!("Running in rehash check mode.");
+        run_rehash_check_mode(args);
+        process::exit(0);}
     if let Some(pos) = args.iter().position(|r| r == "--password") {
         if pos + 1 < args.len() {
             let password = args[pos + 1].clone();
+            let peppers = take_peppers(&mut args);
+            if let Some(vpos) = args.iter().position(|r| r == "--verify") {
+                if vpos + 1 >= args.len() {
+                    error!("Error: Missing stored hash after --verify flag.");
+                    print_usage_cli();
+                    process::exit(1);}
+                info!("Running in password verification mode.");
+                run_verify_password_mode(password.as_bytes(), &args[vpos + 1], &peppers);}
             info!("Running in password hashing mode.");
-            run_password_mode(password.as_bytes());
+            run_password_mode(password.as_bytes(), &mut args, peppers.first());
             process::exit(0);
         } else {
             error!("Error: Missing password after --password flag.");
@@ -63,19 +124,29 @@ fn main() {
         if pos + 1 < args.len() {
             let format_arg = args[pos + 1].clone();
             match format_arg.as_str() {
-                "b36" | "b58" | "b64" | "bin" | "hex" => {
+                "b36" | "b58" | "b58c" | "b64" | "bin" | "hex" => {
                     output_format = Some(format_arg.clone());
                     debug!("Output format set to: {}", format_arg);},
                 _ => {
-                    error!("Error: Invalid output format '{}'. Use -o b36, -o b58, -o b64, -o bin, or -o hex.", format_arg);
+                    error!("Error: Invalid output format '{}'. Use -o b36, -o b58, -o b58c, -o b64, -o bin, or -o hex.", format_arg);
                     print_usage_cli();
                     process::exit(1);}}
             args.remove(pos + 1);
             args.remove(pos);
         } else {
-            error!("Error: Missing output format after -o flag. Use -o b36, -o b58, -o b64, -o bin, or -o hex.");
+            error!("Error: Missing output format after -o flag. Use -o b36, -o b58, -o b58c, -o b64, -o bin, or -o hex.");
             print_usage_cli();
             process::exit(1);}}
+    let lattice_mode = match take_flag_value(&mut args, "--mode").as_deref() {
+        None | Some("standard") => false,
+        Some("lattice") => true,
+        Some(other) => {
+            error!("Error: Invalid hash mode '{}'. Use --mode standard or --mode lattice.", other);
+            print_usage_cli();
+            process::exit(1);}};
+    if lattice_mode && key.is_some() {
+        error!("Error: --mode lattice is an unkeyed hash and cannot be combined with --key.");
+        process::exit(1);}
     let mut batch_file_path: Option<String> = None;
     if let Some(pos) = args.iter().position(|r| r == "--batch-file") {
         if pos + 1 < args.len() {
@@ -87,6 +158,9 @@ fn main() {
             error!("Error: Missing file path after --batch-file flag.");
             print_usage_cli();
             process::exit(1);}}
+    if lattice_mode && (batch_file_path.is_some() || args.contains(&"--interactive".to_string())) {
+        error!("Error: --mode lattice only applies to -f and -s input.");
+        process::exit(1);}
     if args.contains(&"--interactive".to_string()) {
         if batch_file_path.is_some() {
             error!("Error: Cannot use --interactive and --batch-file together.");
@@ -99,7 +173,7 @@ fn main() {
         run_batch_mode(path, key, output_format);
     } else {
         info!("Running in CLI mode.");
-        run_cli_mode(args, key, output_format);}
+        run_cli_mode(args, key, output_format, lattice_mode);}
     debug!("Application finished.");}
 
 fn print_help() {
@@ -108,35 +182,965 @@ fn print_help() {
     println!("Input Modes:");
     println!("  -f <file>      Specify a file path as input.");
     println!("  -s <string>    Specify a string literal as input.");
-    println!("  --password <password> Run in password hashing (KDF) mode.\n");
+    println!("  --password <password> Run in password hashing (KDF) mode. Output is a PHC string.");
+    println!("  --password <password> --verify <hash> Check a password against a PHC or legacy salt$hash string.");
+    println!("  kdf [KDF options] <password> Derive a raw key with PBKDF2-HMAC-Qosmic and print it as hex.");
+    println!("  kdf-calibrate --target-ms <n> [--kdf pbkdf2|mem] [--max-memory <KiB>] Benchmark this machine and print KDF parameters that take about <n> ms.");
+    println!("  hmac --key <key> [--key <older key>...] (-s <string> | -f <file>) [--verify <tag>] [--min-tag-len <n>]");
+    println!("                 Print the HMAC-qosmic tag, or check a tag (hex, b58c, b64, b58 or bin; truncated to >= 16 bytes).");
+    println!("                 The first --key is current; later ones are still accepted by --verify during rotation.");
+    println!("  xmss keygen [--height <n>] <private_key_file> <public_key_file>");
+    println!("                 Create a stateful XMSS (WOTS+) hash-based signature key with 2^n one-time keys (default n = 8).");
+    println!("  xmss sign <private_key_file> (-s <string> | -f <file>) [--out <signature_file>]");
+    println!("                 Sign with the next one-time key; the key file is updated before the signature is printed.");
+    println!("  xmss verify <public_key_file> <signature_file> (-s <string> | -f <file>)");
+    println!("  xmss info <key_file>  Show height, root and remaining signatures of a private or public key.");
+    println!("  cert issue --subject <name> --signing-key <xmss_private_key> [--issuer-cert <cert>] [--public-key <xmss_public_key>]");
+    println!("             [--days <n>] [--not-before <unix>] [--serial <n>] [--ca [--path-len <n>]] [--ext <id>=<text>...] --out <cert_file>");
+    println!("                 Issue a certificate. Without --issuer-cert it is self-signed for the signing key's own public key;");
+    println!("                 with it, --public-key names the subject's key. Validity defaults to 365 days from now.");
+    println!("  cert show <cert_file>  Print the fields of a certificate.");
+    println!("  cert verify <cert_file> --trust-dir <dir> [--chain <cert_file>...] [--at <unix>]");
+    println!("                 Check the chain up to a root in <dir> (every *.qcert file there is trusted).");
+    println!("  key inspect <key_file> [--password <password>]");
+    println!("                 Show the algorithm, format, protection and checksum of a key file; with --password, also open it.");
+    println!("  key wrap --alg <algorithm> <raw_key_file> --out <key_file> [--password <password>] [--iterations <n>] [--salt-len <n>] [--binary]");
+    println!("                 Store a raw key (e.g. from xmss keygen) in an armored (default) or binary key file.");
+    println!("                 Algorithms: hmac-qosmic, xmss-private, xmss-public, lwe-public, lwe-secret, lwe-kem-secret, aead.");
+    println!("  key unwrap <key_file> --out <raw_key_file> [--password <password>]  Extract the raw key again.");
+    println!("  merkle root <file> [--chunk-size <n>]  Print the Merkle root of the file split into <n>-byte chunks (default 4096).");
+    println!("  merkle prove <file> --index <i> [--chunk-size <n>] [--out <proof_file>] [--chunk-out <file>]");
+    println!("                 Print (or write) the inclusion proof for chunk <i>; --chunk-out also saves the chunk itself.");
+    println!("  merkle verify <proof_file> --root <hex> (-s <string> | -f <chunk_file>)  Check that the chunk is under the root.");
+    println!("  log append <log_dir> (-s <string> | -f <file>)  Append an entry to the append-only log (created if missing).");
+    println!("  log head <log_dir> (--key <key> | --xmss-key <xmss_private_key>) [--out <sth_file>]  Sign the current tree head.");
+    println!("  log prove <log_dir> (--index <i> | --from <old_size>) [--size <n>] [--out <proof_file>]");
+    println!("                 Inclusion proof for entry <i>, or consistency proof from <old_size>, against the tree of <n> entries (default: all).");
+    println!("  log audit <log_dir> [--sth <sth_file>... (--key <key> | --xmss-pub <file>)]");
+    println!("                 Rehash every entry and check that each signed head still matches the log.");
+    println!("  log audit --sth <sth_file> (--key <key> | --xmss-pub <file>) --proof <proof_file> (-s <string> | -f <file>)");
+    println!("  log audit --sth <old_sth> --sth <new_sth> (--key <key> | --xmss-pub <file>) --proof <proof_file>");
+    println!("                 Check an entry against a signed head, or that the log only grew between two heads, without the log.");
+    println!("  encrypt <in_file> --out <out_file> (--password <password> | --key-file <key_file> [--key-password <password>])");
+    println!("          [--iterations <n>] [--salt-len <n>] [--chunk-size <n>] [--aad <text>]");
+    println!("                 Encrypt a file with the qosmic AEAD in authenticated chunks (default 65536 bytes). A password is");
+    println!("                 stretched with PBKDF2; a key file must hold a 32-byte aead key. --aad binds extra context to the file.");
+    println!("  decrypt <in_file> --out <out_file> (--password <password> | --key-file <key_file> [--key-password <password>]) [--aad <text>]");
+    println!("                 Decrypt and authenticate a file; <out_file> is only written if every chunk checks.");
+    println!("  hkdf --ikm <hex> [--salt <hex>] [--context <text>] [--length <n>] Derive a subkey with HKDF-Qosmic (RFC 5869).");
+    println!("  rehash-check [KDF options] [--allow-legacy] [--pepper-file <file>] <file> Report which stored hashes (one per line) fall below the given parameters.\n");
+    println!("KDF options (for --password and kdf):");
+    println!("  --iterations <n>  PBKDF2 iteration count (default 10000).");
+    println!("  --length <n>      Derived key length in bytes (default 64).");
+    println!("  --salt-len <n>    Random salt length in bytes (default 16).");
+    println!("  --salt <hex>      Use this salt instead of a random one (deterministic derivation).");
+    println!("  --kdf <name>      Password hashing algorithm for --password: 'pbkdf2' (default) or 'mem' (memory-hard).");
+    println!("  --memory <KiB>    Memory cost for --kdf mem (default 4096).");
+    println!("  --time <n>        Passes over memory for --kdf mem (default 3).");
+    println!("  --parallelism <n> Independent lanes, each on its own thread, for --kdf mem (default 1).");
+    println!("  --pepper-file <file> Server-side peppers, one 'id:hex_secret' per line. The first is used for new hashes;");
+    println!("                    all are accepted by --verify, which picks the pepper named by the hash's pid= parameter.");
+    println!("  --pepper-id <id>  Use this pepper from --pepper-file for new hashes instead of the first one.\n");
     println!("Options:");
-    println!("  -o <format>    Specify output format: 'b36' (Base36), 'b58' (Base58), 'b64' (Base64), 'bin' (Binary), 'hex' (Hex, default)");
+    println!("  -o <format>    Specify output format: 'b36' (Base36), 'b58' (Base58), 'b58c' (Base58Check), 'b64' (Base64), 'bin' (Binary), 'hex' (Hex, default)");
     println!("  --help         Display this help message and exit.");
     println!("  --version      Display version information and exit.");
     println!("  --debug        Enable debug logging (writes to qosmic_debug.txt or stderr).");
     println!("  --info         Enable info logging (writes to stderr).");
+    println!("  --mode <mode>  Hash mode for -f/-s: 'standard' (default) or 'lattice' (adds Ring-LWE absorption, about 3.5 ms per 4 KiB).");
     println!("  --key <key>    Specify a user-defined key. When present, HMAC-qosmic is used. For multi-word input, enclose in double quotes.");
     println!("  --interactive  Run in interactive mode, processing input line by line from stdin.");
     println!("  --batch-file <file> Process lines from a file as input, outputting hashes one per line for max performance.\n");
     println!("Examples:");
     println!("  qosmic -s \"Hello World\" -o b36");
     println!("  qosmic --password \"yourPassword123\"");
+    println!("  qosmic --password \"yourPassword123\" --verify '$qosmic-pbkdf2$v=1$i=10000,l=64$...'");
+    println!("  qosmic --password \"yourPassword123\" --kdf mem --memory 65536 --time 3 --parallelism 4");
+    println!("  qosmic --password \"yourPassword123\" --pepper-file peppers.txt --pepper-id 2026a");
+    println!("  qosmic kdf --iterations 50000 --salt 00112233445566778899aabbccddeeff --length 32 \"yourPassword123\"");
+    println!("  qosmic kdf-calibrate --target-ms 250 --kdf mem --parallelism 2");
+    println!("  qosmic hmac --key \"new key\" --key \"old key\" -s \"message\" --verify 5f3a9c...");
+    println!("  qosmic xmss keygen --height 10 signer.xmss signer.xmss.pub");
+    println!("  qosmic xmss sign signer.xmss -f release.tar --out release.tar.sig");
+    println!("  qosmic cert issue --subject \"Example Root\" --signing-key root.xmss --ca --days 3650 --out trust/root.qcert");
+    println!("  qosmic cert issue --subject build.example --signing-key root.xmss --issuer-cert trust/root.qcert --public-key signer.xmss.pub --out signer.qcert");
+    println!("  qosmic cert verify signer.qcert --trust-dir trust");
+    println!("  qosmic key wrap --alg xmss-private signer.xmss --out signer.key --password \"key password\"");
+    println!("  qosmic key inspect signer.key");
+    println!("  qosmic merkle prove dataset.bin --index 42 --out chunk42.proof --chunk-out chunk42.bin");
+    println!("  qosmic merkle verify chunk42.proof --root 9c1e... -f chunk42.bin");
+    println!("  qosmic log append releases.log -f release.tar.sig");
+    println!("  qosmic log head releases.log --xmss-key log.xmss --out head-0042.sth");
+    println!("  qosmic log audit --sth head-0042.sth --sth head-0050.sth --xmss-pub log.xmss.pub --proof 42-50.proof");
+    println!("  qosmic key wrap --alg aead backup.rawkey --out backup.key --password \"key password\"");
+    println!("  qosmic encrypt backup.tar --out backup.tar.qenc --key-file backup.key --key-password \"key password\"");
+    println!("  qosmic decrypt notes.txt.qenc --out notes.txt --password \"correct horse\"");
+    println!("  qosmic hkdf --ikm 000102030405060708090a0b0c0d0e0f --context \"session key\" --length 32");
+    println!("  qosmic rehash-check --iterations 50000 stored_hashes.txt");
+    println!("  qosmic -s \"Hello World\" --mode lattice");
     println!("  qosmic -f my_document.txt --key \"my secret key\" --debug");
     println!("  qosmic --interactive -o bin");
     println!("  qosmic --batch-file nonce_list.txt -o hex\n");}
 
 fn print_usage_cli() {
-    println!("Usage: qosmic [--debug|--info] (-f <file> | -s <string> | --password <password> | --interactive | --batch-file <file>) [-o b36|b58|b64|bin|hex] [--key <key>] [--version|--help]");
+    println!("Usage: qosmic [--debug|--info] (-f <file> | -s <string> | --password <password> | --interactive | --batch-file <file> | kdf <password> | kdf-calibrate --target-ms <n> | hmac --key <key> -s <string> [--verify <tag>] | xmss keygen|sign|verify|info | cert issue|show|verify | key inspect|wrap|unwrap | merkle root|prove|verify | log append|head|prove|audit | encrypt|decrypt <file> --out <file> | hkdf --ikm <hex> | rehash-check <file>) [-o b36|b58|b58c|b64|bin|hex] [--mode standard|lattice] [--key <key>] [--version|--help]");
     println!("For detailed help, run: qosmic --help");}
 
-fn run_password_mode(password: &[u8]) {
-    info!("Calculating password hash with 10,000 iterations...");
-    let hash_result = hash_password(password);
+fn subcommand(args: &[String]) -> Option<&str> {
+    args.iter().skip(1).map(String::as_str).find(|a| *a != "--debug" && *a != "--info")}
+
+fn take_flag_value(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let pos = args.iter().position(|r| r == flag)?;
+    if pos + 1 >= args.len() {
+        error!("Error: Missing value after {} flag.", flag);
+        print_usage_cli();
+        process::exit(1);}
+    let value = args.remove(pos + 1);
+    args.remove(pos);
+    Some(value)}
+
+fn take_flag_number<T: std::str::FromStr>(args: &mut Vec<String>, flag: &str) -> Option<T> {
+    let value = take_flag_value(args, flag)?;
+    match value.parse::<T>() {
+        Ok(n) => Some(n),
+        Err(_) => {
+            error!("Error: Invalid number '{}' for {} flag.", value, flag);
+            print_usage_cli();
+            process::exit(1);}}}
+
+fn parse_kdf_params(args: &mut Vec<String>) -> KdfParams {
+    let mut params = KdfParams::default();
+    if let Some(iterations) = take_flag_number(args, "--iterations") {
+        params.iterations = iterations;}
+    if let Some(salt_len) = take_flag_number(args, "--salt-len") {
+        params.salt_len = salt_len;}
+    if let Some(output_len) = take_flag_number(args, "--length") {
+        params.output_len = output_len;}
+    if let Some(salt_hex) = take_flag_value(args, "--salt") {
+        match hex::decode(&salt_hex) {
+            Ok(salt) => params.salt = Some(salt),
+            Err(e) => {
+                error!("Error: Salt must be hex-encoded: {}", e);
+                process::exit(1);}}}
+    if let Err(e) = params.validate() {
+        error!("Error: Invalid KDF parameters: {}", e);
+        process::exit(1);}
+    debug!("KDF parameters: iterations={}, salt_len={}, output_len={}, fixed_salt={}",
+        params.iterations, params.salt_len, params.output_len, params.salt.is_some());
+    params}
+
+fn run_kdf_mode(mut args: Vec<String>) {
+    let params = parse_kdf_params(&mut args);
+    let positional: Vec<&String> = args.iter().skip(1)
+        .filter(|a| !matches!(a.as_str(), "kdf" | "--debug" | "--info"))
+        .collect();
+    if positional.len() != 1 {
+        error!("Error: kdf expects exactly one password argument.");
+        print_usage_cli();
+        process::exit(1);}
+    info!("Deriving {} bytes with {} iterations...", params.output_len, params.iterations);
+    let (salt, derived_key) = match derive_key(positional[0].as_bytes(), &params) {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Error: Key derivation failed: {}", e);
+            process::exit(1);}};
+    if params.salt.is_none() {
+        eprintln!("Salt: {}", hex::encode(&salt));}
+    println!("{}", hex::encode(derived_key));
+    debug!("Derived key printed to stdout.");}
+
+fn run_kdf_calibrate_mode(mut args: Vec<String>) {
+    let target_ms: u64 = match take_flag_number(&mut args, "--target-ms") {
+        Some(ms) if ms > 0 => ms,
+        Some(_) => {
+            error!("Error: --target-ms must be greater than zero.");
+            process::exit(1);},
+        None => {
+            error!("Error: kdf-calibrate requires --target-ms <n>.");
+            print_usage_cli();
+            process::exit(1);}};
+    let target = std::time::Duration::from_millis(target_ms);
+    let kdf = take_flag_value(&mut args, "--kdf").unwrap_or_else(|| "pbkdf2".to_string());
+    let calibration = match kdf.as_str() {
+        "pbkdf2" => calibrate_pbkdf2(target, &parse_kdf_params(&mut args)),
+        "mem" => {
+            let max_memory_kib = take_flag_number(&mut args, "--max-memory").unwrap_or(1024 * 1024);
+            calibrate_memory_hard(target, &parse_memory_hard_params(&mut args), max_memory_kib)},
+        other => {
+            error!("Error: Unknown KDF '{}'. Use --kdf pbkdf2 or --kdf mem.", other);
+            print_usage_cli();
+            process::exit(1);}};
+    let calibration = match calibration {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Error: Calibration failed: {}", e);
+            process::exit(1);}};
+    eprintln!("Measured {} ms (target {} ms). Flags: {}", calibration.measured.as_millis(), target_ms, calibration.params.cli_flags());
+    println!("{}", calibration.params);
+    debug!("Calibrated parameters printed to stdout.");}
+
+fn parse_memory_hard_params(args: &mut Vec<String>) -> MemoryHardParams {
+    let mut params = MemoryHardParams::default();
+    if let Some(memory_kib) = take_flag_number(args, "--memory") {
+        params.memory_kib = memory_kib;}
+    if let Some(time_cost) = take_flag_number(args, "--time") {
+        params.time_cost = time_cost;}
+    if let Some(parallelism) = take_flag_number(args, "--parallelism") {
+        params.parallelism = parallelism;}
+    if let Some(output_len) = take_flag_number(args, "--length") {
+        params.output_len = output_len;}
+    if let Some(salt_len) = take_flag_number(args, "--salt-len") {
+        params.salt_len = salt_len;}
+    if let Some(salt_hex) = take_flag_value(args, "--salt") {
+        match hex::decode(&salt_hex) {
+            Ok(salt) => params.salt = Some(salt),
+            Err(e) => {
+                error!("Error: Salt must be hex-encoded: {}", e);
+                process::exit(1);}}}
+    if let Err(e) = params.validate() {
+        error!("Error: Invalid memory-hard KDF parameters: {}", e);
+        process::exit(1);}
+    debug!("Memory-hard parameters: {:?}", params);
+    params}
+
+fn take_hex_flag(args: &mut Vec<String>, flag: &str) -> Option<Vec<u8>> {
+    let value = take_flag_value(args, flag)?;
+    match hex::decode(&value) {
+        Ok(bytes) => Some(bytes),
+        Err(e) => {
+            error!("Error: Value for {} must be hex-encoded: {}", flag, e);
+            process::exit(1);}}}
+
+/// Reads `--pepper-file` (and `--pepper-id`) from `args`. The current pepper is moved to the front.
+fn take_peppers(args: &mut Vec<String>) -> Vec<Pepper> {
+    let current_id = take_flag_value(args, "--pepper-id");
+    let path = match take_flag_value(args, "--pepper-file") {
+        Some(path) => path,
+        None if current_id.is_some() => {
+            error!("Error: --pepper-id requires --pepper-file.");
+            print_usage_cli();
+            process::exit(1);},
+        None => return Vec::new(),};
+    let contents = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to read pepper file '{}': {}", path, e);
+            process::exit(1);}};
+    let mut peppers = match parse_pepper_list(&contents) {
+        Ok(p) if !p.is_empty() => p,
+        Ok(_) => {
+            error!("Error: Pepper file '{}' contains no peppers.", path);
+            process::exit(1);},
+        Err(e) => {
+            error!("Error: Invalid pepper file '{}': {}", path, e);
+            process::exit(1);}};
+    if let Some(id) = current_id {
+        match peppers.iter().position(|p| p.id() == id) {
+            Some(idx) => peppers[..=idx].rotate_right(1),
+            None => {
+                error!("Error: Pepper id '{}' not found in '{}'.", id, path);
+                process::exit(1);}}}
+    debug!("Loaded {} pepper(s), current id '{}'.", peppers.len(), peppers[0].id());
+    peppers}
+
+/// Reads the `-s <string>` or `-f <file>` input of a subcommand.
+fn take_message(args: &mut Vec<String>, mode: &str) -> Vec<u8> {
+    if let Some(text) = take_flag_value(args, "-s") {
+        text.into_bytes()
+    } else if let Some(path) = take_flag_value(args, "-f") {
+        match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                error!("Failed to read file '{}': {}", path, e);
+                process::exit(1);}}
+    } else {
+        error!("Error: {} requires -s <string> or -f <file>.", mode);
+        print_usage_cli();
+        process::exit(1);}}
+
+fn run_hmac_mode(mut args: Vec<String>) {
+    let mut ring = MacKeyRing::new();
+    let mut key_count = 0usize;
+    while let Some(key) = take_flag_value(&mut args, "--key") {
+        key_count += 1;
+        ring.add_key(&key_count.to_string(), key.as_bytes());}
+    if key_count == 0 {
+        error!("Error: hmac requires at least one --key <key>.");
+        print_usage_cli();
+        process::exit(1);}
+    if let Some(min_tag_len) = take_flag_number(&mut args, "--min-tag-len") {
+        ring = ring.with_min_tag_len(min_tag_len);}
+    let tag = take_flag_value(&mut args, "--verify");
+    let message = take_message(&mut args, "hmac");
+    let tag = match tag {
+        Some(tag) => tag,
+        None => {
+            let (_, tag) = ring.sign(&message).expect("Key ring has at least one key");
+            println!("{}", hex::encode(tag));
+            return;}};
+    match ring.verify(&message, &tag) {
+        Some(id) => {
+            debug!("Tag verified with key #{}.", id);
+            println!("OK");
+            process::exit(0);},
+        None => {
+            println!("MISMATCH");
+            process::exit(2);}}}
+
+fn read_file_or_exit(path: &str, what: &str) -> Vec<u8> {
+    match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to read {} '{}': {}", what, path, e);
+            process::exit(1);}}}
+
+fn write_file_or_exit(path: &str, bytes: &[u8], what: &str) {
+    if let Err(e) = fs::write(path, bytes) {
+        error!("Failed to write {} '{}': {}", what, path, e);
+        process::exit(1);}}
+
+fn run_xmss_mode(mut args: Vec<String>) {
+    let height = take_flag_number(&mut args, "--height").unwrap_or(xmss::DEFAULT_HEIGHT);
+    let out_path = take_flag_value(&mut args, "--out");
+    let message = if args.iter().any(|a| a == "-s" || a == "-f") { Some(take_message(&mut args, "xmss")) } else { None };
+    let positional: Vec<String> = args.iter().skip(1)
+        .filter(|a| !matches!(a.as_str(), "xmss" | "--debug" | "--info"))
+        .cloned()
+        .collect();
+    let action = positional.first().map(String::as_str).unwrap_or("");
+    match (action, positional.len(), message) {
+        ("keygen", 3, None) => {
+            eprintln!("Generating XMSS key with {} one-time keys...", 1u64 << height.min(63));
+            let key = match XmssPrivateKey::generate(height) {
+                Ok(key) => key,
+                Err(e) => {
+                    error!("Error: Key generation failed: {}", e);
+                    process::exit(1);}};
+            if let Err(e) = xmss::write_state_atomically(std::path::Path::new(&positional[1]), &key.to_bytes()) {
+                error!("Failed to write private key '{}': {}", positional[1], e);
+                process::exit(1);}
+            write_file_or_exit(&positional[2], &key.public_key().to_bytes(), "public key");
+            println!("{}", hex::encode(key.public_key().root));},
+        ("sign", 2, Some(message)) => {
+            let (signature, remaining) = match sign_with_state_file(std::path::Path::new(&positional[1]), &message) {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Error: Signing failed: {}", e);
+                    process::exit(1);}};
+            eprintln!("Used one-time key {}; {} signatures remaining.", signature.index, remaining);
+            match out_path {
+                Some(path) => write_file_or_exit(&path, &signature.to_bytes(), "signature"),
+                None => println!("{}", hex::encode(signature.to_bytes())),}},
+        ("verify", 3, Some(message)) => {
+            let public_key = XmssPublicKey::from_bytes(&read_file_or_exit(&positional[1], "public key"));
+            let signature_bytes = read_file_or_exit(&positional[2], "signature");
+            let signature = XmssSignature::from_bytes(&signature_bytes)
+                .or_else(|e| hex::decode(String::from_utf8_lossy(&signature_bytes).trim()).map_err(|_| e).and_then(|b| XmssSignature::from_bytes(&b)));
+            match (public_key, signature) {
+                (Ok(pk), Ok(sig)) if pk.verify(&message, &sig) => {
+                    println!("OK");
+                    process::exit(0);},
+                (Ok(_), Ok(_)) => {
+                    println!("MISMATCH");
+                    process::exit(2);},
+                (Err(e), _) | (_, Err(e)) => {
+                    error!("Error: Could not verify signature: {}", e);
+                    process::exit(1);}}},
+        ("info", 2, None) => {
+            let bytes = read_file_or_exit(&positional[1], "key");
+            if let Ok(pk) = XmssPublicKey::from_bytes(&bytes) {
+                println!("XMSS public key: height {}, root {}", pk.height, hex::encode(pk.root));
+            } else {
+                match XmssPrivateKey::from_bytes(&bytes) {
+                    Ok(key) => println!("XMSS private key: height {}, root {}, next index {}, {} signatures remaining",
+                        key.height(), hex::encode(key.public_key().root), key.next_index(), key.remaining_signatures()),
+                    Err(e) => {
+                        error!("Error: '{}' is not an XMSS key: {}", positional[1], e);
+                        process::exit(1);}}}},
+        _ => {
+            error!("Error: Usage: xmss keygen [--height <n>] <private_key_file> <public_key_file> | xmss sign <private_key_file> (-s|-f) [--out <file>] | xmss verify <public_key_file> <signature_file> (-s|-f) | xmss info <key_file>");
+            print_usage_cli();
+            process::exit(1);}}}
+
+fn read_cert_or_exit(path: &str) -> Certificate {
+    match Certificate::from_bytes(&read_file_or_exit(path, "certificate")) {
+        Ok(certificate) => certificate,
+        Err(e) => {
+            error!("Error: '{}' is not a certificate: {}", path, e);
+            process::exit(1);}}}
+
+fn read_public_key_or_exit(path: &str) -> XmssPublicKey {
+    match XmssPublicKey::from_bytes(&read_file_or_exit(path, "public key")) {
+        Ok(public_key) => public_key,
+        Err(e) => {
+            error!("Error: '{}' is not an XMSS public key: {}", path, e);
+            process::exit(1);}}}
+
+fn run_cert_mode(mut args: Vec<String>) {
+    let subject = take_flag_value(&mut args, "--subject");
+    let signing_key = take_flag_value(&mut args, "--signing-key");
+    let issuer_cert = take_flag_value(&mut args, "--issuer-cert");
+    let public_key = take_flag_value(&mut args, "--public-key");
+    let days: u64 = take_flag_number(&mut args, "--days").unwrap_or(365);
+    let not_before = take_flag_number(&mut args, "--not-before").unwrap_or_else(cert::unix_now);
+    let serial: Option<u64> = take_flag_number(&mut args, "--serial");
+    let path_len: Option<u8> = take_flag_number(&mut args, "--path-len");
+    let is_ca = args.iter().any(|a| a == "--ca");
+    args.retain(|a| a != "--ca");
+    let mut extensions = Vec::new();
+    while let Some(ext) = take_flag_value(&mut args, "--ext") {
+        match ext.split_once('=') {
+            Some((id, value)) if !id.is_empty() => extensions.push((id.to_string(), value.to_string())),
+            _ => {
+                error!("Error: --ext expects <id>=<text>, got '{}'.", ext);
+                process::exit(1);}}}
+    let out_path = take_flag_value(&mut args, "--out");
+    let trust_dir = take_flag_value(&mut args, "--trust-dir");
+    let mut chain_paths = Vec::new();
+    while let Some(path) = take_flag_value(&mut args, "--chain") {
+        chain_paths.push(path);}
+    let at = take_flag_number(&mut args, "--at").unwrap_or_else(cert::unix_now);
+    let positional: Vec<String> = args.iter().skip(1)
+        .filter(|a| !matches!(a.as_str(), "cert" | "--debug" | "--info"))
+        .cloned()
+        .collect();
+    let action = positional.first().map(String::as_str).unwrap_or("");
+    match (action, positional.len(), subject, signing_key, out_path, trust_dir) {
+        ("issue", 1, Some(subject), Some(signing_key), Some(out_path), None) => {
+            let subject_key = match (&issuer_cert, public_key) {
+                (_, Some(path)) => read_public_key_or_exit(&path),
+                (None, None) => match XmssPrivateKey::from_bytes(&read_file_or_exit(&signing_key, "private key")) {
+                    Ok(key) => key.public_key(),
+                    Err(e) => {
+                        error!("Error: '{}' is not an XMSS private key: {}", signing_key, e);
+                        process::exit(1);}},
+                (Some(_), None) => {
+                    error!("Error: cert issue with --issuer-cert requires --public-key <xmss_public_key> for the subject.");
+                    process::exit(1);}};
+            let mut tbs = TbsCertificate::new(&subject, subject_key, not_before, days.saturating_mul(86_400));
+            if let Some(path) = &issuer_cert {
+                tbs = tbs.issued_by(&read_cert_or_exit(path));}
+            if let Some(serial) = serial {
+                tbs = tbs.with_serial(serial);}
+            if is_ca {
+                tbs = tbs.with_ca(path_len);}
+            for (id, value) in &extensions {
+                tbs = tbs.with_extension(id, false, value.as_bytes());}
+            let (certificate, remaining) = match issue_with_state_file(tbs, std::path::Path::new(&signing_key)) {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Error: Could not issue certificate: {}", e);
+                    process::exit(1);}};
+            write_file_or_exit(&out_path, &certificate.to_bytes(), "certificate");
+            eprintln!("Issued certificate for '{}'; {} signatures remaining on the signing key.", certificate.tbs.subject, remaining);
+            println!("{}", hex::encode(certificate.key_id()));},
+        ("show", 2, None, None, None, None) => {
+            println!("{}", read_cert_or_exit(&positional[1]));},
+        ("verify", 2, None, None, None, Some(trust_dir)) => {
+            let leaf = read_cert_or_exit(&positional[1]);
+            let intermediates: Vec<Certificate> = chain_paths.iter().map(|path| read_cert_or_exit(path)).collect();
+            let store = match TrustStore::from_dir(std::path::Path::new(&trust_dir)) {
+                Ok(store) => store,
+                Err(e) => {
+                    error!("Error: Could not load trust store '{}': {}", trust_dir, e);
+                    process::exit(1);}};
+            match verify_chain(&leaf, &intermediates, &store, at) {
+                Ok(chain) => {
+                    let names: Vec<&str> = chain.iter().map(|c| c.tbs.subject.as_str()).collect();
+                    eprintln!("Chain: {}", names.join(" <- "));
+                    println!("OK");
+                    process::exit(0);},
+                Err(e) => {
+                    eprintln!("{}", e);
+                    println!("MISMATCH");
+                    process::exit(2);}}},
+        _ => {
+            error!("Error: Usage: cert issue --subject <name> --signing-key <key> [--issuer-cert <cert> --public-key <key>] --out <file> | cert show <cert_file> | cert verify <cert_file> --trust-dir <dir> [--chain <cert_file>...]");
+            print_usage_cli();
+            process::exit(1);}}}
+
+fn load_key_file_or_exit(path: &str, password: Option<&str>) -> KeyFile {
+    match KeyFile::from_bytes(&read_file_or_exit(path, "key file"), password.map(str::as_bytes)) {
+        Ok(key_file) => key_file,
+        Err(e) => {
+            error!("Error: Could not open key file '{}': {}", path, e);
+            process::exit(1);}}}
+
+fn run_key_mode(mut args: Vec<String>) {
+    let password = take_flag_value(&mut args, "--password");
+    let algorithm = take_flag_value(&mut args, "--alg");
+    let out_path = take_flag_value(&mut args, "--out");
+    let binary = args.iter().any(|a| a == "--binary");
+    args.retain(|a| a != "--binary");
+    let params = parse_kdf_params(&mut args);
+    let positional: Vec<String> = args.iter().skip(1)
+        .filter(|a| !matches!(a.as_str(), "key" | "--debug" | "--info"))
+        .cloned()
+        .collect();
+    let action = positional.first().map(String::as_str).unwrap_or("");
+    match (action, positional.len(), algorithm, out_path) {
+        ("inspect", 2, None, None) => {
+            let bytes = read_file_or_exit(&positional[1], "key file");
+            let info = match keyfile::inspect(&bytes) {
+                Ok(info) => info,
+                Err(e) => {
+                    error!("Error: '{}' is not a qosmic key file: {}", positional[1], e);
+                    process::exit(1);}};
+            println!("Algorithm:   {}", info.algorithm);
+            println!("Format:      {}", if info.format == KeyFormat::Armored { "armored" } else { "binary" });
+            match &info.protection {
+                keyfile::Protection::None => println!("Protection:  none"),
+                keyfile::Protection::Pbkdf2 { iterations, salt } =>
+                    println!("Protection:  pbkdf2-hmac-qosmic, {} iterations, {}-byte salt", iterations, salt.len()),}
+            println!("Payload:     {} bytes", info.payload_len);
+            println!("Checksum:    {}", if info.checksum_ok { "OK" } else { "MISMATCH" });
+            if !info.checksum_ok {
+                process::exit(2);}
+            let key_file = match (&info.protection, &password) {
+                (keyfile::Protection::Pbkdf2 { .. }, None) => return,
+                _ => match KeyFile::from_bytes(&bytes, password.as_deref().map(str::as_bytes)) {
+                    Ok(key_file) => key_file,
+                    Err(e) => {
+                        println!("Password:    {}", e);
+                        process::exit(2);}},};
+            println!("Fingerprint: {}", key_file.fingerprint());
+            match key_file.algorithm.describe_payload(&key_file.key) {
+                Ok(description) => println!("Key:         {}", description),
+                Err(e) => {
+                    println!("Key:         {}", e);
+                    process::exit(2);}}},
+        ("wrap", 2, Some(algorithm), Some(out_path)) => {
+            let algorithm = match KeyAlgorithm::from_name(&algorithm) {
+                Some(algorithm) => algorithm,
+                None => {
+                    error!("Error: Unknown key algorithm '{}'.", algorithm);
+                    process::exit(1);}};
+            let raw = read_file_or_exit(&positional[1], "raw key");
+            if let Err(e) = algorithm.describe_payload(&raw) {
+                error!("Error: '{}' is not a {} key: {}", positional[1], algorithm, e);
+                process::exit(1);}
+            if password.is_none() && algorithm.is_secret() {
+                eprintln!("Warning: storing a secret key without --password.");}
+            let format = if binary { KeyFormat::Binary } else { KeyFormat::Armored };
+            if let Err(e) = KeyFile::new(algorithm, &raw).save(std::path::Path::new(&out_path), format, password.as_deref().map(str::as_bytes), &params) {
+                error!("Error: Could not write key file '{}': {}", out_path, e);
+                process::exit(1);}},
+        ("unwrap", 2, None, Some(out_path)) => {
+            let key_file = load_key_file_or_exit(&positional[1], password.as_deref());
+            write_file_or_exit(&out_path, &key_file.key, "raw key");},
+        _ => {
+            error!("Error: Usage: key inspect <key_file> [--password <pw>] | key wrap --alg <algorithm> <raw_key_file> --out <key_file> [--password <pw>] [--binary] | key unwrap <key_file> --out <raw_key_file> [--password <pw>]");
+            print_usage_cli();
+            process::exit(1);}}}
+
+fn merkle_tree_or_exit(path: &str, chunk_size: usize) -> MerkleTree {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            error!("Failed to read file '{}': {}", path, e);
+            process::exit(1);}};
+    match MerkleTree::from_reader(BufReader::new(file), chunk_size) {
+        Ok(tree) => tree,
+        Err(e) => {
+            error!("Failed to read file '{}': {}", path, e);
+            process::exit(1);}}}
+
+fn run_merkle_mode(mut args: Vec<String>) {
+    let chunk_size: usize = take_flag_number(&mut args, "--chunk-size").unwrap_or(merkle::DEFAULT_CHUNK_SIZE);
+    if chunk_size == 0 {
+        error!("Error: --chunk-size must be positive.");
+        process::exit(1);}
+    let index: Option<usize> = take_flag_number(&mut args, "--index");
+    let root = take_hex_flag(&mut args, "--root");
+    let out_path = take_flag_value(&mut args, "--out");
+    let chunk_out = take_flag_value(&mut args, "--chunk-out");
+    let message = if args.iter().any(|a| a == "-s" || a == "-f") { Some(take_message(&mut args, "merkle")) } else { None };
+    let positional: Vec<String> = args.iter().skip(1)
+        .filter(|a| !matches!(a.as_str(), "merkle" | "--debug" | "--info"))
+        .cloned()
+        .collect();
+    let action = positional.first().map(String::as_str).unwrap_or("");
+    match (action, positional.len(), index, root, message) {
+        ("root", 2, None, None, None) => {
+            let tree = merkle_tree_or_exit(&positional[1], chunk_size);
+            eprintln!("{} chunks of {} bytes.", tree.len(), chunk_size);
+            println!("{}", hex::encode(tree.root()));},
+        ("prove", 2, Some(index), None, None) => {
+            let tree = merkle_tree_or_exit(&positional[1], chunk_size);
+            let proof = match tree.prove(index) {
+                Ok(proof) => proof,
+                Err(e) => {
+                    error!("Error: {}", e);
+                    process::exit(1);}};
+            if let Some(path) = chunk_out {
+                let mut chunk = vec![0u8; chunk_size];
+                let read = File::open(&positional[1]).and_then(|mut file| {
+                    io::Seek::seek(&mut file, io::SeekFrom::Start((index * chunk_size) as u64))?;
+                    let mut filled = 0;
+                    while filled < chunk_size {
+                        match file.read(&mut chunk[filled..])? {
+                            0 => break,
+                            n => filled += n,}}
+                    Ok(filled)});
+                match read {
+                    Ok(filled) => write_file_or_exit(&path, &chunk[..filled], "chunk"),
+                    Err(e) => {
+                        error!("Failed to read file '{}': {}", positional[1], e);
+                        process::exit(1);}}}
+            eprintln!("Root: {}", hex::encode(tree.root()));
+            match out_path {
+                Some(path) => write_file_or_exit(&path, &proof.to_bytes(), "proof"),
+                None => println!("{}", hex::encode(proof.to_bytes())),}},
+        ("verify", 2, None, Some(root), Some(chunk)) => {
+            let root: merkle::MerkleHash = match root.try_into() {
+                Ok(root) => root,
+                Err(_) => {
+                    error!("Error: --root must be a {}-byte hex hash.", merkle::HASH_LEN);
+                    process::exit(1);}};
+            let proof_bytes = read_file_or_exit(&positional[1], "proof");
+            let proof = InclusionProof::from_bytes(&proof_bytes)
+                .or_else(|e| hex::decode(String::from_utf8_lossy(&proof_bytes).trim()).map_err(|_| e).and_then(|b| InclusionProof::from_bytes(&b)));
+            match proof {
+                Ok(proof) if proof.verify(&chunk, &root) => {
+                    eprintln!("Chunk {} of {}.", proof.leaf_index, proof.tree_size);
+                    println!("OK");
+                    process::exit(0);},
+                Ok(_) => {
+                    println!("MISMATCH");
+                    process::exit(2);},
+                Err(e) => {
+                    error!("Error: Could not read proof: {}", e);
+                    process::exit(1);}}},
+        _ => {
+            error!("Error: Usage: merkle root <file> [--chunk-size <n>] | merkle prove <file> --index <i> [--out <proof_file>] [--chunk-out <file>] | merkle verify <proof_file> --root <hex> (-s|-f)");
+            print_usage_cli();
+            process::exit(1);}}}
+
+fn open_log_or_exit(dir: &str) -> TransparencyLog {
+    match TransparencyLog::open(std::path::Path::new(dir)) {
+        Ok(log) => log,
+        Err(e) => {
+            error!("Error: Could not open log '{}': {}", dir, e);
+            process::exit(1);}}}
+
+fn read_sth_or_exit(path: &str) -> SignedTreeHead {
+    let bytes = read_file_or_exit(path, "signed tree head");
+    match SignedTreeHead::from_bytes(&bytes)
+        .or_else(|e| hex::decode(String::from_utf8_lossy(&bytes).trim()).map_err(|_| e).and_then(|b| SignedTreeHead::from_bytes(&b))) {
+        Ok(sth) => sth,
+        Err(e) => {
+            error!("Error: '{}' is not a signed tree head: {}", path, e);
+            process::exit(1);}}}
+
+/// Reads a raw or hex-encoded proof file.
+fn read_proof_bytes(path: &str) -> Vec<u8> {
+    let bytes = read_file_or_exit(path, "proof");
+    match hex::decode(String::from_utf8_lossy(&bytes).trim()) {
+        Ok(decoded) if !decoded.is_empty() => decoded,
+        _ => bytes,}}
+
+fn print_audit_result(result: Result<(), transparency::LogError>) -> ! {
+    match result {
+        Ok(()) => {
+            println!("OK");
+            process::exit(0);},
+        Err(e) => {
+            eprintln!("{}", e);
+            println!("MISMATCH");
+            process::exit(2);}}}
+
+fn run_log_mode(mut args: Vec<String>) {
+    let hmac_key = take_flag_value(&mut args, "--key");
+    let xmss_key = take_flag_value(&mut args, "--xmss-key");
+    let xmss_pub = take_flag_value(&mut args, "--xmss-pub").map(|path| read_public_key_or_exit(&path));
+    let index: Option<u64> = take_flag_number(&mut args, "--index");
+    let from: Option<u64> = take_flag_number(&mut args, "--from");
+    let size: Option<u64> = take_flag_number(&mut args, "--size");
+    let out_path = take_flag_value(&mut args, "--out");
+    let proof_path = take_flag_value(&mut args, "--proof");
+    let mut sth_paths = Vec::new();
+    while let Some(path) = take_flag_value(&mut args, "--sth") {
+        sth_paths.push(path);}
+    let message = if args.iter().any(|a| a == "-s" || a == "-f") { Some(take_message(&mut args, "log")) } else { None };
+    let verifier = match (&hmac_key, &xmss_pub) {
+        (Some(key), None) => Some(HeadVerifier::Hmac(key.as_bytes())),
+        (None, Some(public_key)) => Some(HeadVerifier::Xmss(public_key)),
+        _ => None,};
+    let positional: Vec<String> = args.iter().skip(1)
+        .filter(|a| !matches!(a.as_str(), "log" | "--debug" | "--info"))
+        .cloned()
+        .collect();
+    let action = positional.first().map(String::as_str).unwrap_or("");
+    match (action, positional.len(), message) {
+        ("append", 2, Some(entry)) => {
+            let mut log = open_log_or_exit(&positional[1]);
+            match log.append(&entry) {
+                Ok(index) => {
+                    eprintln!("Log now holds {} entries; root {}", log.size(), hex::encode(log.root()));
+                    println!("{}", index);},
+                Err(e) => {
+                    error!("Error: Could not append to log: {}", e);
+                    process::exit(1);}}},
+        ("head", 2, None) => {
+            let log = open_log_or_exit(&positional[1]);
+            let head = log.head(cert::unix_now());
+            let signed = match (hmac_key, xmss_key) {
+                (Some(key), None) => head.sign_hmac(key.as_bytes()),
+                (None, Some(path)) => {
+                    let result = XmssPrivateKey::from_bytes(&read_file_or_exit(&path, "private key"))
+                        .map_err(transparency::LogError::from)
+                        .and_then(|mut key| {
+                            let signed = head.sign_xmss(&mut key)?;
+                            xmss::write_state_atomically(std::path::Path::new(&path), &key.to_bytes())?;
+                            Ok(signed)});
+                    match result {
+                        Ok(signed) => signed,
+                        Err(e) => {
+                            error!("Error: Could not sign tree head: {}", e);
+                            process::exit(1);}}},
+                _ => {
+                    error!("Error: log head requires exactly one of --key <key> or --xmss-key <file>.");
+                    process::exit(1);}};
+            eprintln!("Tree size {}, root {}", signed.head.tree_size, hex::encode(signed.head.root));
+            match out_path {
+                Some(path) => write_file_or_exit(&path, &signed.to_bytes(), "signed tree head"),
+                None => println!("{}", hex::encode(signed.to_bytes())),}},
+        ("prove", 2, None) => {
+            let log = open_log_or_exit(&positional[1]);
+            let size = size.unwrap_or(log.size());
+            let proof = match (index, from) {
+                (Some(index), None) => log.prove_inclusion(index, size).map(|proof| proof.to_bytes()),
+                (None, Some(from)) => log.prove_consistency(from, size).map(|proof| proof.to_bytes()),
+                _ => {
+                    error!("Error: log prove requires exactly one of --index <i> or --from <old_size>.");
+                    process::exit(1);}};
+            match (proof, out_path) {
+                (Ok(proof), Some(path)) => write_file_or_exit(&path, &proof, "proof"),
+                (Ok(proof), None) => println!("{}", hex::encode(proof)),
+                (Err(e), _) => {
+                    error!("Error: {}", e);
+                    process::exit(1);}}},
+        ("audit", 2, None) => {
+            let log = open_log_or_exit(&positional[1]);
+            let heads: Vec<SignedTreeHead> = sth_paths.iter().map(|path| read_sth_or_exit(path)).collect();
+            if !heads.is_empty() && verifier.is_none() {
+                error!("Error: checking --sth requires exactly one of --key <key> or --xmss-pub <file>.");
+                process::exit(1);}
+            let result = log.audit_entries().and_then(|()| {
+                heads.iter().try_for_each(|head| log.check_head(head, verifier.expect("checked above")))});
+            eprintln!("{} entries, root {}", log.size(), hex::encode(log.root()));
+            print_audit_result(result);},
+        ("audit", 1, message) => {
+            let (verifier, proof_path) = match (verifier, proof_path) {
+                (Some(verifier), Some(proof_path)) => (verifier, proof_path),
+                _ => {
+                    error!("Error: log audit without a log directory requires --proof <file> and one of --key <key> or --xmss-pub <file>.");
+                    process::exit(1);}};
+            let heads: Vec<SignedTreeHead> = sth_paths.iter().map(|path| read_sth_or_exit(path)).collect();
+            let proof_bytes = read_proof_bytes(&proof_path);
+            let result = match (heads.as_slice(), message) {
+                ([head], Some(entry)) => match InclusionProof::from_bytes(&proof_bytes) {
+                    Ok(proof) => transparency::verify_inclusion(head, verifier, &entry, &proof),
+                    Err(e) => Err(e.into()),},
+                ([old, new], None) => match ConsistencyProof::from_bytes(&proof_bytes) {
+                    Ok(proof) => transparency::verify_consistency(old, new, verifier, &proof),
+                    Err(e) => Err(e.into()),},
+                _ => {
+                    error!("Error: give one --sth with an entry (-s|-f), or two --sth (old, new) for a consistency proof.");
+                    process::exit(1);}};
+            print_audit_result(result);},
+        _ => {
+            error!("Error: Usage: log append <log_dir> (-s|-f) | log head <log_dir> (--key|--xmss-key) | log prove <log_dir> (--index <i> | --from <n>) [--size <n>] | log audit ...");
+            print_usage_cli();
+            process::exit(1);}}}
+
+/// The file key from `--key-file` (an `aead` key file, opened with `--key-password`), or `None` when a
+/// password is used instead.
+fn take_aead_key_file(args: &mut Vec<String>) -> Option<[u8; aead::KEY_LEN]> {
+    let path = take_flag_value(args, "--key-file")?;
+    let key_password = take_flag_value(args, "--key-password");
+    let key_file = load_key_file_or_exit(&path, key_password.as_deref());
+    if key_file.algorithm != KeyAlgorithm::Aead || key_file.key.len() != aead::KEY_LEN {
+        error!("Error: '{}' holds a {} key, not an aead key.", path, key_file.algorithm);
+        process::exit(1);}
+    let mut key = [0u8; aead::KEY_LEN];
+    key.copy_from_slice(&key_file.key);
+    Some(key)}
+
+fn run_aead_mode(mut args: Vec<String>, mode: &str) {
+    let password = take_flag_value(&mut args, "--password");
+    let key = take_aead_key_file(&mut args);
+    let out_path = take_flag_value(&mut args, "--out");
+    let aad = take_flag_value(&mut args, "--aad").unwrap_or_default().into_bytes();
+    let chunk_size = take_flag_number(&mut args, "--chunk-size").unwrap_or(aead::DEFAULT_CHUNK_SIZE);
+    let params = parse_kdf_params(&mut args);
+    let positional: Vec<String> = args.iter().skip(1)
+        .filter(|a| !matches!(a.as_str(), "encrypt" | "decrypt" | "--debug" | "--info"))
+        .cloned()
+        .collect();
+    let (in_path, out_path) = match (positional.as_slice(), out_path, password.is_some() != key.is_some()) {
+        ([in_path], Some(out_path), true) => (in_path.clone(), out_path),
+        _ => {
+            error!("Error: Usage: {} <in_file> --out <out_file> (--password <pw> | --key-file <key_file> [--key-password <pw>]) [--aad <text>]", mode);
+            print_usage_cli();
+            process::exit(1);}};
+    let mut reader = match File::open(&in_path) {
+        Ok(file) => BufReader::new(file),
+        Err(e) => {
+            error!("Failed to read file '{}': {}", in_path, e);
+            process::exit(1);}};
+    let mut tmp_name = std::ffi::OsString::from(&out_path);
+    tmp_name.push(".tmp");
+    let mut writer = match File::create(&tmp_name) {
+        Ok(file) => BufWriter::new(file),
+        Err(e) => {
+            error!("Failed to write file '{}': {}", out_path, e);
+            process::exit(1);}};
+    let result = if mode == "encrypt" {
+        let key_source = match &password {
+            Some(_) => {
+                let salt = params.salt.clone().unwrap_or_else(|| {
+                    let mut salt = vec![0u8; params.salt_len];
+                    rand::RngCore::fill_bytes(&mut rand::rng(), &mut salt);
+                    salt});
+                KeySource::Password { iterations: params.iterations, salt }},
+            None => KeySource::Raw,};
+        StreamHeader::new(key_source, chunk_size).and_then(|header| {
+            let file_key = match &password {
+                Some(password) => header.password_key(password.as_bytes())?,
+                None => key.expect("a key file when no password is given"),};
+            info!("Encrypting '{}' in {}-byte chunks...", in_path, header.chunk_size);
+            aead::encrypt_stream(&file_key, &header, &aad, &mut reader, &mut writer)})
+    } else {
+        StreamHeader::read_from(&mut reader).and_then(|header| {
+            let file_key = match (&password, &header.key_source) {
+                (Some(password), _) => header.password_key(password.as_bytes())?,
+                (None, KeySource::Password { .. }) => return Err(AeadError::InvalidParameter("file is encrypted with a password, not a key".to_string())),
+                (None, KeySource::Raw) => key.expect("a key file when no password is given"),};
+            info!("Decrypting '{}'...", in_path);
+            aead::decrypt_stream(&file_key, &header, &aad, &mut reader, &mut writer)})};
+    let result = result.and_then(|total| {
+        let file = writer.into_inner().map_err(|e| AeadError::Io(e.error().to_string()))?;
+        file.sync_all()?;
+        fs::rename(&tmp_name, &out_path)?;
+        Ok(total)});
+    match result {
+        Ok(total) => debug!("{}ed {} bytes into '{}'.", mode, total, out_path),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_name);
+            error!("Error: Could not {} '{}': {}", mode, in_path, e);
+            process::exit(1);}}}
+
+fn run_hkdf_mode(mut args: Vec<String>) {
+    let ikm = match take_hex_flag(&mut args, "--ikm") {
+        Some(ikm) => ikm,
+        None => {
+            error!("Error: hkdf requires --ikm <hex>.");
+            print_usage_cli();
+            process::exit(1);}};
+    let salt = take_hex_flag(&mut args, "--salt").unwrap_or_default();
+    let info_bytes = take_flag_value(&mut args, "--context").map(String::into_bytes).unwrap_or_default();
+    let output_len = take_flag_number(&mut args, "--length").unwrap_or(hkdf::HASH_LEN);
+    debug!("HKDF: ikm_len={}, salt_len={}, info_len={}, output_len={}", ikm.len(), salt.len(), info_bytes.len(), output_len);
+    match hkdf::hkdf(&salt, &ikm, &info_bytes, output_len) {
+        Ok(okm) => println!("{}", hex::encode(okm)),
+        Err(e) => {
+            error!("Error: HKDF failed: {}", e);
+            process::exit(1);}}
+    debug!("HKDF output printed to stdout.");}
+
+fn run_rehash_check_mode(mut args: Vec<String>) {
+    let allow_legacy = args.iter().any(|a| a == "--allow-legacy");
+    args.retain(|a| a != "--allow-legacy");
+    let kdf = take_flag_value(&mut args, "--kdf").unwrap_or_else(|| "pbkdf2".to_string());
+    let mut policy = match kdf.as_str() {
+        "pbkdf2" => RehashPolicy::from_kdf_params(&parse_kdf_params(&mut args)),
+        "mem" => RehashPolicy::from_memory_hard_params(&parse_memory_hard_params(&mut args)),
+        other => {
+            error!("Error: Unknown KDF '{}'. Use --kdf pbkdf2 or --kdf mem.", other);
+            print_usage_cli();
+            process::exit(1);}};
+    policy.allow_legacy = allow_legacy;
+    policy.pepper_id = take_peppers(&mut args).first().map(|p| p.id().to_string());
+    debug!("Rehash policy: {:?}", policy);
+    let positional: Vec<&String> = args.iter().skip(1)
+        .filter(|a| !matches!(a.as_str(), "rehash-check" | "--debug" | "--info"))
+        .collect();
+    if positional.len() != 1 {
+        error!("Error: rehash-check expects exactly one file of stored hashes.");
+        print_usage_cli();
+        process::exit(1);}
+    let file = match File::open(positional[0]) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("Failed to open hash file '{}': {}", positional[0], e);
+            process::exit(1);}};
+    let mut stdout_buf = BufWriter::new(io::stdout());
+    let (mut total, mut outdated) = (0usize, 0usize);
+    for (idx, line) in BufReader::new(file).lines().enumerate() {
+        let line_no = idx + 1;
+        let stored = match line {
+            Ok(l) => l.trim().to_string(),
+            Err(e) => {
+                error!("Error reading line {} from hash file: {}", line_no, e);
+                continue;}};
+        if stored.is_empty() {
+            continue;}
+        total += 1;
+        let status = match rehash_reasons(&stored, &policy) {
+            Ok(reasons) if reasons.is_empty() => "OK".to_string(),
+            Ok(reasons) => {
+                outdated += 1;
+                let listed: Vec<String> = reasons.iter().map(|r| r.to_string()).collect();
+                format!("REHASH ({})", listed.join(", "))},
+            Err(e) => {
+                outdated += 1;
+                format!("INVALID ({})", e)},};
+        if writeln!(stdout_buf, "{}\t{}", line_no, status).is_err() {
+            error!("Error writing rehash report to stdout.");
+            break;}}
+    if stdout_buf.flush().is_err() {
+        error!("Error flushing stdout at the end of rehash check.");}
+    eprintln!("{} of {} stored hashes fall below policy.", outdated, total);}
+
+fn run_password_mode(password: &[u8], args: &mut Vec<String>, pepper: Option<&Pepper>) {
+    let kdf = take_flag_value(args, "--kdf").unwrap_or_else(|| "pbkdf2".to_string());
+    let hash_result = match kdf.as_str() {
+        "pbkdf2" => {
+            let params = parse_kdf_params(args);
+            info!("Calculating PBKDF2 password hash with {} iterations...", params.iterations);
+            hash_password_with_pepper(password, &params, pepper)},
+        "mem" => {
+            let params = parse_memory_hard_params(args);
+            info!("Calculating memory-hard password hash with m={} KiB, t={}, p={}...", params.memory_kib, params.time_cost, params.parallelism);
+            hash_password_memory_hard_with_pepper(password, &params, pepper)},
+        other => {
+            error!("Error: Unknown KDF '{}'. Use --kdf pbkdf2 or --kdf mem.", other);
+            print_usage_cli();
+            process::exit(1);}};
+    let hash_result = match hash_result {
+        Ok(phc) => phc,
+        Err(e) => {
+            error!("Error: Password hashing failed: {}", e);
+            process::exit(1);}};
     info!("Password hash calculation complete.");
     println!("{}", hash_result);
-    debug!("Password hash output (salt$hash) printed to stdout.");}
+    debug!("Password hash output (PHC string) printed to stdout.");}
+
+fn run_verify_password_mode(password: &[u8], stored: &str, peppers: &[Pepper]) -> ! {
+    info!("Verifying password against stored hash...");
+    match verify_password_with_peppers(password, stored, peppers) {
+        Ok(true) => {
+            println!("OK");
+            process::exit(0);},
+        Ok(false) => {
+            println!("MISMATCH");
+            process::exit(2);},
+        Err(e) => {
+            error!("Error: Could not verify password: {}", e);
+            process::exit(1);}}}
 
-fn run_cli_mode(args: Vec<String>, pre_set_key: Option<Vec<u8>>, output_format: Option<String>) {
+fn run_cli_mode(args: Vec<String>, pre_set_key: Option<Vec<u8>>, output_format: Option<String>, lattice_mode: bool) {
     debug!("run_cli_mode: Arguments (filtered): {:?}", args);
     let filtered_args: Vec<String> = args.into_iter()
         .filter(|arg| !arg.starts_with("--") && !arg.starts_with("-o"))
@@ -170,9 +1174,13 @@ fn run_cli_mode(args: Vec<String>, pre_set_key: Option<Vec<u8>>, output_format:
         info!("Using string input: '{}'", input_arg);
         debug!("String input as bytes: {:?}", input_arg.as_bytes());
         (input_arg.as_bytes().to_vec(), 's')};
+    let b58c_version = if pre_set_key.is_some() { encode::B58C_VERSION_HMAC } else { encode::B58C_VERSION_HASH };
     let hash_result = if let Some(key_bytes) = pre_set_key {
         info!("Calculating HMAC-qosmic hash with provided key...");
         hmac_qosmic(&key_bytes, &input_data)
+    } else if lattice_mode {
+        info!("Calculating lattice-hardened qosmic hash...");
+        hex::encode(qosmic_lattice_digest(&input_data))
     } else {
         info!("Calculating qosmic (unkeyed) hash...");
         let s_box = get_sbox();
@@ -187,6 +1195,9 @@ fn run_cli_mode(args: Vec<String>, pre_set_key: Option<Vec<u8>>, output_format:
             "b58" => {
                 debug!("Encoding hash to Base58.");
                 encode::to_base58(&hash_result)},
+            "b58c" => {
+                debug!("Encoding hash to Base58Check.");
+                encode::to_base58check(&hash_result, b58c_version)},
             "b64" => {
                 debug!("Encoding hash to Base64.");
                 encode::to_base64(&hash_result)},
@@ -209,6 +1220,7 @@ fn run_interactive_mode(key: Option<Vec<u8>>, initial_output_format: Option<Stri
     let mut stdout = io::stdout();
     let mut buffer = String::new();
     let output_format = initial_output_format;
+    let b58c_version = if key.is_some() { encode::B58C_VERSION_HMAC } else { encode::B58C_VERSION_HASH };
     loop {
         debug!("Waiting for input in interactive mode...");
         if stdout.flush().is_err() {
@@ -245,6 +1257,9 @@ fn run_interactive_mode(key: Option<Vec<u8>>, initial_output_format: Option<Stri
                         "b58" => {
                             debug!("Encoding hash to Base58.");
                             encode::to_base58(&hash_result)},
+                        "b58c" => {
+                            debug!("Encoding hash to Base58Check.");
+                            encode::to_base58check(&hash_result, b58c_version)},
                         "b64" => {
                             debug!("Encoding hash to Base64.");
                             encode::to_base64(&hash_result)},
@@ -279,6 +1294,7 @@ fn run_batch_mode(file_path: String, key: Option<Vec<u8>>, output_format: Option
     let reader = BufReader::new(file);
     let mut stdout_buf = BufWriter::new(io::stdout());
     let mut line_count = 0;
+    let b58c_version = if key.is_some() { encode::B58C_VERSION_HMAC } else { encode::B58C_VERSION_HASH };
     for line in reader.lines() {
         line_count += 1;
         let input = match line {
@@ -308,6 +1324,9 @@ fn run_batch_mode(file_path: String, key: Option<Vec<u8>>, output_format: Option
                 "b58" => {
                     debug!("Encoding hash for line {} to Base58.", line_count);
                     encode::to_base58(&hash_result)},
+                "b58c" => {
+                    debug!("Encoding hash for line {} to Base58Check.", line_count);
+                    encode::to_base58check(&hash_result, b58c_version)},
                 "b64" => {
                     debug!("Encoding hash for line {} to Base64.", line_count);
                     encode::to_base64(&hash_result)},
@@ -0,0 +1,268 @@
+// src/memhard.rs
+use crate::components::h_func_internal;
+use crate::constants::*;
+use crate::core::{qosmic_digest, KdfError, MAX_KEY_LEN, MIN_SALT_SIZE, SALT_SIZE};
+use crate::pepper::{self, Pepper};
+use crate::phc::{PasswordHashError, PhcString};
+use crate::utils;
+use log::{debug, info};
+use std::time::Instant;
+
+pub const MEMHARD_ALGORITHM: &str = "qosmic-mem";
+pub const MEMHARD_VERSION: u32 = 1;
+pub const MEMORY_KIB: u32 = 4096;
+pub const TIME_COST: u32 = 3;
+pub const PARALLELISM: u32 = 1;
+pub const MAX_PARALLELISM: u32 = 64;
+pub const MAX_MEMORY_KIB: u32 = 4 * 1024 * 1024;
+/// Largest memory cost `verify_password` will spend on a stored hash (1 GiB), so a crafted hash
+/// cannot make verification allocate up to `MAX_MEMORY_KIB`. New hashes are held to the same
+/// limit so that every hash this crate writes can be verified.
+pub const MAX_VERIFY_MEMORY_KIB: u32 = 1024 * 1024;
+/// Largest number of passes `verify_password` will run, since work grows with `m * t`. Parallelism
+/// only splits the same memory into lanes, so it needs no limit beyond `MAX_PARALLELISM`.
+pub const MAX_VERIFY_TIME_COST: u32 = 16;
+const BLOCK_WORDS: usize = 128;
+const BLOCK_BYTES: usize = BLOCK_WORDS * 8;
+const ROW_WORDS: usize = 16;
+const MIN_LANE_BLOCKS: u32 = 8;
+
+type Block = [u64; BLOCK_WORDS];
+
+/// Parameters for the memory-hard KDF. Memory is counted in 1 KiB blocks and split evenly
+/// between `parallelism` lanes, each of which is filled independently (scrypt-style `p`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryHardParams {
+    pub memory_kib: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+    pub output_len: usize,
+    pub salt_len: usize,
+    pub salt: Option<Vec<u8>>,}
+
+impl Default for MemoryHardParams {
+    fn default() -> Self {
+        MemoryHardParams {
+            memory_kib: MEMORY_KIB,
+            time_cost: TIME_COST,
+            parallelism: PARALLELISM,
+            output_len: 64,
+            salt_len: SALT_SIZE,
+            salt: None,}}}
+
+impl MemoryHardParams {
+    /// Rejects memory costs above `MAX_VERIFY_MEMORY_KIB` and time costs above `MAX_VERIFY_TIME_COST`
+    /// before anything is allocated.
+    pub fn check_verify_limit(&self) -> Result<(), KdfError> {
+        if self.memory_kib > MAX_VERIFY_MEMORY_KIB {
+            return Err(KdfError::CostLimitExceeded { param: "m", value: self.memory_kib as u64, limit: MAX_VERIFY_MEMORY_KIB as u64 });}
+        if self.time_cost > MAX_VERIFY_TIME_COST {
+            return Err(KdfError::CostLimitExceeded { param: "t", value: self.time_cost as u64, limit: MAX_VERIFY_TIME_COST as u64 });}
+        Ok(())}
+
+    pub fn validate(&self) -> Result<(), PasswordHashError> {
+        if self.parallelism == 0 || self.parallelism > MAX_PARALLELISM {
+            return Err(PasswordHashError::InvalidParameter(format!("p={}", self.parallelism)));}
+        if self.memory_kib < MIN_LANE_BLOCKS * self.parallelism || self.memory_kib > MAX_MEMORY_KIB {
+            return Err(PasswordHashError::InvalidParameter(format!("m={}", self.memory_kib)));}
+        if self.time_cost == 0 {
+            return Err(PasswordHashError::InvalidParameter(format!("t={}", self.time_cost)));}
+        if self.output_len == 0 || self.output_len > MAX_KEY_LEN {
+            return Err(PasswordHashError::InvalidParameter(format!("l={}", self.output_len)));}
+        match &self.salt {
+            Some(salt) if salt.is_empty() => Err(PasswordHashError::InvalidParameter("salt is empty".to_string())),
+            None if self.salt_len < MIN_SALT_SIZE => Err(PasswordHashError::InvalidParameter(format!("salt_len={}", self.salt_len))),
+            _ => Ok(()),}}
+
+    pub fn salt_or_generate(&self) -> Vec<u8> {
+        match &self.salt {
+            Some(salt) => salt.clone(),
+            None => utils::generate_salt(self.salt_len),}}
+
+    pub(crate) fn phc_params(&self) -> Vec<(String, String)> {
+        vec![
+            ("m".to_string(), self.memory_kib.to_string()),
+            ("t".to_string(), self.time_cost.to_string()),
+            ("p".to_string(), self.parallelism.to_string()),
+            ("l".to_string(), self.output_len.to_string()),]}
+
+    pub fn from_phc(phc: &PhcString) -> Result<Self, PasswordHashError> {
+        Ok(MemoryHardParams {
+            memory_kib: phc.param_u32("m")?,
+            time_cost: phc.param_u32("t")?,
+            parallelism: phc.param_u32("p")?,
+            output_len: phc.param_u32("l")? as usize,
+            salt_len: phc.salt.len(),
+            salt: Some(phc.salt.clone()),})}}
+
+/// Counter-mode expansion of `seed` to `len` bytes: `qosmic(seed || counter_be32)` for counter = 0, 1, ...
+fn expand(seed: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len + 64);
+    let mut counter: u32 = 0;
+    while out.len() < len {
+        let mut input = seed.to_vec();
+        input.extend_from_slice(&counter.to_be_bytes());
+        out.extend_from_slice(&qosmic_digest(&input));
+        counter += 1;}
+    out.truncate(len);
+    out}
+
+fn block_from_bytes(bytes: &[u8]) -> Block {
+    let mut block = [0u64; BLOCK_WORDS];
+    for (word, chunk) in block.iter_mut().zip(bytes.chunks_exact(8)) {
+        *word = u64::from_be_bytes(chunk.try_into().unwrap());}
+    block}
+
+/// Mixing function `G(X, Y)`. The XOR of both inputs is chained forward, each 16-word row is
+/// folded into four lanes and run through `h_func_internal`, then chained backward so every
+/// output word depends on every input word. The result is fed forward with `X ^ Y`.
+fn compress(x: &Block, y: &Block, p_array: &[u64; 5]) -> Block {
+    let mut r = [0u64; BLOCK_WORDS];
+    for k in 0..BLOCK_WORDS {
+        r[k] = x[k] ^ y[k];}
+    let mut z = r;
+    for k in 1..BLOCK_WORDS {
+        z[k] = z[k].wrapping_add(z[k - 1].rotate_left(ARX_BITS[k % 8])) ^ RATIO;}
+    let mut internal_seed = ((z[BLOCK_WORDS - 1] as u128) << 64 | z[0] as u128) & MASK_128;
+    for (row_idx, row) in z.chunks_exact_mut(ROW_WORDS).enumerate() {
+        let mut lanes = [0u64; 4];
+        for (j, &word) in row.iter().enumerate() {
+            lanes[j % 4] ^= word.rotate_left((j / 4) as u32 * 16);}
+        let main_state: [u64; 8] = row[..8].try_into().unwrap();
+        let (a, b, c, d) = h_func_internal(
+            lanes[0],
+            lanes[1],
+            lanes[2],
+            lanes[3],
+            row[ROW_WORDS - 1],
+            &mut internal_seed,
+            row_idx as u64,
+            p_array,
+            &main_state,);
+        let mixed = [a, b, c, d];
+        for (j, word) in row.iter_mut().enumerate() {
+            *word = word.wrapping_add(mixed[j % 4]).rotate_left(ARX_BITS[j % 8]) ^ mixed[(j + 1) % 4];}}
+    for k in (0..BLOCK_WORDS - 1).rev() {
+        z[k] ^= z[k + 1].wrapping_mul(MAGIC).rotate_right(ARX_BITS[k % 8]);}
+    for k in 0..BLOCK_WORDS {
+        z[k] ^= r[k];}
+    z}
+
+fn fill_lane(h0: &[u8], lane: u32, lane_blocks: usize, time_cost: u32, p_array: &[u64; 5]) -> Block {
+    let mut memory: Vec<Block> = Vec::with_capacity(lane_blocks);
+    for i in 0..2u32 {
+        let mut seed = h0.to_vec();
+        seed.extend_from_slice(&i.to_be_bytes());
+        seed.extend_from_slice(&lane.to_be_bytes());
+        memory.push(block_from_bytes(&expand(&seed, BLOCK_BYTES)));}
+    for i in 2..lane_blocks {
+        let ref_idx = (memory[i - 1][0] % i as u64) as usize;
+        let next = compress(&memory[i - 1], &memory[ref_idx], p_array);
+        memory.push(next);}
+    for pass in 1..time_cost {
+        debug!("memory-hard lane {}: pass {}/{}", lane, pass + 1, time_cost);
+        for i in 0..lane_blocks {
+            let prev_idx = (i + lane_blocks - 1) % lane_blocks;
+            let ref_idx = (memory[prev_idx][0] % lane_blocks as u64) as usize;
+            let next = compress(&memory[prev_idx], &memory[ref_idx], p_array);
+            for (word, new_word) in memory[i].iter_mut().zip(next.iter()) {
+                *word ^= new_word;}}}
+    memory[lane_blocks - 1]}
+
+/// Memory-hard key derivation over `params.memory_kib` KiB. Block references are data-dependent
+/// (the first word of the previous block picks the reference), and lanes run on separate threads.
+pub fn memory_hard_kdf(password: &[u8], salt: &[u8], params: &MemoryHardParams) -> Result<Vec<u8>, PasswordHashError> {
+    params.validate()?;
+    let start = Instant::now();
+    let mut h0_input = Vec::with_capacity(password.len() + salt.len() + 24);
+    h0_input.extend_from_slice(&(password.len() as u32).to_be_bytes());
+    h0_input.extend_from_slice(password);
+    h0_input.extend_from_slice(&(salt.len() as u32).to_be_bytes());
+    h0_input.extend_from_slice(salt);
+    h0_input.extend_from_slice(&params.memory_kib.to_be_bytes());
+    h0_input.extend_from_slice(&params.time_cost.to_be_bytes());
+    h0_input.extend_from_slice(&params.parallelism.to_be_bytes());
+    h0_input.extend_from_slice(&(params.output_len as u32).to_be_bytes());
+    let h0 = qosmic_digest(&h0_input);
+    let p_array: [u64; 5] = {
+        let mut arr = [0u64; 5];
+        for (i, chunk) in h0.chunks_exact(8).take(5).enumerate() {
+            arr[i] = u64::from_be_bytes(chunk.try_into().unwrap()).wrapping_add(COEFFS[i]) | (1 << 63) | 1;}
+        arr};
+    let lane_blocks = (params.memory_kib / params.parallelism) as usize;
+    let final_blocks: Vec<Block> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..params.parallelism)
+            .map(|lane| {
+                let h0 = &h0;
+                let p_array = &p_array;
+                scope.spawn(move || fill_lane(h0, lane, lane_blocks, params.time_cost, p_array))})
+            .collect();
+        handles.into_iter().map(|h| h.join().expect("Memory-hard lane thread panicked")).collect()});
+    let mut combined = [0u64; BLOCK_WORDS];
+    for block in &final_blocks {
+        for (word, lane_word) in combined.iter_mut().zip(block.iter()) {
+            *word ^= lane_word;}}
+    let combined_bytes: Vec<u8> = combined.iter().flat_map(|w| w.to_be_bytes()).collect();
+    let output = expand(&combined_bytes, params.output_len);
+    info!("Memory-hard KDF (m={} KiB, t={}, p={}) took: {:?}", params.memory_kib, params.time_cost, params.parallelism, start.elapsed());
+    Ok(output)}
+
+pub fn hash_password_memory_hard(password: &[u8], params: &MemoryHardParams) -> Result<String, PasswordHashError> {
+    hash_password_memory_hard_with_pepper(password, params, None)}
+
+pub fn hash_password_memory_hard_with_pepper(password: &[u8], params: &MemoryHardParams, pepper: Option<&Pepper>) -> Result<String, PasswordHashError> {
+    params.validate()?;
+    params.check_verify_limit()?;
+    let (kdf_password, pepper_param) = pepper::pepper_password(password, pepper);
+    let salt = params.salt_or_generate();
+    let derived_key = memory_hard_kdf(&kdf_password, &salt, params)?;
+    let mut phc_params = params.phc_params();
+    phc_params.extend(pepper_param);
+    Ok(PhcString::new(MEMHARD_ALGORITHM, MEMHARD_VERSION, phc_params, salt, derived_key).to_string())}
+
+pub(crate) fn verify_phc(password: &[u8], phc: &PhcString) -> Result<bool, PasswordHashError> {
+    if let Some(v) = phc.version && v != MEMHARD_VERSION {
+        return Err(PasswordHashError::UnsupportedVersion(v));}
+    phc.check_known_params(&["m", "t", "p", "l", pepper::PEPPER_PARAM])?;
+    let params = MemoryHardParams::from_phc(phc)?;
+    params.check_verify_limit()?;
+    if params.output_len != phc.hash.len() {
+        return Err(PasswordHashError::InvalidParameter(format!("l={}", params.output_len)));}
+    let derived_key = memory_hard_kdf(password, &phc.salt, &params)?;
+    Ok(utils::constant_time_eq(&derived_key, &phc.hash))}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::verify_password;
+
+    fn small_params() -> MemoryHardParams {
+        MemoryHardParams { memory_kib: 64, time_cost: 1, ..MemoryHardParams::default() }}
+
+    #[test]
+    fn hash_and_verify_round_trip() {
+        let stored = hash_password_memory_hard(b"hunter2", &small_params()).unwrap();
+        assert!(stored.starts_with("$qosmic-mem$v=1$m=64,t=1,p=1,l=64$"));
+        assert_eq!(verify_password(b"hunter2", &stored), Ok(true));
+        assert_eq!(verify_password(b"hunter3", &stored), Ok(false));}
+
+    #[test]
+    fn verification_rejects_memory_above_limit_before_allocating() {
+        let stored = hash_password_memory_hard(b"pw", &small_params()).unwrap();
+        let crafted = stored.replace("m=64,", &format!("m={},", MAX_MEMORY_KIB));
+        let expected = KdfError::CostLimitExceeded { param: "m", value: MAX_MEMORY_KIB as u64, limit: MAX_VERIFY_MEMORY_KIB as u64 };
+        assert_eq!(verify_password(b"pw", &crafted), Err(PasswordHashError::Kdf(expected)));}
+
+    #[test]
+    fn verification_rejects_time_cost_above_limit() {
+        let stored = hash_password_memory_hard(b"pw", &small_params()).unwrap();
+        let crafted = stored.replace("t=1,", &format!("t={},", u32::MAX));
+        let expected = KdfError::CostLimitExceeded { param: "t", value: u32::MAX as u64, limit: MAX_VERIFY_TIME_COST as u64 };
+        assert_eq!(verify_password(b"pw", &crafted), Err(PasswordHashError::Kdf(expected)));
+        let params = MemoryHardParams { time_cost: MAX_VERIFY_TIME_COST + 1, ..small_params() };
+        assert!(matches!(hash_password_memory_hard(b"pw", &params), Err(PasswordHashError::Kdf(KdfError::CostLimitExceeded { param: "t", .. }))));}
+
+    #[test]
+    fn hashing_refuses_unverifiable_memory_cost() {
+        let params = MemoryHardParams { memory_kib: MAX_VERIFY_MEMORY_KIB + 1, ..small_params() };
+        assert!(matches!(hash_password_memory_hard(b"pw", &params), Err(PasswordHashError::Kdf(KdfError::CostLimitExceeded { .. }))));}}
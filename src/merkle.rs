@@ -0,0 +1,466 @@
+// src/merkle.rs
+use crate::core::qosmic_digest;
+use log::{debug, info};
+use std::fmt;
+use std::io::{self, Read};
+use std::time::Instant;
+
+pub const HASH_LEN: usize = 64;
+pub const PROOF_VERSION: u8 = 1;
+pub const DEFAULT_CHUNK_SIZE: usize = 4096;
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+pub type MerkleHash = [u8; HASH_LEN];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MerkleError {
+    InvalidEncoding(&'static str),
+    UnsupportedVersion(u8),
+    IndexOutOfRange { index: u64, size: u64 },
+    SizeOutOfRange { size: u64, current: u64 },
+    Io(String),}
+
+impl fmt::Display for MerkleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MerkleError::InvalidEncoding(what) => write!(f, "malformed {}", what),
+            MerkleError::UnsupportedVersion(v) => write!(f, "unsupported Merkle proof version {}", v),
+            MerkleError::IndexOutOfRange { index, size } => write!(f, "leaf {} is outside a tree of {} leaves", index, size),
+            MerkleError::SizeOutOfRange { size, current } => write!(f, "tree size {} is outside 0..={}", size, current),
+            MerkleError::Io(e) => write!(f, "I/O error: {}", e),}}}
+
+impl std::error::Error for MerkleError {}
+
+impl From<io::Error> for MerkleError {
+    fn from(e: io::Error) -> Self {
+        MerkleError::Io(e.to_string())}}
+
+/// `qosmic(0x00 || data)`. The prefix keeps a leaf from being passed off as an inner node.
+pub fn leaf_hash(data: &[u8]) -> MerkleHash {
+    let mut input = Vec::with_capacity(1 + data.len());
+    input.push(LEAF_PREFIX);
+    input.extend_from_slice(data);
+    qosmic_digest(&input)}
+
+/// `qosmic(0x01 || left || right)`.
+pub fn node_hash(left: &MerkleHash, right: &MerkleHash) -> MerkleHash {
+    let mut input = [0u8; 1 + 2 * HASH_LEN];
+    input[0] = NODE_PREFIX;
+    input[1..1 + HASH_LEN].copy_from_slice(left);
+    input[1 + HASH_LEN..].copy_from_slice(right);
+    qosmic_digest(&input)}
+
+/// Root of the empty tree: `qosmic("")`.
+pub fn empty_root() -> MerkleHash {
+    qosmic_digest(&[])}
+
+/// Largest power of two strictly below `n` (`n >= 2`): where RFC 6962 splits a subtree.
+fn split_point(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())}
+
+/// Reads `reader` to the end in `chunk_size`-byte chunks (the last may be shorter) and returns
+/// their leaf hashes, without holding more than one chunk in memory.
+pub fn chunk_leaf_hashes<R: Read>(mut reader: R, chunk_size: usize) -> io::Result<Vec<MerkleHash>> {
+    assert!(chunk_size > 0, "chunk size must be positive");
+    let mut hashes = Vec::new();
+    let mut chunk = vec![0u8; chunk_size];
+    loop {
+        let mut filled = 0;
+        while filled < chunk_size {
+            match reader.read(&mut chunk[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),}}
+        if filled == 0 {
+            return Ok(hashes);}
+        hashes.push(leaf_hash(&chunk[..filled]));
+        if filled < chunk_size {
+            return Ok(hashes);}}}
+
+/// A Merkle tree with the RFC 6962 shape: leaves are paired left to right, and a node without a
+/// partner is carried up a level unchanged rather than duplicated, so no two different leaf lists
+/// share a root. All levels are kept, so proofs against the current size cost no hashing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    levels: Vec<Vec<MerkleHash>>,}
+
+impl MerkleTree {
+    pub fn from_leaves<I, T>(leaves: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,{
+        MerkleTree::from_leaf_hashes(leaves.into_iter().map(|leaf| leaf_hash(leaf.as_ref())).collect())}
+
+    pub fn from_leaf_hashes(leaf_hashes: Vec<MerkleHash>) -> Self {
+        let start = Instant::now();
+        let mut levels = vec![leaf_hashes];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let below = levels.last().expect("loop condition checked a level exists");
+            let level = below.chunks(2)
+                .map(|pair| if pair.len() == 2 { node_hash(&pair[0], &pair[1]) } else { pair[0] })
+                .collect();
+            levels.push(level);}
+        debug!("Merkle tree over {} leaves built in {:?}", levels[0].len(), start.elapsed());
+        MerkleTree { levels }}
+
+    /// Splits `data` into `chunk_size`-byte leaves.
+    pub fn from_chunks(data: &[u8], chunk_size: usize) -> Self {
+        MerkleTree::from_leaves(data.chunks(chunk_size))}
+
+    pub fn from_reader<R: Read>(reader: R, chunk_size: usize) -> io::Result<Self> {
+        let start = Instant::now();
+        let tree = MerkleTree::from_leaf_hashes(chunk_leaf_hashes(reader, chunk_size)?);
+        info!("Merkle tree over {} chunks of {} bytes took: {:?}", tree.len(), chunk_size, start.elapsed());
+        Ok(tree)}
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()}
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()}
+
+    pub fn root(&self) -> MerkleHash {
+        match self.levels.last().and_then(|level| level.first()) {
+            Some(root) => *root,
+            None => empty_root(),}}
+
+    pub fn leaf(&self, index: usize) -> Option<&MerkleHash> {
+        self.levels[0].get(index)}
+
+    /// Proof that leaf `index` is in this tree: the partner of each node on the way to the root,
+    /// skipping levels where that node has none.
+    pub fn prove(&self, index: usize) -> Result<InclusionProof, MerkleError> {
+        if index >= self.len() {
+            return Err(MerkleError::IndexOutOfRange { index: index as u64, size: self.len() as u64 });}
+        let mut path = Vec::with_capacity(self.levels.len());
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(position ^ 1) {
+                path.push(*sibling);}
+            position >>= 1;}
+        Ok(InclusionProof { leaf_index: index as u64, tree_size: self.len() as u64, path })}
+
+    /// Appends one leaf, rehashing only the right edge of the tree.
+    pub fn push_hash(&mut self, leaf_hash: MerkleHash) {
+        self.levels[0].push(leaf_hash);
+        let mut depth = 0;
+        while self.levels[depth].len() > 1 {
+            let level = &self.levels[depth];
+            let parent_index = (level.len() - 1) / 2;
+            let parent = if level.len().is_multiple_of(2) {
+                node_hash(&level[2 * parent_index], &level[2 * parent_index + 1])
+            } else {
+                level[2 * parent_index]};
+            if self.levels.len() == depth + 1 {
+                self.levels.push(Vec::new());}
+            let up = &mut self.levels[depth + 1];
+            if parent_index < up.len() {
+                up[parent_index] = parent;
+            } else {
+                up.push(parent);}
+            depth += 1;}}
+
+    pub fn push(&mut self, leaf_data: &[u8]) {
+        self.push_hash(leaf_hash(leaf_data))}
+
+    /// Hash of the subtree over leaves `start..end`. Aligned power-of-two ranges are stored nodes;
+    /// other ranges are split the RFC 6962 way.
+    fn range_hash(&self, start: usize, end: usize) -> MerkleHash {
+        let len = end - start;
+        if len == 0 {
+            return empty_root();}
+        if len.is_power_of_two() && start.is_multiple_of(len) {
+            let depth = len.trailing_zeros() as usize;
+            return self.levels[depth][start >> depth];}
+        let k = split_point(len);
+        node_hash(&self.range_hash(start, start + k), &self.range_hash(start + k, end))}
+
+    fn check_size(&self, size: usize) -> Result<(), MerkleError> {
+        if size > self.len() {
+            return Err(MerkleError::SizeOutOfRange { size: size as u64, current: self.len() as u64 });}
+        Ok(())}
+
+    /// Root the tree had when it held only its first `size` leaves.
+    pub fn root_at(&self, size: usize) -> Result<MerkleHash, MerkleError> {
+        self.check_size(size)?;
+        Ok(self.range_hash(0, size))}
+
+    fn inclusion_path(&self, index: usize, start: usize, end: usize, path: &mut Vec<MerkleHash>) {
+        if end - start <= 1 {
+            return;}
+        let k = split_point(end - start);
+        if index < k {
+            self.inclusion_path(index, start, start + k, path);
+            path.push(self.range_hash(start + k, end));
+        } else {
+            self.inclusion_path(index - k, start + k, end, path);
+            path.push(self.range_hash(start, start + k));}}
+
+    /// Inclusion proof for leaf `index` in the tree of the first `size` leaves (RFC 6962 `PATH`).
+    pub fn prove_at(&self, index: usize, size: usize) -> Result<InclusionProof, MerkleError> {
+        self.check_size(size)?;
+        if index >= size {
+            return Err(MerkleError::IndexOutOfRange { index: index as u64, size: size as u64 });}
+        let mut path = Vec::new();
+        self.inclusion_path(index, 0, size, &mut path);
+        Ok(InclusionProof { leaf_index: index as u64, tree_size: size as u64, path })}
+
+    fn consistency_path(&self, old_size: usize, start: usize, end: usize, whole: bool, path: &mut Vec<MerkleHash>) {
+        let len = end - start;
+        if old_size == len {
+            if !whole {
+                path.push(self.range_hash(start, end));}
+            return;}
+        let k = split_point(len);
+        if old_size <= k {
+            self.consistency_path(old_size, start, start + k, whole, path);
+            path.push(self.range_hash(start + k, end));
+        } else {
+            self.consistency_path(old_size - k, start + k, end, false, path);
+            path.push(self.range_hash(start, start + k));}}
+
+    /// Proof that the tree of the first `old_size` leaves is a prefix of the tree of the first
+    /// `new_size` leaves (RFC 6962 `PROOF`). Empty when either size is 0 or they are equal.
+    pub fn prove_consistency(&self, old_size: usize, new_size: usize) -> Result<ConsistencyProof, MerkleError> {
+        self.check_size(new_size)?;
+        if old_size > new_size {
+            return Err(MerkleError::SizeOutOfRange { size: old_size as u64, current: new_size as u64 });}
+        let mut path = Vec::new();
+        if old_size > 0 {
+            self.consistency_path(old_size, 0, new_size, true, &mut path);}
+        Ok(ConsistencyProof { old_size: old_size as u64, new_size: new_size as u64, path })}}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InclusionProof {
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    pub path: Vec<MerkleHash>,}
+
+impl InclusionProof {
+    pub fn verify(&self, leaf_data: &[u8], root: &MerkleHash) -> bool {
+        self.verify_hash(&leaf_hash(leaf_data), root)}
+
+    /// Recomputes the root from the leaf hash with the RFC 9162 (section 2.1.3.2) algorithm,
+    /// which uses the index and tree size to tell which side each path element goes on.
+    pub fn verify_hash(&self, leaf_hash: &MerkleHash, root: &MerkleHash) -> bool {
+        if self.leaf_index >= self.tree_size {
+            return false;}
+        let mut index = self.leaf_index;
+        let mut last = self.tree_size - 1;
+        let mut hash = *leaf_hash;
+        for sibling in &self.path {
+            if last == 0 {
+                return false;}
+            if index & 1 == 1 || index == last {
+                hash = node_hash(sibling, &hash);
+                while index & 1 == 0 && index != 0 {
+                    index >>= 1;
+                    last >>= 1;}
+            } else {
+                hash = node_hash(&hash, sibling);}
+            index >>= 1;
+            last >>= 1;}
+        last == 0 && hash == *root}
+
+    /// `[version (0x01)][leaf index u64 BE][tree size u64 BE][path length u8][path hashes, leaf level first]`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_proof(self.leaf_index, self.tree_size, &self.path)}
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let (leaf_index, tree_size, path) = decode_proof(bytes, "Merkle inclusion proof")?;
+        Ok(InclusionProof { leaf_index, tree_size, path })}}
+
+fn encode_proof(first: u64, second: u64, path: &[MerkleHash]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(18 + path.len() * HASH_LEN);
+    out.push(PROOF_VERSION);
+    out.extend_from_slice(&first.to_be_bytes());
+    out.extend_from_slice(&second.to_be_bytes());
+    out.push(path.len() as u8);
+    for hash in path {
+        out.extend_from_slice(hash);}
+    out}
+
+fn decode_proof(bytes: &[u8], what: &'static str) -> Result<(u64, u64, Vec<MerkleHash>), MerkleError> {
+    if bytes.len() < 18 {
+        return Err(MerkleError::InvalidEncoding(what));}
+    if bytes[0] != PROOF_VERSION {
+        return Err(MerkleError::UnsupportedVersion(bytes[0]));}
+    let first = u64::from_be_bytes(bytes[1..9].try_into().unwrap());
+    let second = u64::from_be_bytes(bytes[9..17].try_into().unwrap());
+    let count = bytes[17] as usize;
+    if bytes.len() != 18 + count * HASH_LEN {
+        return Err(MerkleError::InvalidEncoding(what));}
+    let path = bytes[18..].chunks_exact(HASH_LEN).map(|chunk| chunk.try_into().unwrap()).collect();
+    Ok((first, second, path))}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsistencyProof {
+    pub old_size: u64,
+    pub new_size: u64,
+    pub path: Vec<MerkleHash>,}
+
+impl ConsistencyProof {
+    /// Checks that `old_root` (over `old_size` leaves) and `new_root` (over `new_size` leaves) are
+    /// roots of the same log, the first a prefix of the second, with the RFC 9162 (section 2.1.4.2)
+    /// algorithm.
+    pub fn verify(&self, old_root: &MerkleHash, new_root: &MerkleHash) -> bool {
+        let (old_size, new_size) = (self.old_size, self.new_size);
+        if old_size > new_size {
+            return false;}
+        if old_size == 0 {
+            return self.path.is_empty();}
+        if old_size == new_size {
+            return self.path.is_empty() && old_root == new_root;}
+        let mut path = self.path.iter();
+        let seed = if old_size.is_power_of_two() {
+            old_root
+        } else {
+            match path.next() {
+                Some(hash) => hash,
+                None => return false,}};
+        let mut index = old_size - 1;
+        let mut last = new_size - 1;
+        while index & 1 == 1 {
+            index >>= 1;
+            last >>= 1;}
+        let (mut old_hash, mut new_hash) = (*seed, *seed);
+        for sibling in path {
+            if last == 0 {
+                return false;}
+            if index & 1 == 1 || index == last {
+                old_hash = node_hash(sibling, &old_hash);
+                new_hash = node_hash(sibling, &new_hash);
+                while index & 1 == 0 && index != 0 {
+                    index >>= 1;
+                    last >>= 1;}
+            } else {
+                new_hash = node_hash(&new_hash, sibling);}
+            index >>= 1;
+            last >>= 1;}
+        last == 0 && old_hash == *old_root && new_hash == *new_root}
+
+    /// Same layout as an inclusion proof, with the old and new tree sizes in place of the leaf
+    /// index and tree size.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_proof(self.old_size, self.new_size, &self.path)}
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let (old_size, new_size, path) = decode_proof(bytes, "Merkle consistency proof")?;
+        Ok(ConsistencyProof { old_size, new_size, path })}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 9162 `MTH`, straight from the recursive definition.
+    fn reference_root(leaves: &[Vec<u8>]) -> MerkleHash {
+        match leaves.len() {
+            0 => empty_root(),
+            1 => leaf_hash(&leaves[0]),
+            n => {
+                let k = split_point(n);
+                node_hash(&reference_root(&leaves[..k]), &reference_root(&leaves[k..]))},}}
+
+    fn leaves(n: usize) -> Vec<Vec<u8>> {
+        (0..n).map(|i| format!("leaf {}", i).into_bytes()).collect()}
+
+    #[test]
+    fn roots_match_the_rfc_definition() {
+        for n in 0..=17 {
+            let data = leaves(n);
+            let tree = MerkleTree::from_leaves(&data);
+            assert_eq!(tree.len(), n);
+            assert_eq!(tree.root(), reference_root(&data), "n = {}", n);}
+        assert_eq!(MerkleTree::from_chunks(b"abcdefg", 3).root(), reference_root(&[b"abc".to_vec(), b"def".to_vec(), b"g".to_vec()]));
+        assert_eq!(MerkleTree::from_reader(&b"abcdefg"[..], 3).unwrap(), MerkleTree::from_chunks(b"abcdefg", 3));
+        assert!(MerkleTree::from_chunks(b"", 3).is_empty());}
+
+    #[test]
+    fn inclusion_proofs_verify_for_every_leaf() {
+        for n in 1..=16 {
+            let data = leaves(n);
+            let tree = MerkleTree::from_leaves(&data);
+            let root = tree.root();
+            for (m, leaf) in data.iter().enumerate() {
+                let proof = tree.prove(m).unwrap();
+                assert!(proof.verify(leaf, &root), "m = {}, n = {}", m, n);
+                assert!(!proof.verify(b"other", &root));
+                assert_eq!(InclusionProof::from_bytes(&proof.to_bytes()).unwrap(), proof);
+                if n > 1 {
+                    let moved = InclusionProof { leaf_index: ((m + 1) % n) as u64, ..proof.clone() };
+                    assert!(!moved.verify(leaf, &root), "m = {}, n = {}", m, n);
+                    let mut altered = proof.clone();
+                    altered.path[0][0] ^= 1;
+                    assert!(!altered.verify(leaf, &root));
+                    let mut short = proof.clone();
+                    short.path.pop();
+                    assert!(!short.verify(leaf, &root));}}
+            assert_eq!(tree.prove(n), Err(MerkleError::IndexOutOfRange { index: n as u64, size: n as u64 }));}}
+
+    #[test]
+    fn proof_encoding_rejects_bad_input() {
+        let proof = MerkleTree::from_leaves(leaves(5)).prove(4).unwrap();
+        let bytes = proof.to_bytes();
+        assert_eq!(bytes.len(), 18 + proof.path.len() * HASH_LEN);
+        assert!(InclusionProof::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(InclusionProof::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_err());
+        let mut version = bytes.clone();
+        version[0] = 2;
+        assert_eq!(InclusionProof::from_bytes(&version), Err(MerkleError::UnsupportedVersion(2)));}
+
+    #[test]
+    fn appending_matches_rebuilding_and_old_roots_are_kept() {
+        let data = leaves(17);
+        let mut tree = MerkleTree::from_leaves(Vec::<Vec<u8>>::new());
+        assert_eq!(tree.root(), empty_root());
+        for (n, leaf) in data.iter().enumerate() {
+            tree.push(leaf);
+            assert_eq!(tree, MerkleTree::from_leaves(&data[..=n]));}
+        for size in 0..=data.len() {
+            assert_eq!(tree.root_at(size).unwrap(), reference_root(&data[..size]));}
+        assert_eq!(tree.root_at(18), Err(MerkleError::SizeOutOfRange { size: 18, current: 17 }));}
+
+    #[test]
+    fn historical_inclusion_proofs_verify_for_all_sizes() {
+        let data = leaves(16);
+        let tree = MerkleTree::from_leaves(&data);
+        for n in 1..=16 {
+            let root = tree.root_at(n).unwrap();
+            for m in 0..n {
+                let proof = tree.prove_at(m, n).unwrap();
+                assert!(proof.verify(&data[m], &root), "m = {}, n = {}", m, n);
+                assert!(!proof.verify(&data[(m + 1) % 16], &root), "m = {}, n = {}", m, n);
+                if n < 16 {
+                    assert!(!proof.verify(&data[m], &tree.root_at(n + 1).unwrap()));}}}
+        assert_eq!(tree.prove_at(3, 3), Err(MerkleError::IndexOutOfRange { index: 3, size: 3 }));}
+
+    #[test]
+    fn consistency_proofs_verify_for_all_size_pairs() {
+        let data = leaves(16);
+        let tree = MerkleTree::from_leaves(&data);
+        let roots: Vec<MerkleHash> = (0..=16).map(|size| tree.root_at(size).unwrap()).collect();
+        for n in 0..=16 {
+            for m in 0..=n {
+                let proof = tree.prove_consistency(m, n).unwrap();
+                assert!(proof.verify(&roots[m], &roots[n]), "m = {}, n = {}", m, n);
+                assert_eq!(ConsistencyProof::from_bytes(&proof.to_bytes()).unwrap(), proof);
+                if m == 0 || m == n {
+                    assert!(proof.path.is_empty());
+                    continue;}
+                assert!(!proof.verify(&roots[m - 1], &roots[n]), "m = {}, n = {}", m, n);
+                assert!(!proof.verify(&roots[m], &roots[n - 1]), "m = {}, n = {}", m, n);
+                let mut altered = proof.clone();
+                altered.path[0][0] ^= 1;
+                assert!(!altered.verify(&roots[m], &roots[n]), "m = {}, n = {}", m, n);
+                let mut short = proof.clone();
+                short.path.pop();
+                assert!(!short.verify(&roots[m], &roots[n]), "m = {}, n = {}", m, n);}}
+        // A log that rewrote leaf 2 cannot prove it only appended.
+        let mut forked = data.clone();
+        forked[2] = b"rewritten".to_vec();
+        let forked = MerkleTree::from_leaves(&forked);
+        assert!(!tree.prove_consistency(4, 8).unwrap().verify(&roots[4], &forked.root_at(8).unwrap()));
+        assert!(!forked.prove_consistency(4, 8).unwrap().verify(&roots[4], &forked.root_at(8).unwrap()));
+        assert_eq!(tree.prove_consistency(5, 4), Err(MerkleError::SizeOutOfRange { size: 5, current: 4 }));}}
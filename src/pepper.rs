@@ -0,0 +1,128 @@
+// src/pepper.rs
+use crate::core::hmac_qosmic_bytes;
+use crate::phc::{PasswordHashError, PhcString};
+use hex;
+use std::fmt;
+
+pub const PEPPER_PARAM: &str = "pid";
+pub const MIN_PEPPER_LEN: usize = 16;
+
+/// A server-side secret mixed into every password before the KDF runs. The password is replaced
+/// by `HMAC-Qosmic(secret, password)`, and only `id` is stored in the hash string (as `pid=<id>`),
+/// so old peppers can be kept for verification while new hashes use the current one.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Pepper {
+    id: String,
+    secret: Vec<u8>,}
+
+impl fmt::Debug for Pepper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pepper").field("id", &self.id).finish_non_exhaustive()}}
+
+impl Pepper {
+    pub fn new(id: &str, secret: &[u8]) -> Result<Self, PasswordHashError> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-')) {
+            return Err(PasswordHashError::InvalidParameter(format!("{}={}", PEPPER_PARAM, id)));}
+        if secret.len() < MIN_PEPPER_LEN {
+            return Err(PasswordHashError::InvalidParameter(format!("pepper '{}' is shorter than {} bytes", id, MIN_PEPPER_LEN)));}
+        Ok(Pepper { id: id.to_string(), secret: secret.to_vec() })}
+
+    pub fn id(&self) -> &str {
+        &self.id}
+
+    pub fn apply(&self, password: &[u8]) -> [u8; 64] {
+        hmac_qosmic_bytes(&self.secret, password)}}
+
+/// Parses `id:hex_secret` lines; blank lines and lines starting with `#` are skipped.
+/// The first pepper is treated as the current one by callers that hash new passwords.
+pub fn parse_pepper_list(contents: &str) -> Result<Vec<Pepper>, PasswordHashError> {
+    let mut peppers: Vec<Pepper> = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;}
+        let (id, secret_hex) = line.split_once(':').ok_or(PasswordHashError::InvalidFormat("expected 'id:hex_secret' pepper line"))?;
+        let secret = hex::decode(secret_hex.trim()).map_err(|_| PasswordHashError::InvalidEncoding("pepper secret"))?;
+        let pepper = Pepper::new(id.trim(), &secret)?;
+        if peppers.iter().any(|p| p.id == pepper.id) {
+            return Err(PasswordHashError::InvalidParameter(format!("duplicate pepper id '{}'", pepper.id)));}
+        peppers.push(pepper);}
+    Ok(peppers)}
+
+/// Returns the password bytes the KDF should see when hashing with `pepper`, and the `pid` parameter to record.
+pub(crate) fn pepper_password(password: &[u8], pepper: Option<&Pepper>) -> (Vec<u8>, Option<(String, String)>) {
+    match pepper {
+        Some(p) => (p.apply(password).to_vec(), Some((PEPPER_PARAM.to_string(), p.id.clone()))),
+        None => (password.to_vec(), None),}}
+
+/// Returns the password bytes the KDF should see for `phc`: the peppered password when the hash
+/// records a pepper id, the password itself otherwise.
+pub(crate) fn kdf_input(password: &[u8], phc: &PhcString, peppers: &[Pepper]) -> Result<Vec<u8>, PasswordHashError> {
+    match phc.param(PEPPER_PARAM) {
+        None => Ok(password.to_vec()),
+        Some(id) => peppers.iter()
+            .find(|p| p.id == id)
+            .map(|p| p.apply(password).to_vec())
+            .ok_or_else(|| PasswordHashError::UnknownPepper(id.to_string())),}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{hash_password_with_params, hash_password_with_pepper, verify_password, verify_password_with_peppers, KdfParams};
+    use crate::memhard::{hash_password_memory_hard_with_pepper, MemoryHardParams};
+
+    fn fast() -> KdfParams {
+        KdfParams { iterations: 3, ..KdfParams::default() }}
+
+    #[test]
+    fn peppered_hash_verifies_only_with_its_pepper() {
+        let pepper = Pepper::new("2024a", &[7; 32]).unwrap();
+        let stored = hash_password_with_pepper(b"hunter2", &fast(), Some(&pepper)).unwrap();
+        assert!(stored.starts_with("$qosmic-pbkdf2$v=1$i=3,l=64,pid=2024a$"));
+        assert_eq!(verify_password_with_peppers(b"hunter2", &stored, std::slice::from_ref(&pepper)), Ok(true));
+        assert_eq!(verify_password_with_peppers(b"hunter3", &stored, std::slice::from_ref(&pepper)), Ok(false));
+        let same_id = Pepper::new("2024a", &[8; 32]).unwrap();
+        assert_eq!(verify_password_with_peppers(b"hunter2", &stored, &[same_id]), Ok(false));
+        let params = MemoryHardParams { memory_kib: 64, time_cost: 1, ..MemoryHardParams::default() };
+        let stored = hash_password_memory_hard_with_pepper(b"hunter2", &params, Some(&pepper)).unwrap();
+        assert_eq!(verify_password_with_peppers(b"hunter2", &stored, &[pepper]), Ok(true));}
+
+    #[test]
+    fn missing_pepper_is_reported() {
+        let pepper = Pepper::new("old", &[1; 16]).unwrap();
+        let stored = hash_password_with_pepper(b"pw", &fast(), Some(&pepper)).unwrap();
+        assert_eq!(verify_password(b"pw", &stored), Err(PasswordHashError::UnknownPepper("old".to_string())));
+        let other = Pepper::new("new", &[2; 16]).unwrap();
+        assert_eq!(verify_password_with_peppers(b"pw", &stored, &[other]), Err(PasswordHashError::UnknownPepper("old".to_string())));}
+
+    #[test]
+    fn rotation_keeps_old_and_unpeppered_hashes_valid() {
+        let peppers = parse_pepper_list(&format!("new:{}\nold:{}\n", hex::encode([0x22; 32]), hex::encode([0x11; 32]))).unwrap();
+        let old_hash = hash_password_with_pepper(b"pw", &fast(), Some(&peppers[1])).unwrap();
+        let new_hash = hash_password_with_pepper(b"pw", &fast(), Some(&peppers[0])).unwrap();
+        let plain_hash = hash_password_with_params(b"pw", &fast()).unwrap();
+        for stored in [&old_hash, &new_hash, &plain_hash] {
+            assert_eq!(verify_password_with_peppers(b"pw", stored, &peppers), Ok(true), "{}", stored);
+            assert_eq!(verify_password_with_peppers(b"wrong", stored, &peppers), Ok(false), "{}", stored);}
+        assert!(new_hash.contains(",pid=new$"));
+        assert_ne!(old_hash, new_hash);}
+
+    #[test]
+    fn pepper_list_parsing() {
+        let secret = hex::encode([0xab; 16]);
+        let peppers = parse_pepper_list(&format!("# current first\n\n  a.1 : {}  \nb-2:{}\n", secret, secret)).unwrap();
+        assert_eq!(peppers.iter().map(Pepper::id).collect::<Vec<_>>(), ["a.1", "b-2"]);
+        assert_eq!(parse_pepper_list("").unwrap(), Vec::new());
+        assert_eq!(parse_pepper_list("no separator"), Err(PasswordHashError::InvalidFormat("expected 'id:hex_secret' pepper line")));
+        assert_eq!(parse_pepper_list("a:zz"), Err(PasswordHashError::InvalidEncoding("pepper secret")));
+        assert_eq!(parse_pepper_list(&format!("a:{}", hex::encode([1; 15]))),
+            Err(PasswordHashError::InvalidParameter("pepper 'a' is shorter than 16 bytes".to_string())));
+        assert_eq!(parse_pepper_list(&format!("a b:{}", secret)), Err(PasswordHashError::InvalidParameter("pid=a b".to_string())));
+        assert_eq!(parse_pepper_list(&format!(":{}", secret)), Err(PasswordHashError::InvalidParameter("pid=".to_string())));
+        assert_eq!(parse_pepper_list(&format!("a:{}\na:{}", secret, secret)),
+            Err(PasswordHashError::InvalidParameter("duplicate pepper id 'a'".to_string())));}
+
+    #[test]
+    fn debug_output_hides_the_secret() {
+        let pepper = Pepper::new("k1", b"super secret pepper").unwrap();
+        assert_eq!(format!("{:?}", pepper), "Pepper { id: \"k1\", .. }");}}
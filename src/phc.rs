@@ -0,0 +1,175 @@
+// src/phc.rs
+use crate::core::KdfError;
+use base64::{engine::general_purpose, Engine as _};
+use hex;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordHashError {
+    InvalidFormat(&'static str),
+    InvalidEncoding(&'static str),
+    UnsupportedAlgorithm(String),
+    UnsupportedVersion(u32),
+    MissingParameter(&'static str),
+    InvalidParameter(String),
+    UnknownPepper(String),
+    Kdf(KdfError),}
+
+impl fmt::Display for PasswordHashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordHashError::InvalidFormat(reason) => write!(f, "invalid password hash string: {}", reason),
+            PasswordHashError::InvalidEncoding(field) => write!(f, "invalid encoding in {} field", field),
+            PasswordHashError::UnsupportedAlgorithm(alg) => write!(f, "unsupported algorithm '{}'", alg),
+            PasswordHashError::UnsupportedVersion(v) => write!(f, "unsupported algorithm version {}", v),
+            PasswordHashError::MissingParameter(name) => write!(f, "missing parameter '{}'", name),
+            PasswordHashError::InvalidParameter(param) => write!(f, "invalid parameter '{}'", param),
+            PasswordHashError::UnknownPepper(id) => write!(f, "no pepper with id '{}' is configured", id),
+            PasswordHashError::Kdf(e) => write!(f, "{}", e),}}}
+
+impl std::error::Error for PasswordHashError {}
+
+impl From<KdfError> for PasswordHashError {
+    fn from(e: KdfError) -> Self {
+        PasswordHashError::Kdf(e)}}
+
+/// A parsed `$alg$v=N$k=v,...$salt$hash` string. Salt and hash are PHC Base64 (standard alphabet, no padding).
+/// `legacy` is set when the string came from the older `salt_hex$hash_hex` output of `hash_password`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhcString {
+    pub algorithm: String,
+    pub version: Option<u32>,
+    pub params: Vec<(String, String)>,
+    pub salt: Vec<u8>,
+    pub hash: Vec<u8>,
+    pub legacy: bool,}
+
+fn is_valid_param_value(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '+' | '.' | '-'))}
+
+impl PhcString {
+    pub fn new(algorithm: &str, version: u32, params: Vec<(String, String)>, salt: Vec<u8>, hash: Vec<u8>) -> Self {
+        PhcString { algorithm: algorithm.to_string(), version: Some(version), params, salt, hash, legacy: false }}
+
+    pub fn parse(encoded: &str) -> Result<Self, PasswordHashError> {
+        let encoded = encoded.trim();
+        let body = encoded.strip_prefix('$').ok_or(PasswordHashError::InvalidFormat("expected leading '$'"))?;
+        let mut fields: Vec<&str> = body.split('$').collect();
+        if fields.len() < 3 || fields.len() > 5 {
+            return Err(PasswordHashError::InvalidFormat("wrong number of '$'-separated fields"));}
+        let algorithm = fields.remove(0);
+        if algorithm.is_empty() || !algorithm.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+            return Err(PasswordHashError::InvalidFormat("bad algorithm identifier"));}
+        let mut version = None;
+        if let Some(v) = fields.first().and_then(|f| f.strip_prefix("v=")) {
+            version = Some(v.parse::<u32>().map_err(|_| PasswordHashError::InvalidParameter(format!("v={}", v)))?);
+            fields.remove(0);}
+        let mut params = Vec::new();
+        if fields.len() == 3 {
+            for pair in fields.remove(0).split(',') {
+                let (name, value) = pair.split_once('=').ok_or_else(|| PasswordHashError::InvalidParameter(pair.to_string()))?;
+                if name.is_empty() || !is_valid_param_value(value) || params.iter().any(|(k, _)| k == name) {
+                    return Err(PasswordHashError::InvalidParameter(pair.to_string()));}
+                params.push((name.to_string(), value.to_string()));}}
+        if fields.len() != 2 {
+            return Err(PasswordHashError::InvalidFormat("expected salt and hash fields"));}
+        let salt = general_purpose::STANDARD_NO_PAD.decode(fields[0]).map_err(|_| PasswordHashError::InvalidEncoding("salt"))?;
+        let hash = general_purpose::STANDARD_NO_PAD.decode(fields[1]).map_err(|_| PasswordHashError::InvalidEncoding("hash"))?;
+        Ok(PhcString { algorithm: algorithm.to_string(), version, params, salt, hash, legacy: false })}
+
+    /// Parses the legacy `salt_hex$hash_hex` format, filling in the parameters it was produced with.
+    pub fn parse_legacy(encoded: &str, legacy_algorithm: &str, legacy_params: &[(&str, &str)]) -> Result<Self, PasswordHashError> {
+        let (salt_hex, hash_hex) = encoded.trim().split_once('$').ok_or(PasswordHashError::InvalidFormat("expected 'salt$hash'"))?;
+        let salt = hex::decode(salt_hex).map_err(|_| PasswordHashError::InvalidEncoding("salt"))?;
+        let hash = hex::decode(hash_hex).map_err(|_| PasswordHashError::InvalidEncoding("hash"))?;
+        let params = legacy_params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Ok(PhcString { algorithm: legacy_algorithm.to_string(), version: None, params, salt, hash, legacy: true })}
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())}
+
+    /// Rejects parameters outside `known`, so a mistyped or newer parameter is not silently ignored.
+    pub fn check_known_params(&self, known: &[&str]) -> Result<(), PasswordHashError> {
+        match self.params.iter().find(|(k, _)| !known.contains(&k.as_str())) {
+            Some((k, v)) => Err(PasswordHashError::InvalidParameter(format!("{}={}", k, v))),
+            None => Ok(()),}}
+
+    pub fn param_u32(&self, name: &'static str) -> Result<u32, PasswordHashError> {
+        let value = self.param(name).ok_or(PasswordHashError::MissingParameter(name))?;
+        value.parse::<u32>().map_err(|_| PasswordHashError::InvalidParameter(format!("{}={}", name, value)))}}
+
+impl fmt::Display for PhcString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "${}", self.algorithm)?;
+        if let Some(v) = self.version {
+            write!(f, "$v={}", v)?;}
+        if !self.params.is_empty() {
+            let joined: Vec<String> = self.params.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            write!(f, "${}", joined.join(","))?;}
+        write!(f, "${}${}",
+            general_purpose::STANDARD_NO_PAD.encode(&self.salt),
+            general_purpose::STANDARD_NO_PAD.encode(&self.hash))}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{hash_password_with_params, pbkdf2_hmac_qosmic, verify_password, KdfParams, ITERATIONS, KEY_LEN};
+
+    fn sample() -> PhcString {
+        let params = vec![("i".to_string(), "10000".to_string()), ("l".to_string(), "32".to_string())];
+        PhcString::new("qosmic-pbkdf2", 1, params, b"0123456789abcdef".to_vec(), vec![0xa5; 32])}
+
+    #[test]
+    fn format_and_parse_round_trip() {
+        let phc = sample();
+        let encoded = phc.to_string();
+        assert_eq!(encoded, "$qosmic-pbkdf2$v=1$i=10000,l=32$MDEyMzQ1Njc4OWFiY2RlZg$paWlpaWlpaWlpaWlpaWlpaWlpaWlpaWlpaWlpaWlpaU");
+        assert_eq!(PhcString::parse(&encoded), Ok(phc.clone()));
+        assert_eq!(PhcString::parse(&format!("  {}\n", encoded)), Ok(phc));
+        let bare = PhcString { version: None, params: Vec::new(), ..sample() };
+        assert_eq!(bare.to_string(), "$qosmic-pbkdf2$MDEyMzQ1Njc4OWFiY2RlZg$paWlpaWlpaWlpaWlpaWlpaWlpaWlpaWlpaWlpaWlpaU");
+        assert_eq!(PhcString::parse(&bare.to_string()), Ok(bare));
+        let stored = hash_password_with_params(b"pw", &KdfParams { iterations: 3, ..KdfParams::default() }).unwrap();
+        assert_eq!(PhcString::parse(&stored).unwrap().to_string(), stored);}
+
+    #[test]
+    fn malformed_strings_are_rejected() {
+        let salt = "MDEyMzQ1Njc4OWFiY2RlZg";
+        let cases = [
+            ("qosmic-pbkdf2$v=1$i=1,l=32$MDEy$paWl".to_string(), PasswordHashError::InvalidFormat("expected leading '$'")),
+            ("$qosmic-pbkdf2".to_string(), PasswordHashError::InvalidFormat("wrong number of '$'-separated fields")),
+            ("$Qosmic$v=1$i=1$MDEy$paWl".to_string(), PasswordHashError::InvalidFormat("bad algorithm identifier")),
+            ("$qosmic-pbkdf2$v=x$i=1$MDEy$paWl".to_string(), PasswordHashError::InvalidParameter("v=x".to_string())),
+            (format!("$qosmic-pbkdf2$v=1$i=1,l=32${}!$paWl", salt), PasswordHashError::InvalidEncoding("salt")),
+            (format!("$qosmic-pbkdf2$v=1$i=1,l=32${}$paWl=", salt), PasswordHashError::InvalidEncoding("hash")),
+            (format!("$qosmic-pbkdf2$v=1$i=1,i=2${}$paWl", salt), PasswordHashError::InvalidParameter("i=2".to_string())),
+            (format!("$qosmic-pbkdf2$v=1$i=1,l${}$paWl", salt), PasswordHashError::InvalidParameter("l".to_string())),
+            (format!("$qosmic-pbkdf2$v=1$i=1,l=${}$paWl", salt), PasswordHashError::InvalidParameter("l=".to_string())),];
+        for (encoded, expected) in cases {
+            assert_eq!(PhcString::parse(&encoded), Err(expected), "{}", encoded);}}
+
+    #[test]
+    fn unknown_and_missing_parameters_fail_verification() {
+        let stored = hash_password_with_params(b"pw", &KdfParams { iterations: 3, ..KdfParams::default() }).unwrap();
+        assert_eq!(verify_password(b"pw", &stored), Ok(true));
+        let unknown = stored.replace("i=3,", "i=3,x=1,");
+        assert_eq!(verify_password(b"pw", &unknown), Err(PasswordHashError::InvalidParameter("x=1".to_string())));
+        let missing = stored.replace("i=3,", "");
+        assert_eq!(verify_password(b"pw", &missing), Err(PasswordHashError::MissingParameter("i")));
+        let phc = PhcString::parse(&unknown).unwrap();
+        assert_eq!(phc.param("x"), Some("1"));
+        assert_eq!(phc.param_u32("l"), Ok(64));
+        assert_eq!(phc.param_u32("y"), Err(PasswordHashError::MissingParameter("y")));}
+
+    #[test]
+    fn legacy_hex_format_still_verifies() {
+        let salt = b"legacy salt 0123";
+        let legacy = format!("{}${}", hex::encode(salt), hex::encode(pbkdf2_hmac_qosmic(b"hunter2", salt, ITERATIONS, KEY_LEN)));
+        let phc = crate::core::parse_password_hash(&legacy).unwrap();
+        assert!(phc.legacy);
+        assert_eq!(phc.version, None);
+        assert_eq!((phc.param_u32("i"), phc.param_u32("l")), (Ok(ITERATIONS), Ok(KEY_LEN as u32)));
+        assert_eq!(verify_password(b"hunter2", &legacy), Ok(true));
+        assert_eq!(verify_password(b"hunter3", &legacy), Ok(false));
+        assert_eq!(verify_password(b"hunter2", "zz$00"), Err(PasswordHashError::InvalidEncoding("salt")));
+        assert_eq!(verify_password(b"hunter2", "no separator"), Err(PasswordHashError::InvalidFormat("expected 'salt$hash'")));}}
@@ -2,50 +2,50 @@
 use crate::constants::*;
 use crate::core::SBoxType;
 use crate::utils::{key_as_u128, key_as_u64};
-use ndarray::{Array1, Array2, Axis};
+use ndarray::Array1;
 use num_traits::ToBytes;
-use rand::Rng;
+use crate::lwe::ring::{NttPoly, Poly, RING_N};
+use crate::lwe::sampler::{CenteredBinomial, XofBits};
+use crate::xof::QosmicXof;
 use std::convert::TryInto;
 use log::debug;
 
 const POLY_GF2_9_DEG9: u16 = 0x211;
+pub const LATTICE_BLOCK_SIZE: usize = 4096;
+const LATTICE_SEED_DOMAIN: &[u8] = b"qosmic-lattice seed";
+const LATTICE_POLY_DOMAIN: &[u8] = b"qosmic-lattice a";
+const LATTICE_ERROR_DOMAIN: &[u8] = b"qosmic-lattice error";
+/// Centered binomial parameter for the Ring-LWE secret and error (variance 4).
+const LATTICE_ETA: u32 = 8;
 
-pub fn encrypt_internal(message_bits: &Array1<u8>) -> (Array2<u64>, Array1<u64>) {
+/// Ring-LWE "encryption" of up to `RING_N` message bits against the public ring element `a`
+/// (already in the NTT domain): `b = a * s + e + round(q / 2) * m` in `lwe::ring`, with the secret
+/// `s` and error `e` drawn from `CBD_8` over `error_bits`.
+pub fn encrypt_internal(message_bits: &Array1<u8>, a: &NttPoly, error_bits: &mut XofBits) -> Array1<u64> {
     let num_bits = message_bits.len();
-    let mut rng = rand::thread_rng();
-    let mut a_flat = Vec::with_capacity(DIM * num_bits);
-    for _ in 0..(DIM * num_bits) {
-        a_flat.push(rng.gen_range(0..Q_MOD));}
-    let a_list = Array2::from_shape_vec((num_bits, DIM), a_flat).expect("Failed to reshape a_list for LWE matrix A");
-    debug!("encrypt_internal: Generated A_list matrix (first 3 rows): {:?}", a_list.rows().into_iter().take(3).collect::<Vec<_>>());
-    let mut errors_vec = Vec::with_capacity(num_bits);
-    for _ in 0..num_bits {
-        errors_vec.push(rng.gen_range(-MAGNITUDE..(MAGNITUDE + 1)));}
-    let errors = Array1::from_vec(errors_vec);
-    debug!("encrypt_internal: Generated errors vector (first 10 elements): {:?}", &errors.as_slice().unwrap()[..std::cmp::min(errors.len(), 10)]);
-    let a_sq = a_list.mapv(|x| (x as u128 * x as u128 % Q_MOD as u128) as u64);
-    let sum_a_sq: Array1<u64> = a_sq.sum_axis(Axis(1));
-    let message_bits_u64 = message_bits.mapv(|x| x as u64);
-    debug!("encrypt_internal: Message bits converted to u64: {:?}", &message_bits_u64.as_slice().unwrap()[..std::cmp::min(message_bits_u64.len(), 10)]);
-    let term3_message_encoding = message_bits_u64.mapv(|bit| (Q_MOD / 2) * bit);
-    let b_ciphertext = sum_a_sq
-        .iter()
-        .zip(errors.iter())
-        .zip(term3_message_encoding.iter())
-        .map(|((&sa_sq_val, &err_val), &term3_val)| {
-            let sa_sq_mod_q = sa_sq_val % Q_MOD;
-            let term1_plus_2_mod_q = (sa_sq_mod_q as i128 + err_val as i128 + Q_MOD as i128) % Q_MOD as i128;
-            let result = (term1_plus_2_mod_q as u128 + term3_val as u128) % Q_MOD as u128;
-            result as u64})
-        .collect::<Array1<u64>>();
-    (a_list, b_ciphertext)}
+    let cbd = CenteredBinomial::new(LATTICE_ETA).expect("LATTICE_ETA is a valid CBD parameter");
+    let s = Poly::sample(&cbd, error_bits);
+    let e = Poly::sample(&cbd, error_bits);
+    debug!("encrypt_internal: Generated errors (first 10 coefficients): {:?}", &e.centered()[..10]);
+    let m = Poly::from_message_bits(message_bits.as_slice().expect("message bits are contiguous"));
+    let b = a.pointwise_mul(&s.ntt()).inverse_ntt().add(&e).add(&m);
+    debug!("encrypt_internal: Ring-LWE output (first 10 coefficients): {:?}", &b.coeffs()[..10]);
+    b.coeffs().iter().take(num_bits).map(|&c| c as u64).collect()}
 
+/// Lattice absorption behind `--mode lattice`. A 32-byte seed is squeezed from the qosmic XOF over
+/// the whole input, and block `i` (4 KiB, zero-padded) takes its Ring-LWE `a`, secret and error from
+/// `seed || i`, so the output is reproducible and every block depends on all of the input.
+/// Each block's rotated state is XORed into the result.
 pub fn quantum_internal(input_data: &[u8]) -> Vec<u8> {
     let mut hash_value = vec![0u8; 128];
     let input_len = input_data.len();
-    let block_size: usize = 4096;
+    let block_size: usize = LATTICE_BLOCK_SIZE;
+    let seed = QosmicXof::new(LATTICE_SEED_DOMAIN, input_data).squeeze(32);
     for i in (0..input_len).step_by(block_size) {
         let end = (i + block_size).min(input_len);
+        let mut block_seed = seed.clone();
+        block_seed.extend_from_slice(&((i / block_size) as u64).to_be_bytes());
+        let mut error_bits = XofBits::new(QosmicXof::new(LATTICE_ERROR_DOMAIN, &block_seed));
         let mut block_bytes = input_data[i..end].to_vec();
         if block_bytes.len() < block_size {
             block_bytes.resize(block_size, 0x00);}
@@ -53,9 +53,10 @@ pub fn quantum_internal(input_data: &[u8]) -> Vec<u8> {
         for byte_val in &block_bytes {
             for k in 0..8 {
                 bits_unpacked.push((byte_val >> (7 - k)) & 1);}}
-        bits_unpacked.truncate(DIM);
+        bits_unpacked.truncate(RING_N);
         let bits_arr = Array1::from_vec(bits_unpacked);
-        let (_a_list, b_values) = encrypt_internal(&bits_arr);
+        let a = Poly::uniform(&mut QosmicXof::new(LATTICE_POLY_DOMAIN, &block_seed)).ntt();
+        let b_values = encrypt_internal(&bits_arr, &a, &mut error_bits);
         let mut state_u64 = Vec::with_capacity(block_size / 8);
         for chunk in block_bytes.chunks_exact(8) {
             state_u64.push(u64::from_be_bytes(chunk.try_into().unwrap()));}
@@ -63,8 +64,7 @@ pub fn quantum_internal(input_data: &[u8]) -> Vec<u8> {
         let state_arr_len = state_arr.len();
         if state_arr_len > 0 {
             for (idx, &b_val) in b_values.iter().enumerate() {
-                let effect = b_val % Q_MOD;
-                state_arr[idx % state_arr_len] ^= effect;}}
+                state_arr[idx % state_arr_len] ^= b_val;}}
         let rotated_state_arr = state_arr.mapv(|val| (val << 27) | (val >> (64 - 27)));
         for j in 0..16 {
             if j < rotated_state_arr.len() {
@@ -73,7 +73,8 @@ pub fn quantum_internal(input_data: &[u8]) -> Vec<u8> {
                 let start_index = j * 8;
                 let end_index = (j + 1) * 8;
                 if end_index <= hash_value.len() {
-                    hash_value[start_index..end_index].copy_from_slice(&bytes_to_write);}}}}
+                    for (out, byte) in hash_value[start_index..end_index].iter_mut().zip(bytes_to_write) {
+                        *out ^= byte;}}}}}
     hash_value.truncate(64);
     hash_value}
 
@@ -9,6 +9,17 @@
 extern "C" {
 #endif
 
+typedef enum {
+    QOSMIC_SUCCESS = 0,
+    QOSMIC_NULL_INPUT = 1,
+    QOSMIC_CSTRING_CONVERSION_ERROR = 2,
+    QOSMIC_HEX_DECODING_ERROR = 3,
+    QOSMIC_MEMORY_ALLOCATION_ERROR = 4,
+    QOSMIC_INVALID_PARAMETER = 5,
+    QOSMIC_INVALID_HASH_FORMAT = 6,
+    QOSMIC_COST_LIMIT_EXCEEDED = 7
+} QosmicErrorCode;
+
 /**
  * @brief Hashes input data using the qosmic algorithm and returns a hex-encoded C string.
  * The returned string is allocated by Rust and MUST be freed by calling `qosmic_free_string` to prevent memory leaks.
@@ -19,6 +30,53 @@ extern "C" {
  */
 char* qosmic_hash(const uint8_t* input_ptr, size_t input_len);
 
+/**
+ * @brief Hashes a password with PBKDF2-HMAC-Qosmic and returns a PHC-format string.
+ * The returned string MUST be freed by calling `qosmic_free_string`.
+ * @param password_ptr A pointer to the password bytes.
+ * @param password_len The length of the password.
+ * @param iterations The PBKDF2 iteration count, or 0 for the default (10,000).
+ * @param salt_len The random salt length in bytes, or 0 for the default (16).
+ * @param output_len The derived key length in bytes, or 0 for the default (64).
+ * @param output_hash_ptr Receives the PHC string, e.g. `$qosmic-pbkdf2$v=1$i=10000,l=64$<salt>$<hash>`.
+ * @return QOSMIC_SUCCESS, or QOSMIC_INVALID_PARAMETER if the parameters are out of range.
+ */
+QosmicErrorCode qosmic_hash_password(const uint8_t* password_ptr, size_t password_len, uint32_t iterations, size_t salt_len, size_t output_len, char** output_hash_ptr);
+
+/**
+ * @brief Verifies a password against a PHC string or a legacy `salt$hash` string in constant time.
+ * @param password_ptr A pointer to the password bytes.
+ * @param password_len The length of the password.
+ * @param stored_hash_ptr The null-terminated stored hash.
+ * @param result_ptr Set to 1 if the password matches, 0 otherwise.
+ * @return QOSMIC_SUCCESS, QOSMIC_INVALID_HASH_FORMAT if the stored hash cannot be parsed, or
+ *         QOSMIC_COST_LIMIT_EXCEEDED if its cost (e.g. more than 1,000,000 iterations) is above the verification limit.
+ */
+QosmicErrorCode qosmic_verify_password(const uint8_t* password_ptr, size_t password_len, const char* stored_hash_ptr, int* result_ptr);
+
+/**
+ * @brief Derives key material with HKDF-Qosmic (RFC 5869 extract-and-expand over HMAC-qosmic).
+ * The output MUST be freed by calling `qosmic_free_bytes` with the returned length.
+ * @param ikm_ptr The input keying material.
+ * @param ikm_len The length of the input keying material.
+ * @param salt_ptr The salt, or NULL for none (treated as 64 zero bytes).
+ * @param salt_len The length of the salt.
+ * @param info_ptr The context/application info, or NULL for none.
+ * @param info_len The length of the info.
+ * @param output_len_requested The desired output length in bytes, at most 255 * 64.
+ * @param derived_key_ptr Receives the output bytes.
+ * @param derived_key_actual_len_ptr Receives the output length.
+ * @return QOSMIC_SUCCESS, or QOSMIC_INVALID_PARAMETER if the output length is too large.
+ */
+QosmicErrorCode qosmic_hkdf(const uint8_t* ikm_ptr, size_t ikm_len, const uint8_t* salt_ptr, size_t salt_len, const uint8_t* info_ptr, size_t info_len, size_t output_len_requested, uint8_t** derived_key_ptr, size_t* derived_key_actual_len_ptr);
+
+/**
+ * @brief Frees a byte array returned by `qosmic_hkdf` or `qosmic_pbkdf2_hmac_qosmic`.
+ * @param ptr The byte array.
+ * @param len The length returned alongside it.
+ */
+void qosmic_free_bytes(uint8_t* ptr, size_t len);
+
 /**
  * @brief Frees a C string that was allocated by the Rust `qosmic_hash` function.
  * This function must be called to deallocate the memory for the string returned by `qosmic_hash`.
@@ -0,0 +1,177 @@
+// src/rehash.rs
+use crate::core::{parse_password_hash, KdfParams, PBKDF2_ALGORITHM, PBKDF2_VERSION};
+use crate::pepper::PEPPER_PARAM;
+use crate::memhard::{MemoryHardParams, MEMHARD_ALGORITHM, MEMHARD_VERSION};
+use crate::phc::{PasswordHashError, PhcString};
+use std::fmt;
+
+/// Minimum parameters a stored password hash must meet. Hashes made with a different algorithm
+/// than `algorithm`, or with an older version, always need rehashing. When `pepper_id` is set,
+/// hashes made without that pepper (or with a retired one) are flagged as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RehashPolicy {
+    pub algorithm: String,
+    pub min_iterations: u32,
+    pub min_memory_kib: u32,
+    pub min_time_cost: u32,
+    pub min_output_len: usize,
+    pub min_salt_len: usize,
+    pub allow_legacy: bool,
+    pub pepper_id: Option<String>,}
+
+impl Default for RehashPolicy {
+    fn default() -> Self {
+        RehashPolicy::from_kdf_params(&KdfParams::default())}}
+
+impl RehashPolicy {
+    pub fn from_kdf_params(params: &KdfParams) -> Self {
+        RehashPolicy {
+            algorithm: PBKDF2_ALGORITHM.to_string(),
+            min_iterations: params.iterations,
+            min_memory_kib: 0,
+            min_time_cost: 0,
+            min_output_len: params.output_len,
+            min_salt_len: params.salt_len,
+            allow_legacy: false,
+            pepper_id: None,}}
+
+    pub fn from_memory_hard_params(params: &MemoryHardParams) -> Self {
+        RehashPolicy {
+            algorithm: MEMHARD_ALGORITHM.to_string(),
+            min_iterations: 0,
+            min_memory_kib: params.memory_kib,
+            min_time_cost: params.time_cost,
+            min_output_len: params.output_len,
+            min_salt_len: params.salt_len,
+            allow_legacy: false,
+            pepper_id: None,}}}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RehashReason {
+    LegacyFormat,
+    Algorithm(String),
+    Version(Option<u32>),
+    Iterations(u32),
+    Memory(u32),
+    TimeCost(u32),
+    OutputLen(usize),
+    SaltLen(usize),
+    Pepper(Option<String>),}
+
+impl fmt::Display for RehashReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RehashReason::LegacyFormat => write!(f, "legacy salt$hash format"),
+            RehashReason::Algorithm(alg) => write!(f, "algorithm {}", alg),
+            RehashReason::Version(Some(v)) => write!(f, "version {}", v),
+            RehashReason::Version(None) => write!(f, "unversioned"),
+            RehashReason::Iterations(i) => write!(f, "iterations {}", i),
+            RehashReason::Memory(m) => write!(f, "memory {} KiB", m),
+            RehashReason::TimeCost(t) => write!(f, "time cost {}", t),
+            RehashReason::OutputLen(l) => write!(f, "output length {}", l),
+            RehashReason::SaltLen(s) => write!(f, "salt length {}", s),
+            RehashReason::Pepper(Some(id)) => write!(f, "pepper {}", id),
+            RehashReason::Pepper(None) => write!(f, "unpeppered"),}}}
+
+/// Lists every way `phc` falls short of `policy`. An empty list means the hash is current.
+pub fn rehash_reasons_phc(phc: &PhcString, policy: &RehashPolicy) -> Result<Vec<RehashReason>, PasswordHashError> {
+    let mut reasons = Vec::new();
+    if phc.legacy && !policy.allow_legacy {
+        reasons.push(RehashReason::LegacyFormat);}
+    if phc.algorithm != policy.algorithm {
+        reasons.push(RehashReason::Algorithm(phc.algorithm.clone()));}
+    let current_version = match phc.algorithm.as_str() {
+        PBKDF2_ALGORITHM => PBKDF2_VERSION,
+        MEMHARD_ALGORITHM => MEMHARD_VERSION,
+        other => return Err(PasswordHashError::UnsupportedAlgorithm(other.to_string())),};
+    if !phc.legacy && phc.version.is_none_or(|v| v < current_version) {
+        reasons.push(RehashReason::Version(phc.version));}
+    if phc.algorithm == PBKDF2_ALGORITHM {
+        let iterations = phc.param_u32("i")?;
+        if iterations < policy.min_iterations {
+            reasons.push(RehashReason::Iterations(iterations));}
+    } else {
+        let params = MemoryHardParams::from_phc(phc)?;
+        if params.memory_kib < policy.min_memory_kib {
+            reasons.push(RehashReason::Memory(params.memory_kib));}
+        if params.time_cost < policy.min_time_cost {
+            reasons.push(RehashReason::TimeCost(params.time_cost));}}
+    if phc.hash.len() < policy.min_output_len {
+        reasons.push(RehashReason::OutputLen(phc.hash.len()));}
+    if phc.salt.len() < policy.min_salt_len {
+        reasons.push(RehashReason::SaltLen(phc.salt.len()));}
+    if let Some(current) = &policy.pepper_id {
+        let stored_id = phc.param(PEPPER_PARAM);
+        if stored_id != Some(current.as_str()) {
+            reasons.push(RehashReason::Pepper(stored_id.map(str::to_string)));}}
+    Ok(reasons)}
+
+pub fn rehash_reasons(stored: &str, policy: &RehashPolicy) -> Result<Vec<RehashReason>, PasswordHashError> {
+    rehash_reasons_phc(&parse_password_hash(stored)?, policy)}
+
+/// Returns true when `stored` should be replaced at the next successful login. Hashes that
+/// cannot be parsed also return true, since they cannot be verified with current code either.
+pub fn needs_rehash(stored: &str, policy: &RehashPolicy) -> bool {
+    match rehash_reasons(stored, policy) {
+        Ok(reasons) => !reasons.is_empty(),
+        Err(_) => true,}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pbkdf2_hash(version: u32, iterations: u32, extra: &[(&str, &str)]) -> String {
+        let mut params = vec![("i".to_string(), iterations.to_string()), ("l".to_string(), "64".to_string())];
+        params.extend(extra.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+        PhcString::new(PBKDF2_ALGORITHM, version, params, vec![1; 16], vec![2; 64]).to_string()}
+
+    fn memory_hard_hash(memory_kib: u32) -> String {
+        let params = MemoryHardParams { memory_kib, ..MemoryHardParams::default() };
+        PhcString::new(MEMHARD_ALGORITHM, MEMHARD_VERSION, params.phc_params(), vec![1; 16], vec![2; 64]).to_string()}
+
+    #[test]
+    fn current_hash_has_no_reasons() {
+        let policy = RehashPolicy::default();
+        assert_eq!(rehash_reasons(&pbkdf2_hash(PBKDF2_VERSION, 10_000, &[]), &policy), Ok(vec![]));
+        assert_eq!(rehash_reasons(&pbkdf2_hash(PBKDF2_VERSION, 20_000, &[]), &policy), Ok(vec![]));
+        assert!(!needs_rehash(&pbkdf2_hash(PBKDF2_VERSION, 10_000, &[]), &policy));
+        let policy = RehashPolicy::from_memory_hard_params(&MemoryHardParams::default());
+        assert_eq!(rehash_reasons(&memory_hard_hash(4096), &policy), Ok(vec![]));}
+
+    #[test]
+    fn legacy_format_is_flagged() {
+        let legacy = format!("{}${}", hex::encode([1; 16]), hex::encode([2; 64]));
+        assert_eq!(rehash_reasons(&legacy, &RehashPolicy::default()), Ok(vec![RehashReason::LegacyFormat]));
+        let policy = RehashPolicy { allow_legacy: true, ..RehashPolicy::default() };
+        assert_eq!(rehash_reasons(&legacy, &policy), Ok(vec![]));}
+
+    #[test]
+    fn old_version_is_flagged() {
+        let stored = pbkdf2_hash(0, 10_000, &[]);
+        assert_eq!(rehash_reasons(&stored, &RehashPolicy::default()), Ok(vec![RehashReason::Version(Some(0))]));}
+
+    #[test]
+    fn low_iterations_are_flagged() {
+        let stored = pbkdf2_hash(PBKDF2_VERSION, 9_999, &[]);
+        assert_eq!(rehash_reasons(&stored, &RehashPolicy::default()), Ok(vec![RehashReason::Iterations(9_999)]));
+        assert!(needs_rehash(&stored, &RehashPolicy::default()));}
+
+    #[test]
+    fn low_memory_is_flagged() {
+        let policy = RehashPolicy::from_memory_hard_params(&MemoryHardParams::default());
+        assert_eq!(rehash_reasons(&memory_hard_hash(1024), &policy), Ok(vec![RehashReason::Memory(1024)]));
+        let pbkdf2 = pbkdf2_hash(PBKDF2_VERSION, 10_000, &[]);
+        assert_eq!(rehash_reasons(&pbkdf2, &policy), Ok(vec![RehashReason::Algorithm(PBKDF2_ALGORITHM.to_string())]));}
+
+    #[test]
+    fn pepper_id_mismatch_is_flagged() {
+        let policy = RehashPolicy { pepper_id: Some("2025".to_string()), ..RehashPolicy::default() };
+        assert_eq!(rehash_reasons(&pbkdf2_hash(PBKDF2_VERSION, 10_000, &[(PEPPER_PARAM, "2025")]), &policy), Ok(vec![]));
+        assert_eq!(rehash_reasons(&pbkdf2_hash(PBKDF2_VERSION, 10_000, &[(PEPPER_PARAM, "2024")]), &policy),
+            Ok(vec![RehashReason::Pepper(Some("2024".to_string()))]));
+        assert_eq!(rehash_reasons(&pbkdf2_hash(PBKDF2_VERSION, 10_000, &[]), &policy), Ok(vec![RehashReason::Pepper(None)]));}
+
+    #[test]
+    fn unparseable_hashes_need_rehash() {
+        assert!(needs_rehash("not a hash", &RehashPolicy::default()));
+        assert!(rehash_reasons("$other$v=1$x=1$AAAA$AAAA", &RehashPolicy::default()).is_err());}}
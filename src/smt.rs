@@ -0,0 +1,529 @@
+// src/smt.rs
+use crate::core::qosmic_digest;
+use crate::merkle::{node_hash, MerkleHash, HASH_LEN};
+use crate::xmss::write_state_atomically;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+pub const DEFAULT_DEPTH: usize = 256;
+pub const MAX_DEPTH: usize = 8 * HASH_LEN;
+pub const PROOF_VERSION: u8 = 1;
+const LEAF_PREFIX: u8 = 0x00;
+const STORE_MAGIC: &[u8; 4] = b"QSMT";
+const STORE_VERSION: u8 = 1;
+const BATCH_CHECKSUM_LEN: usize = 16;
+const RECORD_NODE: u8 = 1;
+const RECORD_VALUE: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SmtError {
+    InvalidDepth(usize),
+    InvalidEncoding(&'static str),
+    UnsupportedVersion(u8),
+    Corrupt(String),
+    Io(String),}
+
+impl fmt::Display for SmtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmtError::InvalidDepth(d) => write!(f, "tree depth {} is not a multiple of 8 in 8..={}", d, MAX_DEPTH),
+            SmtError::InvalidEncoding(what) => write!(f, "malformed {}", what),
+            SmtError::UnsupportedVersion(v) => write!(f, "unsupported sparse Merkle tree version {}", v),
+            SmtError::Corrupt(e) => write!(f, "sparse Merkle tree store is corrupt: {}", e),
+            SmtError::Io(e) => write!(f, "I/O error: {}", e),}}}
+
+impl std::error::Error for SmtError {}
+
+impl From<io::Error> for SmtError {
+    fn from(e: io::Error) -> Self {
+        SmtError::Io(e.to_string())}}
+
+/// Backing storage for a `SparseMerkleTree`: non-default node hashes (leaves included) and the
+/// values at leaves.
+/// `None` deletes. The tree calls `commit` after every batch, so a store can make each batch
+/// durable as a unit.
+pub trait SmtStore {
+    fn get_node(&self, key: &[u8]) -> Result<Option<MerkleHash>, SmtError>;
+    fn put_node(&mut self, key: &[u8], hash: Option<MerkleHash>) -> Result<(), SmtError>;
+    fn get_value(&self, path: &[u8]) -> Result<Option<Vec<u8>>, SmtError>;
+    fn put_value(&mut self, path: &[u8], value: Option<&[u8]>) -> Result<(), SmtError>;
+    fn commit(&mut self) -> Result<(), SmtError> {
+        Ok(())}}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    nodes: HashMap<Vec<u8>, MerkleHash>,
+    values: HashMap<Vec<u8>, Vec<u8>>,}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()}
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()}}
+
+impl SmtStore for MemoryStore {
+    fn get_node(&self, key: &[u8]) -> Result<Option<MerkleHash>, SmtError> {
+        Ok(self.nodes.get(key).copied())}
+
+    fn put_node(&mut self, key: &[u8], hash: Option<MerkleHash>) -> Result<(), SmtError> {
+        match hash {
+            Some(hash) => self.nodes.insert(key.to_vec(), hash),
+            None => self.nodes.remove(key),};
+        Ok(())}
+
+    fn get_value(&self, path: &[u8]) -> Result<Option<Vec<u8>>, SmtError> {
+        Ok(self.values.get(path).cloned())}
+
+    fn put_value(&mut self, path: &[u8], value: Option<&[u8]>) -> Result<(), SmtError> {
+        match value {
+            Some(value) => self.values.insert(path.to_vec(), value.to_vec()),
+            None => self.values.remove(path),};
+        Ok(())}}
+
+fn put_record(out: &mut Vec<u8>, kind: u8, key: &[u8], value: Option<&[u8]>) {
+    out.push(kind);
+    out.extend_from_slice(&(key.len() as u16).to_be_bytes());
+    out.extend_from_slice(key);
+    match value {
+        Some(value) => {
+            out.push(1);
+            out.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            out.extend_from_slice(value);},
+        None => out.push(0),}}
+
+/// A `MemoryStore` mirrored to a journal file. The file is `"QSMT" || version (0x01)` followed by
+/// batches, each `length (u32) || records || first 16 bytes of qosmic(records)`, where a record is
+/// `kind (u8: 1 node, 2 value) || key length (u16) || key || present (u8)` and, if present,
+/// `value length (u32) || value`. `open` replays every complete batch and drops a torn last one,
+/// so a crash mid-`commit` loses that batch and nothing else. `compact` rewrites the journal as a
+/// single batch.
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+    memory: MemoryStore,
+    pending: Vec<u8>,}
+
+impl FileStore {
+    pub fn open(path: &Path) -> Result<Self, SmtError> {
+        let start = Instant::now();
+        let mut store = FileStore { path: path.to_path_buf(), memory: MemoryStore::new(), pending: Vec::new() };
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut header = STORE_MAGIC.to_vec();
+                header.push(STORE_VERSION);
+                write_state_atomically(path, &header).map_err(|e| SmtError::Io(e.to_string()))?;
+                return Ok(store);},
+            Err(e) => return Err(e.into()),};
+        if bytes.len() < 5 || &bytes[..4] != STORE_MAGIC {
+            return Err(SmtError::InvalidEncoding("sparse Merkle tree store"));}
+        if bytes[4] != STORE_VERSION {
+            return Err(SmtError::UnsupportedVersion(bytes[4]));}
+        let mut pos = 5;
+        let mut batches = 0;
+        while pos < bytes.len() {
+            let Some(len_bytes) = bytes.get(pos..pos + 4) else { break };
+            let len = u32::from_be_bytes(len_bytes.try_into().unwrap()) as usize;
+            let Some(records) = bytes.get(pos + 4..pos + 4 + len) else { break };
+            let Some(checksum) = bytes.get(pos + 4 + len..pos + 4 + len + BATCH_CHECKSUM_LEN) else { break };
+            if qosmic_digest(records)[..BATCH_CHECKSUM_LEN] != *checksum {
+                break;}
+            store.replay(records)?;
+            pos += 4 + len + BATCH_CHECKSUM_LEN;
+            batches += 1;}
+        if pos < bytes.len() {
+            warn!("Dropping a torn batch at the end of {}", path.display());
+            OpenOptions::new().write(true).open(path)?.set_len(pos as u64)?;}
+        info!("Replayed {} batch(es) from {} in {:?}", batches, path.display(), start.elapsed());
+        Ok(store)}
+
+    fn replay(&mut self, mut records: &[u8]) -> Result<(), SmtError> {
+        let corrupt = || SmtError::Corrupt("record runs past the end of its batch".to_string());
+        while !records.is_empty() {
+            let kind = records[0];
+            let key_len = u16::from_be_bytes(records.get(1..3).ok_or_else(corrupt)?.try_into().unwrap()) as usize;
+            let key = records.get(3..3 + key_len).ok_or_else(corrupt)?;
+            let mut rest = &records[3 + key_len..];
+            let value = match rest.first() {
+                Some(0) => {
+                    rest = &rest[1..];
+                    None},
+                Some(1) => {
+                    let value_len = u32::from_be_bytes(rest.get(1..5).ok_or_else(corrupt)?.try_into().unwrap()) as usize;
+                    let value = rest.get(5..5 + value_len).ok_or_else(corrupt)?;
+                    rest = &rest[5 + value_len..];
+                    Some(value)},
+                _ => return Err(corrupt()),};
+            match kind {
+                RECORD_NODE => {
+                    let hash = match value {
+                        Some(v) => Some(v.try_into().map_err(|_| SmtError::Corrupt("node hash has the wrong length".to_string()))?),
+                        None => None,};
+                    self.memory.put_node(key, hash)?},
+                RECORD_VALUE => self.memory.put_value(key, value)?,
+                _ => return Err(SmtError::Corrupt(format!("unknown record kind {}", kind))),}
+            records = rest;}
+        Ok(())}
+
+    /// Rewrites the journal with only the live nodes and values.
+    pub fn compact(&mut self) -> Result<(), SmtError> {
+        self.commit()?;
+        let mut records = Vec::new();
+        for (key, hash) in &self.memory.nodes {
+            put_record(&mut records, RECORD_NODE, key, Some(hash));}
+        for (path, value) in &self.memory.values {
+            put_record(&mut records, RECORD_VALUE, path, Some(value));}
+        let mut out = STORE_MAGIC.to_vec();
+        out.push(STORE_VERSION);
+        if !records.is_empty() {
+            out.extend_from_slice(&(records.len() as u32).to_be_bytes());
+            out.extend_from_slice(&records);
+            out.extend_from_slice(&qosmic_digest(&records)[..BATCH_CHECKSUM_LEN]);}
+        write_state_atomically(&self.path, &out).map_err(|e| SmtError::Io(e.to_string()))?;
+        info!("Compacted {} to {} bytes", self.path.display(), out.len());
+        Ok(())}}
+
+impl SmtStore for FileStore {
+    fn get_node(&self, key: &[u8]) -> Result<Option<MerkleHash>, SmtError> {
+        self.memory.get_node(key)}
+
+    fn put_node(&mut self, key: &[u8], hash: Option<MerkleHash>) -> Result<(), SmtError> {
+        put_record(&mut self.pending, RECORD_NODE, key, hash.as_ref().map(|h| &h[..]));
+        self.memory.put_node(key, hash)}
+
+    fn get_value(&self, path: &[u8]) -> Result<Option<Vec<u8>>, SmtError> {
+        self.memory.get_value(path)}
+
+    fn put_value(&mut self, path: &[u8], value: Option<&[u8]>) -> Result<(), SmtError> {
+        put_record(&mut self.pending, RECORD_VALUE, path, value);
+        self.memory.put_value(path, value)}
+
+    fn commit(&mut self) -> Result<(), SmtError> {
+        if self.pending.is_empty() {
+            return Ok(());}
+        let len = u32::try_from(self.pending.len()).map_err(|_| SmtError::InvalidEncoding("batch (too large)"))?;
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(&len.to_be_bytes())?;
+        file.write_all(&self.pending)?;
+        file.write_all(&qosmic_digest(&self.pending)[..BATCH_CHECKSUM_LEN])?;
+        file.sync_all()?;
+        debug!("Committed a {}-byte batch to {}", self.pending.len(), self.path.display());
+        self.pending.clear();
+        Ok(())}}
+
+/// Bit `index` of `path`, most significant bit of the first byte first.
+fn bit(path: &[u8], index: usize) -> bool {
+    path[index / 8] >> (7 - index % 8) & 1 == 1}
+
+/// `qosmic(0x00 || path || value)`. The path ties a value to its position in the tree.
+fn value_leaf_hash(path: &[u8], value: &[u8]) -> MerkleHash {
+    let mut input = Vec::with_capacity(1 + path.len() + value.len());
+    input.push(LEAF_PREFIX);
+    input.extend_from_slice(path);
+    input.extend_from_slice(value);
+    qosmic_digest(&input)}
+
+/// Hashes of the all-empty subtrees: `defaults[0] = qosmic(0x00)` for an empty leaf and
+/// `defaults[h + 1] = qosmic(0x01 || defaults[h] || defaults[h])`.
+fn default_hashes(depth: usize) -> Vec<MerkleHash> {
+    let mut defaults = Vec::with_capacity(depth + 1);
+    defaults.push(qosmic_digest(&[LEAF_PREFIX]));
+    for height in 0..depth {
+        let below = defaults[height];
+        defaults.push(node_hash(&below, &below));}
+    defaults}
+
+/// Sparse Merkle tree over `2^depth` leaves: key `k` sits at the leaf addressed by the first
+/// `depth` bits of `qosmic(k)`, and every other leaf is empty. Only nodes that differ from the
+/// all-empty subtree of their height are stored, so the tree is as large as its contents. Proofs
+/// cover both membership and non-membership and omit siblings that are default hashes.
+///
+/// Each update rehashes `depth` nodes (about 9 ms at the default depth of 256), and the store
+/// keeps up to `depth + 1` nodes per key. Batches share the work on common path prefixes.
+#[derive(Debug)]
+pub struct SparseMerkleTree<S: SmtStore = MemoryStore> {
+    depth: usize,
+    defaults: Vec<MerkleHash>,
+    store: S,}
+
+impl SparseMerkleTree<MemoryStore> {
+    pub fn in_memory(depth: usize) -> Result<Self, SmtError> {
+        SparseMerkleTree::new(MemoryStore::new(), depth)}}
+
+impl<S: SmtStore> SparseMerkleTree<S> {
+    /// Uses `store`, which may already hold a tree of the same depth.
+    pub fn new(store: S, depth: usize) -> Result<Self, SmtError> {
+        if depth == 0 || depth > MAX_DEPTH || !depth.is_multiple_of(8) {
+            return Err(SmtError::InvalidDepth(depth));}
+        Ok(SparseMerkleTree { depth, defaults: default_hashes(depth), store })}
+
+    pub fn depth(&self) -> usize {
+        self.depth}
+
+    pub fn store(&self) -> &S {
+        &self.store}
+
+    pub fn into_store(self) -> S {
+        self.store}
+
+    /// Leaf address of `key`: the first `depth / 8` bytes of its qosmic digest.
+    pub fn path_of(&self, key: &[u8]) -> Vec<u8> {
+        qosmic_digest(key)[..self.depth / 8].to_vec()}
+
+    /// Store key of the node at `height` above the leaves on `path`: `height (u16) || path` with
+    /// the bits below that node cleared.
+    fn node_key(&self, height: usize, path: &[u8]) -> Vec<u8> {
+        let mut key = Vec::with_capacity(2 + path.len());
+        key.extend_from_slice(&(height as u16).to_be_bytes());
+        key.extend_from_slice(path);
+        let kept = self.depth - height;
+        for (i, byte) in key[2..].iter_mut().enumerate() {
+            let first_bit = i * 8;
+            if first_bit >= kept {
+                *byte = 0;
+            } else if first_bit + 8 > kept {
+                *byte &= 0xffu8 << (first_bit + 8 - kept);}}
+        key}
+
+    fn node(&self, height: usize, path: &[u8]) -> Result<MerkleHash, SmtError> {
+        Ok(self.store.get_node(&self.node_key(height, path))?.unwrap_or(self.defaults[height]))}
+
+    pub fn root(&self) -> Result<MerkleHash, SmtError> {
+        self.node(self.depth, &vec![0u8; self.depth / 8])}
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, SmtError> {
+        self.store.get_value(&self.path_of(key))}
+
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<MerkleHash, SmtError> {
+        self.update_batch(&[(key, Some(value))])}
+
+    pub fn remove(&mut self, key: &[u8]) -> Result<MerkleHash, SmtError> {
+        self.update_batch(&[(key, None::<&[u8]>)])}
+
+    /// Applies all updates (`None` removes the key; for a repeated key the last update wins),
+    /// commits the store once and returns the new root.
+    pub fn update_batch<K: AsRef<[u8]>, V: AsRef<[u8]>>(&mut self, updates: &[(K, Option<V>)]) -> Result<MerkleHash, SmtError> {
+        let start = Instant::now();
+        let mut by_path: HashMap<Vec<u8>, Option<&[u8]>> = HashMap::with_capacity(updates.len());
+        for (key, value) in updates {
+            by_path.insert(self.path_of(key.as_ref()), value.as_ref().map(|v| v.as_ref()));}
+        let mut items: Vec<(Vec<u8>, Option<&[u8]>)> = by_path.into_iter().collect();
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        let root = self.update_subtree(self.depth, &items)?;
+        self.store.commit()?;
+        debug!("Sparse Merkle tree batch of {} update(s) took {:?}", items.len(), start.elapsed());
+        Ok(root)}
+
+    /// Rewrites the subtree at `height` containing every path in `items` (sorted, non-empty, all
+    /// sharing the bits above `height`) and returns its new hash.
+    fn update_subtree(&mut self, height: usize, items: &[(Vec<u8>, Option<&[u8]>)]) -> Result<MerkleHash, SmtError> {
+        let path = &items[0].0;
+        let hash = if height == 0 {
+            let (path, value) = &items[0];
+            self.store.put_value(path, *value)?;
+            match value {
+                Some(value) => value_leaf_hash(path, value),
+                None => self.defaults[0],}
+        } else {
+            let split = items.partition_point(|(p, _)| !bit(p, self.depth - height));
+            let (left_items, right_items) = items.split_at(split);
+            let left = if left_items.is_empty() {
+                let mut sibling = path.clone();
+                sibling[(self.depth - height) / 8] &= !(0x80u8 >> ((self.depth - height) % 8));
+                self.node(height - 1, &sibling)?
+            } else {
+                self.update_subtree(height - 1, left_items)?};
+            let right = if right_items.is_empty() {
+                let mut sibling = path.clone();
+                sibling[(self.depth - height) / 8] |= 0x80u8 >> ((self.depth - height) % 8);
+                self.node(height - 1, &sibling)?
+            } else {
+                self.update_subtree(height - 1, right_items)?};
+            if left == self.defaults[height - 1] && right == self.defaults[height - 1] {
+                self.defaults[height]
+            } else {
+                node_hash(&left, &right)}};
+        let stored = if hash == self.defaults[height] { None } else { Some(hash) };
+        self.store.put_node(&self.node_key(height, path), stored)?;
+        Ok(hash)}
+
+    /// Proof for `key`, valid for membership if the key is present and non-membership otherwise.
+    pub fn prove(&self, key: &[u8]) -> Result<SmtProof, SmtError> {
+        let path = self.path_of(key);
+        let mut bitmap = vec![0u8; self.depth / 8];
+        let mut siblings = Vec::new();
+        for height in 0..self.depth {
+            let mut sibling_path = path.clone();
+            let index = self.depth - 1 - height;
+            sibling_path[index / 8] ^= 0x80u8 >> (index % 8);
+            let sibling = self.node(height, &sibling_path)?;
+            if sibling != self.defaults[height] {
+                bitmap[height / 8] |= 0x80u8 >> (height % 8);
+                siblings.push(sibling);}}
+        Ok(SmtProof { depth: self.depth, bitmap, siblings })}}
+
+/// Sibling hashes from the leaf up, with a bitmap (bit `h` for height `h`) marking which siblings
+/// are present; the rest are default hashes and are left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmtProof {
+    pub depth: usize,
+    pub bitmap: Vec<u8>,
+    pub siblings: Vec<MerkleHash>,}
+
+impl SmtProof {
+    /// With `Some(value)`, checks that `key` maps to `value` under `root`; with `None`, that
+    /// `key` is absent.
+    pub fn verify(&self, root: &MerkleHash, key: &[u8], value: Option<&[u8]>) -> bool {
+        let depth = self.depth;
+        if depth == 0 || depth > MAX_DEPTH || !depth.is_multiple_of(8) || self.bitmap.len() != depth / 8 {
+            return false;}
+        let present = self.bitmap.iter().map(|b| b.count_ones() as usize).sum::<usize>();
+        if present != self.siblings.len() {
+            return false;}
+        let defaults = default_hashes(depth);
+        let path = &qosmic_digest(key)[..depth / 8];
+        let mut hash = match value {
+            Some(value) => value_leaf_hash(path, value),
+            None => defaults[0],};
+        let mut siblings = self.siblings.iter();
+        for height in 0..depth {
+            let sibling = if bit(&self.bitmap, height) {
+                *siblings.next().expect("sibling count matches the bitmap")
+            } else {
+                defaults[height]};
+            hash = if hash == defaults[height] && sibling == defaults[height] {
+                defaults[height + 1]
+            } else if bit(path, depth - 1 - height) {
+                node_hash(&sibling, &hash)
+            } else {
+                node_hash(&hash, &sibling)};}
+        hash == *root}
+
+    /// `version (0x01) || depth (u16) || bitmap (depth / 8 bytes) || present siblings, leaf level first`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(3 + self.bitmap.len() + self.siblings.len() * HASH_LEN);
+        out.push(PROOF_VERSION);
+        out.extend_from_slice(&(self.depth as u16).to_be_bytes());
+        out.extend_from_slice(&self.bitmap);
+        for sibling in &self.siblings {
+            out.extend_from_slice(sibling);}
+        out}
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SmtError> {
+        if bytes.len() < 3 {
+            return Err(SmtError::InvalidEncoding("sparse Merkle proof"));}
+        if bytes[0] != PROOF_VERSION {
+            return Err(SmtError::UnsupportedVersion(bytes[0]));}
+        let depth = u16::from_be_bytes([bytes[1], bytes[2]]) as usize;
+        if depth == 0 || depth > MAX_DEPTH || !depth.is_multiple_of(8) {
+            return Err(SmtError::InvalidDepth(depth));}
+        let bitmap = bytes.get(3..3 + depth / 8).ok_or(SmtError::InvalidEncoding("sparse Merkle proof"))?.to_vec();
+        let rest = &bytes[3 + depth / 8..];
+        let present = bitmap.iter().map(|b| b.count_ones() as usize).sum::<usize>();
+        if rest.len() != present * HASH_LEN {
+            return Err(SmtError::InvalidEncoding("sparse Merkle proof"));}
+        let siblings = rest.chunks_exact(HASH_LEN).map(|chunk| chunk.try_into().unwrap()).collect();
+        Ok(SmtProof { depth, bitmap, siblings })}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(n: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        (0..n).map(|i| (format!("key {}", i).into_bytes(), format!("value {}", i).into_bytes())).collect()}
+
+    #[test]
+    fn membership_and_non_membership_proofs() {
+        let mut tree = SparseMerkleTree::in_memory(DEFAULT_DEPTH).unwrap();
+        assert_eq!(tree.root().unwrap(), default_hashes(DEFAULT_DEPTH)[DEFAULT_DEPTH]);
+        let absent = tree.prove(b"missing").unwrap();
+        assert!(absent.siblings.is_empty());
+        assert!(absent.verify(&tree.root().unwrap(), b"missing", None));
+        for (key, value) in entries(8) {
+            tree.insert(&key, &value).unwrap();}
+        let root = tree.root().unwrap();
+        for (key, value) in entries(8) {
+            assert_eq!(tree.get(&key).unwrap(), Some(value.clone()));
+            let proof = tree.prove(&key).unwrap();
+            assert!(proof.verify(&root, &key, Some(&value)));
+            assert!(!proof.verify(&root, &key, Some(b"other value")));
+            assert!(!proof.verify(&root, &key, None));
+            assert_eq!(SmtProof::from_bytes(&proof.to_bytes()).unwrap(), proof);}
+        let proof = tree.prove(b"missing").unwrap();
+        assert!(proof.verify(&root, b"missing", None));
+        assert!(!proof.verify(&root, b"missing", Some(b"value 0")));
+        assert!(!proof.verify(&root, b"key 0", None));
+        let mut altered = tree.prove(b"key 3").unwrap();
+        altered.siblings[0][0] ^= 1;
+        assert!(!altered.verify(&root, b"key 3", Some(b"value 3")));}
+
+    #[test]
+    fn batches_match_single_updates_and_removal_restores_the_empty_tree() {
+        let data = entries(20);
+        let mut one_by_one = SparseMerkleTree::in_memory(32).unwrap();
+        for (key, value) in &data {
+            one_by_one.insert(key, value).unwrap();}
+        let mut batched = SparseMerkleTree::in_memory(32).unwrap();
+        let updates: Vec<(Vec<u8>, Option<Vec<u8>>)> = data.iter().rev().map(|(k, v)| (k.clone(), Some(v.clone()))).collect();
+        assert_eq!(batched.update_batch(&updates).unwrap(), one_by_one.root().unwrap());
+        assert_eq!(batched.update_batch(&[(&b"key 0"[..], Some(&b"first"[..])), (b"key 0", Some(b"last"))]).unwrap(), batched.root().unwrap());
+        assert_eq!(batched.get(b"key 0").unwrap(), Some(b"last".to_vec()));
+
+        let empty = SparseMerkleTree::in_memory(32).unwrap().root().unwrap();
+        let removals: Vec<(Vec<u8>, Option<Vec<u8>>)> = data.iter().map(|(k, _)| (k.clone(), None)).collect();
+        assert_eq!(batched.update_batch(&removals).unwrap(), empty);
+        assert_eq!(batched.store().node_count(), 0);
+        assert_eq!(batched.get(b"key 0").unwrap(), None);
+        assert_eq!(SparseMerkleTree::in_memory(12).err(), Some(SmtError::InvalidDepth(12)));}
+
+    #[test]
+    fn proof_encoding_rejects_bad_input() {
+        let mut tree = SparseMerkleTree::in_memory(32).unwrap();
+        tree.insert(b"a", b"1").unwrap();
+        tree.insert(b"b", b"2").unwrap();
+        let bytes = tree.prove(b"a").unwrap().to_bytes();
+        assert_eq!(SmtProof::from_bytes(&bytes[..bytes.len() - 1]), Err(SmtError::InvalidEncoding("sparse Merkle proof")));
+        let mut depth = bytes.clone();
+        depth[2] = 12;
+        assert_eq!(SmtProof::from_bytes(&depth), Err(SmtError::InvalidDepth(12)));
+        let mut version = bytes.clone();
+        version[0] = 2;
+        assert_eq!(SmtProof::from_bytes(&version), Err(SmtError::UnsupportedVersion(2)));}
+
+    #[test]
+    fn file_store_replays_compacts_and_drops_torn_batches() {
+        let path = std::env::temp_dir().join(format!("qosmic-smt-test-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let data = entries(6);
+        let mut tree = SparseMerkleTree::new(FileStore::open(&path).unwrap(), 32).unwrap();
+        for (key, value) in &data {
+            tree.insert(key, value).unwrap();}
+        tree.remove(b"key 5").unwrap();
+        let root = tree.root().unwrap();
+        drop(tree);
+
+        let mut tree = SparseMerkleTree::new(FileStore::open(&path).unwrap(), 32).unwrap();
+        assert_eq!(tree.root().unwrap(), root);
+        assert_eq!(tree.get(b"key 2").unwrap(), Some(b"value 2".to_vec()));
+        assert_eq!(tree.get(b"key 5").unwrap(), None);
+        let journal_len = fs::metadata(&path).unwrap().len();
+        let mut store = tree.into_store();
+        store.compact().unwrap();
+        assert!(fs::metadata(&path).unwrap().len() < journal_len);
+        tree = SparseMerkleTree::new(FileStore::open(&path).unwrap(), 32).unwrap();
+        assert_eq!(tree.root().unwrap(), root);
+
+        // A batch cut off mid-write is dropped on the next open, leaving the earlier state.
+        tree.insert(b"late", b"write").unwrap();
+        let full_len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(full_len - 3).unwrap();
+        let tree = SparseMerkleTree::new(FileStore::open(&path).unwrap(), 32).unwrap();
+        assert_eq!(tree.root().unwrap(), root);
+        assert_eq!(tree.get(b"late").unwrap(), None);
+        fs::remove_file(&path).unwrap();}}
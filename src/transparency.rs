@@ -0,0 +1,390 @@
+// src/transparency.rs
+use crate::core::HmacQosmicKey;
+use crate::mac::TAG_LEN;
+use crate::merkle::{self, ConsistencyProof, InclusionProof, MerkleError, MerkleHash, MerkleTree, HASH_LEN};
+use crate::xmss::{SignatureError, XmssPrivateKey, XmssPublicKey, XmssSignature};
+use hex;
+use log::{debug, info, warn};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+pub const HEAD_MAGIC: &[u8; 4] = b"QSTH";
+pub const HEAD_VERSION: u8 = 1;
+const HEAD_LEN: usize = 4 + 1 + 8 + 8 + HASH_LEN;
+const SIG_HMAC: u8 = 1;
+const SIG_XMSS: u8 = 2;
+/// Length-prefixed entries, in log order.
+pub const ENTRIES_FILE: &str = "entries";
+/// One 64-byte Merkle leaf hash per entry, so the tree can be rebuilt without rehashing entries.
+pub const LEAVES_FILE: &str = "leaves";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogError {
+    Merkle(MerkleError),
+    Corrupt(String),
+    InvalidEncoding(&'static str),
+    UnsupportedVersion(u8),
+    BadSignature,
+    ProofMismatch(&'static str),
+    Signature(SignatureError),
+    Io(String),}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogError::Merkle(e) => write!(f, "{}", e),
+            LogError::Corrupt(e) => write!(f, "log storage is corrupt: {}", e),
+            LogError::InvalidEncoding(what) => write!(f, "malformed {}", what),
+            LogError::UnsupportedVersion(v) => write!(f, "unsupported tree head version {}", v),
+            LogError::BadSignature => write!(f, "tree head signature does not verify"),
+            LogError::ProofMismatch(what) => write!(f, "{} does not verify", what),
+            LogError::Signature(e) => write!(f, "signing failed: {}", e),
+            LogError::Io(e) => write!(f, "I/O error: {}", e),}}}
+
+impl std::error::Error for LogError {}
+
+impl From<MerkleError> for LogError {
+    fn from(e: MerkleError) -> Self {
+        LogError::Merkle(e)}}
+
+impl From<SignatureError> for LogError {
+    fn from(e: SignatureError) -> Self {
+        LogError::Signature(e)}}
+
+impl From<io::Error> for LogError {
+    fn from(e: io::Error) -> Self {
+        LogError::Io(e.to_string())}}
+
+/// The log's state at one moment: how many entries it had and their Merkle root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeHead {
+    pub tree_size: u64,
+    /// Unix seconds at which the head was produced.
+    pub timestamp: u64,
+    pub root: MerkleHash,}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeadSignature {
+    Hmac([u8; TAG_LEN]),
+    Xmss(XmssSignature),}
+
+/// How a signed tree head is checked: a shared HMAC key or the log's XMSS public key.
+#[derive(Debug, Clone, Copy)]
+pub enum HeadVerifier<'a> {
+    Hmac(&'a [u8]),
+    Xmss(&'a XmssPublicKey),}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTreeHead {
+    pub head: TreeHead,
+    pub signature: HeadSignature,}
+
+impl TreeHead {
+    /// The signed message: `"QSTH" || version (0x01) || tree_size (u64) || timestamp (u64) || root`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEAD_LEN);
+        out.extend_from_slice(HEAD_MAGIC);
+        out.push(HEAD_VERSION);
+        out.extend_from_slice(&self.tree_size.to_be_bytes());
+        out.extend_from_slice(&self.timestamp.to_be_bytes());
+        out.extend_from_slice(&self.root);
+        out}
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, LogError> {
+        if bytes.len() != HEAD_LEN || &bytes[..4] != HEAD_MAGIC {
+            return Err(LogError::InvalidEncoding("tree head"));}
+        if bytes[4] != HEAD_VERSION {
+            return Err(LogError::UnsupportedVersion(bytes[4]));}
+        Ok(TreeHead {
+            tree_size: u64::from_be_bytes(bytes[5..13].try_into().unwrap()),
+            timestamp: u64::from_be_bytes(bytes[13..21].try_into().unwrap()),
+            root: bytes[21..].try_into().unwrap(),})}
+
+    pub fn sign_hmac(self, key: &[u8]) -> SignedTreeHead {
+        let tag = HmacQosmicKey::new(key).mac(&self.to_bytes());
+        SignedTreeHead { head: self, signature: HeadSignature::Hmac(tag) }}
+
+    /// Signs with the next one-time key of `key`; the caller must persist the key's new state.
+    pub fn sign_xmss(self, key: &mut XmssPrivateKey) -> Result<SignedTreeHead, LogError> {
+        let signature = key.sign(&self.to_bytes())?;
+        Ok(SignedTreeHead { head: self, signature: HeadSignature::Xmss(signature) })}}
+
+impl SignedTreeHead {
+    pub fn verify(&self, verifier: HeadVerifier) -> bool {
+        let message = self.head.to_bytes();
+        match (&self.signature, verifier) {
+            (HeadSignature::Hmac(tag), HeadVerifier::Hmac(key)) => HmacQosmicKey::new(key).verify(&message, tag, TAG_LEN),
+            (HeadSignature::Xmss(signature), HeadVerifier::Xmss(public_key)) => public_key.verify(&message, signature),
+            _ => false,}}
+
+    /// `tree head || signature type (u8: 1 HMAC-qosmic, 2 XMSS) || signature length (u32) || signature`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.head.to_bytes();
+        let (kind, signature) = match &self.signature {
+            HeadSignature::Hmac(tag) => (SIG_HMAC, tag.to_vec()),
+            HeadSignature::Xmss(signature) => (SIG_XMSS, signature.to_bytes()),};
+        out.push(kind);
+        out.extend_from_slice(&(signature.len() as u32).to_be_bytes());
+        out.extend_from_slice(&signature);
+        out}
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LogError> {
+        if bytes.len() < HEAD_LEN + 5 {
+            return Err(LogError::InvalidEncoding("signed tree head"));}
+        let head = TreeHead::from_bytes(&bytes[..HEAD_LEN])?;
+        let signature_len = u32::from_be_bytes(bytes[HEAD_LEN + 1..HEAD_LEN + 5].try_into().unwrap()) as usize;
+        let signature = &bytes[HEAD_LEN + 5..];
+        if signature.len() != signature_len {
+            return Err(LogError::InvalidEncoding("signed tree head"));}
+        let signature = match bytes[HEAD_LEN] {
+            SIG_HMAC => HeadSignature::Hmac(signature.try_into().map_err(|_| LogError::InvalidEncoding("tree head HMAC tag"))?),
+            SIG_XMSS => HeadSignature::Xmss(XmssSignature::from_bytes(signature).map_err(|_| LogError::InvalidEncoding("tree head XMSS signature"))?),
+            _ => return Err(LogError::InvalidEncoding("tree head signature type")),};
+        Ok(SignedTreeHead { head, signature })}}
+
+/// Checks, without the log, that `entry` is leaf `proof.leaf_index` of the tree behind `head`.
+pub fn verify_inclusion(head: &SignedTreeHead, verifier: HeadVerifier, entry: &[u8], proof: &InclusionProof) -> Result<(), LogError> {
+    if !head.verify(verifier) {
+        return Err(LogError::BadSignature);}
+    if proof.tree_size != head.head.tree_size || !proof.verify(entry, &head.head.root) {
+        return Err(LogError::ProofMismatch("inclusion proof"));}
+    Ok(())}
+
+/// Checks, without the log, that the tree behind `old` is a prefix of the tree behind `new`: the
+/// log only appended between the two heads.
+pub fn verify_consistency(old: &SignedTreeHead, new: &SignedTreeHead, verifier: HeadVerifier, proof: &ConsistencyProof) -> Result<(), LogError> {
+    if !old.verify(verifier) || !new.verify(verifier) {
+        return Err(LogError::BadSignature);}
+    if proof.old_size != old.head.tree_size || proof.new_size != new.head.tree_size || !proof.verify(&old.head.root, &new.head.root) {
+        return Err(LogError::ProofMismatch("consistency proof"));}
+    Ok(())}
+
+/// Reads one `[len u32 BE][data]` record; `Ok(None)` at a clean end of file or a torn final record.
+fn read_record<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len_bytes = [0u8; 4];
+    match reader.read_exact(&mut len_bytes) {
+        Ok(()) => {},
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),}
+    let mut data = vec![0u8; u32::from_be_bytes(len_bytes) as usize];
+    match reader.read_exact(&mut data) {
+        Ok(()) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),}}
+
+/// An append-only log in the style of RFC 6962 Certificate Transparency, with qosmic as the hash.
+/// Entries live in `dir/entries` and their leaf hashes in `dir/leaves`; both files only ever grow.
+/// An entry is written and synced before its leaf hash, so after a crash `open` can finish a
+/// half-done append by hashing the entries that lack a leaf.
+#[derive(Debug)]
+pub struct TransparencyLog {
+    dir: PathBuf,
+    tree: MerkleTree,}
+
+impl TransparencyLog {
+    /// Opens the log in `dir`, creating an empty one if the directory has none.
+    pub fn open(dir: &Path) -> Result<Self, LogError> {
+        fs::create_dir_all(dir)?;
+        let entries_path = dir.join(ENTRIES_FILE);
+        let leaves_path = dir.join(LEAVES_FILE);
+        OpenOptions::new().create(true).append(true).open(&entries_path)?;
+        OpenOptions::new().create(true).append(true).open(&leaves_path)?;
+        let leaf_bytes = fs::read(&leaves_path)?;
+        if leaf_bytes.len() % HASH_LEN != 0 {
+            warn!("Dropping a torn leaf hash at the end of {}", leaves_path.display());
+            OpenOptions::new().write(true).open(&leaves_path)?.set_len((leaf_bytes.len() - leaf_bytes.len() % HASH_LEN) as u64)?;}
+        let mut leaves: Vec<MerkleHash> = leaf_bytes.chunks_exact(HASH_LEN).map(|chunk| chunk.try_into().unwrap()).collect();
+        let mut reader = BufReader::new(File::open(&entries_path)?);
+        let mut entry_count = 0usize;
+        let mut complete_len = 0u64;
+        let mut missing = Vec::new();
+        while let Some(entry) = read_record(&mut reader)? {
+            if entry_count >= leaves.len() {
+                missing.push(merkle::leaf_hash(&entry));}
+            entry_count += 1;
+            complete_len += 4 + entry.len() as u64;}
+        if complete_len != fs::metadata(&entries_path)?.len() {
+            warn!("Dropping a torn entry at the end of {}", entries_path.display());
+            OpenOptions::new().write(true).open(&entries_path)?.set_len(complete_len)?;}
+        if leaves.len() > entry_count {
+            return Err(LogError::Corrupt(format!("{} leaf hashes for {} entries", leaves.len(), entry_count)));}
+        if !missing.is_empty() {
+            warn!("Recovering {} leaf hash(es) missing after an interrupted append", missing.len());
+            let mut file = OpenOptions::new().append(true).open(&leaves_path)?;
+            for hash in &missing {
+                file.write_all(hash)?;}
+            file.sync_all()?;
+            leaves.extend(missing);}
+        let tree = MerkleTree::from_leaf_hashes(leaves);
+        info!("Opened log {} with {} entries", dir.display(), tree.len());
+        Ok(TransparencyLog { dir: dir.to_path_buf(), tree })}
+
+    /// Appends `entry` and returns its index.
+    pub fn append(&mut self, entry: &[u8]) -> Result<u64, LogError> {
+        let len = u32::try_from(entry.len()).map_err(|_| LogError::InvalidEncoding("log entry (too long)"))?;
+        let hash = merkle::leaf_hash(entry);
+        let mut entries = OpenOptions::new().append(true).open(self.dir.join(ENTRIES_FILE))?;
+        entries.write_all(&len.to_be_bytes())?;
+        entries.write_all(entry)?;
+        entries.sync_all()?;
+        let mut leaves = OpenOptions::new().append(true).open(self.dir.join(LEAVES_FILE))?;
+        leaves.write_all(&hash)?;
+        leaves.sync_all()?;
+        self.tree.push_hash(hash);
+        debug!("Appended log entry {} ({} bytes)", self.tree.len() - 1, entry.len());
+        Ok(self.tree.len() as u64 - 1)}
+
+    pub fn size(&self) -> u64 {
+        self.tree.len() as u64}
+
+    pub fn root(&self) -> MerkleHash {
+        self.tree.root()}
+
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree}
+
+    /// The current, unsigned tree head.
+    pub fn head(&self, timestamp: u64) -> TreeHead {
+        TreeHead { tree_size: self.size(), timestamp, root: self.root() }}
+
+    /// Inclusion proof for entry `index` in the tree of the first `tree_size` entries.
+    pub fn prove_inclusion(&self, index: u64, tree_size: u64) -> Result<InclusionProof, LogError> {
+        Ok(self.tree.prove_at(index as usize, tree_size as usize)?)}
+
+    pub fn prove_consistency(&self, old_size: u64, new_size: u64) -> Result<ConsistencyProof, LogError> {
+        Ok(self.tree.prove_consistency(old_size as usize, new_size as usize)?)}
+
+    pub fn entry(&self, index: u64) -> Result<Vec<u8>, LogError> {
+        let mut reader = BufReader::new(File::open(self.dir.join(ENTRIES_FILE))?);
+        let mut current = 0u64;
+        while let Some(entry) = read_record(&mut reader)? {
+            if current == index {
+                return Ok(entry);}
+            current += 1;}
+        Err(LogError::Merkle(MerkleError::IndexOutOfRange { index, size: self.size() }))}
+
+    /// Rehashes every stored entry and compares it with its stored leaf hash.
+    pub fn audit_entries(&self) -> Result<(), LogError> {
+        let mut reader = BufReader::new(File::open(self.dir.join(ENTRIES_FILE))?);
+        let mut index = 0usize;
+        while let Some(entry) = read_record(&mut reader)? {
+            match self.tree.leaf(index) {
+                Some(hash) if *hash == merkle::leaf_hash(&entry) => {},
+                Some(_) => return Err(LogError::Corrupt(format!("entry {} does not match its leaf hash", index))),
+                None => return Err(LogError::Corrupt(format!("entry {} has no leaf hash", index))),}
+            index += 1;}
+        if index != self.tree.len() {
+            return Err(LogError::Corrupt(format!("{} entries for {} leaf hashes", index, self.tree.len())));}
+        Ok(())}
+
+    /// Checks a previously published head against this log: its signature, and that the log's
+    /// first `tree_size` entries still have its root.
+    pub fn check_head(&self, head: &SignedTreeHead, verifier: HeadVerifier) -> Result<(), LogError> {
+        if !head.verify(verifier) {
+            return Err(LogError::BadSignature);}
+        let root = self.tree.root_at(head.head.tree_size as usize)?;
+        if root != head.head.root {
+            return Err(LogError::Corrupt(format!(
+                "root of the first {} entries is {}, not the signed {}", head.head.tree_size, hex::encode(&root[..16]), hex::encode(&head.head.root[..16]))));}
+        Ok(())}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("qosmic-log-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            TempDir(dir)}}
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);}}
+
+    fn head(size: u64) -> TreeHead {
+        TreeHead { tree_size: size, timestamp: 1_700_000_000, root: [size as u8; HASH_LEN] }}
+
+    #[test]
+    fn hmac_heads_sign_verify_and_round_trip() {
+        let signed = head(3).sign_hmac(b"log key");
+        assert!(signed.verify(HeadVerifier::Hmac(b"log key")));
+        assert!(!signed.verify(HeadVerifier::Hmac(b"other key")));
+        assert_eq!(SignedTreeHead::from_bytes(&signed.to_bytes()).unwrap(), signed);
+        let mut altered = signed.clone();
+        altered.head.timestamp += 1;
+        assert!(!altered.verify(HeadVerifier::Hmac(b"log key")));
+        let bytes = signed.to_bytes();
+        assert_eq!(SignedTreeHead::from_bytes(&bytes[..bytes.len() - 1]), Err(LogError::InvalidEncoding("signed tree head")));
+        let mut version = bytes.clone();
+        version[4] = 9;
+        assert_eq!(SignedTreeHead::from_bytes(&version), Err(LogError::UnsupportedVersion(9)));}
+
+    #[test]
+    fn xmss_heads_sign_verify_and_round_trip() {
+        let mut key = XmssPrivateKey::from_seed(2, &[5; 3 * crate::xmss::N]).unwrap();
+        let public_key = key.public_key();
+        let signed = head(7).sign_xmss(&mut key).unwrap();
+        assert_eq!(key.next_index(), 1);
+        assert!(signed.verify(HeadVerifier::Xmss(&public_key)));
+        assert!(!signed.verify(HeadVerifier::Hmac(b"log key")));
+        assert!(!head(7).sign_hmac(b"log key").verify(HeadVerifier::Xmss(&public_key)));
+        let other = XmssPrivateKey::from_seed(2, &[6; 3 * crate::xmss::N]).unwrap().public_key();
+        assert!(!signed.verify(HeadVerifier::Xmss(&other)));
+        assert_eq!(SignedTreeHead::from_bytes(&signed.to_bytes()).unwrap(), signed);
+        let mut altered = signed.clone();
+        altered.head.root[0] ^= 1;
+        assert!(!altered.verify(HeadVerifier::Xmss(&public_key)));}
+
+    #[test]
+    fn log_proofs_verify_against_signed_heads() {
+        let dir = TempDir::new("proofs");
+        let mut log = TransparencyLog::open(&dir.0).unwrap();
+        let verifier = HeadVerifier::Hmac(b"log key");
+        let entries: Vec<Vec<u8>> = (0..9).map(|i| format!("entry {}", i).into_bytes()).collect();
+        for entry in &entries[..5] {
+            log.append(entry).unwrap();}
+        let old = log.head(1).sign_hmac(b"log key");
+        for entry in &entries[5..] {
+            log.append(entry).unwrap();}
+        let new = log.head(2).sign_hmac(b"log key");
+        assert_eq!(log.size(), 9);
+        assert_eq!(log.entry(6).unwrap(), entries[6]);
+        assert_eq!(log.root(), MerkleTree::from_leaves(&entries).root());
+
+        let proof = log.prove_inclusion(3, 9).unwrap();
+        assert_eq!(verify_inclusion(&new, verifier, &entries[3], &proof), Ok(()));
+        assert_eq!(verify_inclusion(&new, verifier, &entries[4], &proof), Err(LogError::ProofMismatch("inclusion proof")));
+        assert_eq!(verify_inclusion(&old, verifier, &entries[3], &proof), Err(LogError::ProofMismatch("inclusion proof")));
+        assert_eq!(verify_inclusion(&new, HeadVerifier::Hmac(b"other"), &entries[3], &proof), Err(LogError::BadSignature));
+        let consistency = log.prove_consistency(5, 9).unwrap();
+        assert_eq!(verify_consistency(&old, &new, verifier, &consistency), Ok(()));
+        assert_eq!(verify_consistency(&new, &old, verifier, &consistency), Err(LogError::ProofMismatch("consistency proof")));
+        assert_eq!(log.check_head(&old, verifier), Ok(()));
+        assert_eq!(log.audit_entries(), Ok(()));}
+
+    #[test]
+    fn reopening_recovers_torn_appends() {
+        let dir = TempDir::new("reopen");
+        let mut log = TransparencyLog::open(&dir.0).unwrap();
+        for i in 0..4u8 {
+            log.append(&[i; 10]).unwrap();}
+        let root = log.root();
+        assert_eq!(TransparencyLog::open(&dir.0).unwrap().root(), root);
+
+        // An entry written without its leaf hash is finished on open; a torn record is dropped.
+        let mut entries = OpenOptions::new().append(true).open(dir.0.join(ENTRIES_FILE)).unwrap();
+        entries.write_all(&3u32.to_be_bytes()).unwrap();
+        entries.write_all(b"abc").unwrap();
+        entries.write_all(&100u32.to_be_bytes()).unwrap();
+        entries.write_all(b"torn").unwrap();
+        drop(entries);
+        let reopened = TransparencyLog::open(&dir.0).unwrap();
+        assert_eq!(reopened.size(), 5);
+        assert_eq!(reopened.entry(4).unwrap(), b"abc");
+        assert_eq!(reopened.tree().root_at(4).unwrap(), root);
+        assert_eq!(reopened.audit_entries(), Ok(()));}}
@@ -128,3 +128,19 @@ pub fn generate_salt(size: usize) -> Vec<u8> {
     let mut salt = vec![0u8; size];
     rand::thread_rng().fill_bytes(&mut salt);
     salt}
+
+/// Draws a salt from `rng`, for example a seeded `drbg::HmacDrbg` when salts must be reproducible.
+pub fn generate_salt_with<R: RngCore + rand::CryptoRng>(rng: &mut R, size: usize) -> Vec<u8> {
+    let mut salt = vec![0u8; size];
+    rng.fill_bytes(&mut salt);
+    salt}
+
+/// Compares two byte slices without short-circuiting on the first mismatch.
+/// Only the lengths are compared in variable time.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;}
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;}
+    std::hint::black_box(diff) == 0}
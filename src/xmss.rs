@@ -0,0 +1,459 @@
+// src/xmss.rs
+use crate::core::qosmic_digest;
+use crate::utils;
+use hex;
+use log::{debug, info};
+use rand::RngCore;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+pub const XMSS_VERSION: u8 = 1;
+/// Hash output length in bytes; qosmic digests are truncated to this.
+pub const N: usize = 32;
+/// Winternitz parameter: each chain encodes 4 bits of the message digest.
+pub const W: u32 = 16;
+const LOG_W: usize = 4;
+const LEN_1: usize = 8 * N / LOG_W;
+const LEN_2: usize = 3;
+pub const WOTS_LEN: usize = LEN_1 + LEN_2;
+pub const DEFAULT_HEIGHT: u8 = 8;
+pub const MIN_HEIGHT: u8 = 2;
+pub const MAX_HEIGHT: u8 = 20;
+const PUBLIC_KEY_LEN: usize = 2 + 2 * N;
+const PRIVATE_HEADER_LEN: usize = 6 + 3 * N;
+
+const DOMAIN_CHAIN: u8 = 0x00;
+const DOMAIN_TREE: u8 = 0x01;
+const DOMAIN_WOTS_PK: u8 = 0x02;
+const DOMAIN_PRF: u8 = 0x03;
+const DOMAIN_MSG: u8 = 0x04;
+const DOMAIN_RANDOMIZER: u8 = 0x05;
+
+type Node = [u8; N];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    InvalidHeight(u8),
+    KeyExhausted,
+    InvalidEncoding(&'static str),
+    UnsupportedVersion(u8),
+    StateLocked(String),
+    Io(String),}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::InvalidHeight(h) => write!(f, "tree height {} is outside {}..={}", h, MIN_HEIGHT, MAX_HEIGHT),
+            SignatureError::KeyExhausted => write!(f, "every one-time key of this private key has been used"),
+            SignatureError::InvalidEncoding(what) => write!(f, "malformed {}", what),
+            SignatureError::UnsupportedVersion(v) => write!(f, "unsupported XMSS encoding version {}", v),
+            SignatureError::StateLocked(lock) => write!(f, "key state is locked by another signer; remove {} if none is running", lock),
+            SignatureError::Io(e) => write!(f, "I/O error: {}", e),}}}
+
+impl std::error::Error for SignatureError {}
+
+impl From<io::Error> for SignatureError {
+    fn from(e: io::Error) -> Self {
+        SignatureError::Io(e.to_string())}}
+
+fn check_height(height: u8) -> Result<(), SignatureError> {
+    if (MIN_HEIGHT..=MAX_HEIGHT).contains(&height) {
+        Ok(())
+    } else {
+        Err(SignatureError::InvalidHeight(height))}}
+
+/// Tweakable hash: `qosmic(domain || key || address || data)` truncated to `N` bytes. The address
+/// words say which chain step, tree node or leaf is being computed, so no two calls share an input.
+fn thash(domain: u8, key: &Node, address: [u32; 4], data: &[u8]) -> Node {
+    let mut input = Vec::with_capacity(1 + N + 16 + data.len());
+    input.push(domain);
+    input.extend_from_slice(key);
+    for word in address {
+        input.extend_from_slice(&word.to_be_bytes());}
+    input.extend_from_slice(data);
+    let digest = qosmic_digest(&input);
+    let mut out = [0u8; N];
+    out.copy_from_slice(&digest[..N]);
+    out}
+
+fn chain(mut x: Node, start: u32, steps: u32, pub_seed: &Node, leaf: u32, chain_idx: u32) -> Node {
+    for step in start..start + steps {
+        x = thash(DOMAIN_CHAIN, pub_seed, [leaf, chain_idx, step, 0], &x);}
+    x}
+
+/// Splits the digest into 4-bit chain positions and appends the 3-digit checksum, so raising any
+/// position forces some checksum digit down.
+fn chain_lengths(digest: &Node) -> [u32; WOTS_LEN] {
+    let mut lengths = [0u32; WOTS_LEN];
+    for (i, byte) in digest.iter().enumerate() {
+        lengths[2 * i] = (byte >> 4) as u32;
+        lengths[2 * i + 1] = (byte & 0x0F) as u32;}
+    let checksum: u32 = lengths[..LEN_1].iter().map(|d| W - 1 - d).sum();
+    lengths[LEN_1] = (checksum >> 8) & 0x0F;
+    lengths[LEN_1 + 1] = (checksum >> 4) & 0x0F;
+    lengths[LEN_1 + 2] = checksum & 0x0F;
+    lengths}
+
+fn wots_secret(sk_seed: &Node, leaf: u32, chain_idx: u32) -> Node {
+    thash(DOMAIN_PRF, sk_seed, [leaf, chain_idx, 0, 0], &[])}
+
+fn compress_wots_pk(pub_seed: &Node, leaf: u32, pk: &[Node]) -> Node {
+    thash(DOMAIN_WOTS_PK, pub_seed, [leaf, 0, 0, 0], pk.concat().as_slice())}
+
+fn wots_leaf(sk_seed: &Node, pub_seed: &Node, leaf: u32) -> Node {
+    let pk: Vec<Node> = (0..WOTS_LEN as u32)
+        .map(|i| chain(wots_secret(sk_seed, leaf, i), 0, W - 1, pub_seed, leaf, i))
+        .collect();
+    compress_wots_pk(pub_seed, leaf, &pk)}
+
+fn tree_node(pub_seed: &Node, height: u32, index: u32, left: &Node, right: &Node) -> Node {
+    thash(DOMAIN_TREE, pub_seed, [height, index, 0, 0], &[left.as_slice(), right.as_slice()].concat())}
+
+/// Index of node `index` at `level` in a tree stored level by level, leaves first.
+fn node_offset(tree_height: u8, level: u8, index: usize) -> usize {
+    let mut offset = 0;
+    for l in 0..level {
+        offset += 1usize << (tree_height - l);}
+    offset + index}
+
+fn message_digest(randomness: &Node, root: &Node, index: u32, message: &[u8]) -> Node {
+    thash(DOMAIN_MSG, randomness, [index, 0, 0, 0], &[root.as_slice(), message].concat())}
+
+fn read_node(bytes: &[u8]) -> Node {
+    let mut node = [0u8; N];
+    node.copy_from_slice(&bytes[..N]);
+    node}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct XmssPublicKey {
+    pub height: u8,
+    pub pub_seed: [u8; N],
+    pub root: [u8; N],}
+
+impl fmt::Debug for XmssPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "XmssPublicKey {{ height: {}, root: {} }}", self.height, hex::encode(self.root))}}
+
+/// Stateful private key. It caches the whole Merkle tree so signing costs one WOTS+ signature,
+/// and `next_index` must be persisted before a signature is released: reusing an index leaks
+/// enough of the one-time key to forge. `sign_with_state_file` does this for keys kept on disk.
+#[derive(Clone)]
+pub struct XmssPrivateKey {
+    height: u8,
+    next_index: u32,
+    sk_seed: Node,
+    sk_prf: Node,
+    pub_seed: Node,
+    tree: Vec<Node>,}
+
+impl fmt::Debug for XmssPrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("XmssPrivateKey")
+            .field("height", &self.height)
+            .field("next_index", &self.next_index)
+            .finish_non_exhaustive()}}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmssSignature {
+    pub height: u8,
+    pub index: u32,
+    pub randomness: [u8; N],
+    pub wots: Vec<[u8; N]>,
+    pub auth_path: Vec<[u8; N]>,}
+
+impl XmssPrivateKey {
+    pub fn generate(height: u8) -> Result<Self, SignatureError> {
+        let mut seed = [0u8; 3 * N];
+        rand::rng().fill_bytes(&mut seed);
+        XmssPrivateKey::from_seed(height, &seed)}
+
+    /// Deterministic key generation from `sk_seed || sk_prf || pub_seed`. Only for test vectors;
+    /// real keys should come from `generate`.
+    pub fn from_seed(height: u8, seed: &[u8; 3 * N]) -> Result<Self, SignatureError> {
+        check_height(height)?;
+        let start = Instant::now();
+        let sk_seed = read_node(&seed[..N]);
+        let sk_prf = read_node(&seed[N..2 * N]);
+        let pub_seed = read_node(&seed[2 * N..]);
+        let leaf_count = 1usize << height;
+        let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).clamp(1, leaf_count);
+        let per_worker = leaf_count.div_ceil(workers);
+        let mut tree: Vec<Node> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|w| {
+                    let (sk_seed, pub_seed) = (&sk_seed, &pub_seed);
+                    scope.spawn(move || {
+                        let end = ((w + 1) * per_worker).min(leaf_count);
+                        (w * per_worker..end).map(|leaf| wots_leaf(sk_seed, pub_seed, leaf as u32)).collect::<Vec<Node>>()})})
+                .collect();
+            handles.into_iter().flat_map(|h| h.join().expect("XMSS leaf thread panicked")).collect()});
+        tree.reserve(leaf_count - 1);
+        for level in 1..=height {
+            let below = node_offset(height, level - 1, 0);
+            for index in 0..(1usize << (height - level)) {
+                let node = tree_node(&pub_seed, level as u32, index as u32, &tree[below + 2 * index], &tree[below + 2 * index + 1]);
+                tree.push(node);}}
+        info!("XMSS key generation (height {}, {} leaves) took: {:?}", height, leaf_count, start.elapsed());
+        Ok(XmssPrivateKey { height, next_index: 0, sk_seed, sk_prf, pub_seed, tree })}
+
+    pub fn height(&self) -> u8 {
+        self.height}
+
+    pub fn next_index(&self) -> u32 {
+        self.next_index}
+
+    pub fn remaining_signatures(&self) -> u32 {
+        (1u32 << self.height) - self.next_index}
+
+    pub fn public_key(&self) -> XmssPublicKey {
+        XmssPublicKey { height: self.height, pub_seed: self.pub_seed, root: *self.tree.last().expect("XMSS tree has a root") }}
+
+    /// Signs with the next unused one-time key and advances `next_index`.
+    pub fn sign(&mut self, message: &[u8]) -> Result<XmssSignature, SignatureError> {
+        if self.remaining_signatures() == 0 {
+            return Err(SignatureError::KeyExhausted);}
+        let index = self.next_index;
+        self.next_index += 1;
+        let randomness = thash(DOMAIN_RANDOMIZER, &self.sk_prf, [index, 0, 0, 0], message);
+        let root = self.public_key().root;
+        let lengths = chain_lengths(&message_digest(&randomness, &root, index, message));
+        let wots = lengths.iter().enumerate()
+            .map(|(i, &len)| chain(wots_secret(&self.sk_seed, index, i as u32), 0, len, &self.pub_seed, index, i as u32))
+            .collect();
+        let auth_path = (0..self.height)
+            .map(|level| self.tree[node_offset(self.height, level, ((index as usize) >> level) ^ 1)])
+            .collect();
+        debug!("XMSS signature with one-time key {} of {}", index, 1u32 << self.height);
+        Ok(XmssSignature { height: self.height, index, randomness, wots, auth_path })}
+
+    /// `[version][height][next_index u32 BE][sk_seed][sk_prf][pub_seed][tree nodes, leaves first]`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(PRIVATE_HEADER_LEN + self.tree.len() * N);
+        out.push(XMSS_VERSION);
+        out.push(self.height);
+        out.extend_from_slice(&self.next_index.to_be_bytes());
+        out.extend_from_slice(&self.sk_seed);
+        out.extend_from_slice(&self.sk_prf);
+        out.extend_from_slice(&self.pub_seed);
+        for node in &self.tree {
+            out.extend_from_slice(node);}
+        out}
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SignatureError> {
+        if bytes.len() < PRIVATE_HEADER_LEN {
+            return Err(SignatureError::InvalidEncoding("XMSS private key"));}
+        if bytes[0] != XMSS_VERSION {
+            return Err(SignatureError::UnsupportedVersion(bytes[0]));}
+        let height = bytes[1];
+        check_height(height)?;
+        let next_index = u32::from_be_bytes(bytes[2..6].try_into().unwrap());
+        let node_count = (2usize << height) - 1;
+        if bytes.len() != PRIVATE_HEADER_LEN + node_count * N || next_index > 1u32 << height {
+            return Err(SignatureError::InvalidEncoding("XMSS private key"));}
+        let tree = bytes[PRIVATE_HEADER_LEN..].chunks_exact(N).map(read_node).collect();
+        Ok(XmssPrivateKey {
+            height,
+            next_index,
+            sk_seed: read_node(&bytes[6..]),
+            sk_prf: read_node(&bytes[6 + N..]),
+            pub_seed: read_node(&bytes[6 + 2 * N..]),
+            tree,})}}
+
+/// Writes `bytes` to a sibling temporary file, syncs it and renames it over `path`, so a crash
+/// leaves either the old or the new state on disk, never a torn one.
+pub fn write_state_atomically(path: &Path, bytes: &[u8]) -> Result<(), SignatureError> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = Path::new(&tmp_name);
+    let mut file = fs::File::create(tmp_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)?;
+    Ok(())}
+
+/// Exclusive lock on a key state file: `<path>.lock`, created with `create_new` so only one signer
+/// can hold it, and removed on drop. Two processes signing with the same file would otherwise read
+/// the same index and reuse a one-time key.
+#[derive(Debug)]
+pub struct StateLock {
+    path: PathBuf,}
+
+impl StateLock {
+    pub fn acquire(state_path: &Path) -> Result<Self, SignatureError> {
+        let mut lock_name = state_path.as_os_str().to_owned();
+        lock_name.push(".lock");
+        let path = PathBuf::from(lock_name);
+        match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => Ok(StateLock { path }),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(SignatureError::StateLocked(path.display().to_string())),
+            Err(e) => Err(e.into()),}}}
+
+impl Drop for StateLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            debug!("Could not remove state lock {}: {}", self.path.display(), e);}}}
+
+/// Loads the private key at `path`, reserves the next one-time key and writes the advanced state
+/// back before the signature is returned. If the write fails, no signature is produced. The whole
+/// read–sign–write sequence runs under a `StateLock`.
+pub fn sign_with_state_file(path: &Path, message: &[u8]) -> Result<(XmssSignature, u32), SignatureError> {
+    let _lock = StateLock::acquire(path)?;
+    let mut key = XmssPrivateKey::from_bytes(&fs::read(path)?)?;
+    let signature = key.sign(message)?;
+    write_state_atomically(path, &key.to_bytes())?;
+    Ok((signature, key.remaining_signatures()))}
+
+impl XmssPublicKey {
+    /// `[version][height][pub_seed][root]`, 66 bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(PUBLIC_KEY_LEN);
+        out.push(XMSS_VERSION);
+        out.push(self.height);
+        out.extend_from_slice(&self.pub_seed);
+        out.extend_from_slice(&self.root);
+        out}
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SignatureError> {
+        if bytes.len() != PUBLIC_KEY_LEN {
+            return Err(SignatureError::InvalidEncoding("XMSS public key"));}
+        if bytes[0] != XMSS_VERSION {
+            return Err(SignatureError::UnsupportedVersion(bytes[0]));}
+        check_height(bytes[1])?;
+        Ok(XmssPublicKey { height: bytes[1], pub_seed: read_node(&bytes[2..]), root: read_node(&bytes[2 + N..]) })}
+
+    pub fn verify(&self, message: &[u8], signature: &XmssSignature) -> bool {
+        if signature.height != self.height
+            || signature.index >= 1u32 << self.height
+            || signature.wots.len() != WOTS_LEN
+            || signature.auth_path.len() != self.height as usize {
+            return false;}
+        let index = signature.index;
+        let lengths = chain_lengths(&message_digest(&signature.randomness, &self.root, index, message));
+        let pk: Vec<Node> = signature.wots.iter().zip(lengths.iter()).enumerate()
+            .map(|(i, (sig, &len))| chain(*sig, len, W - 1 - len, &self.pub_seed, index, i as u32))
+            .collect();
+        let mut node = compress_wots_pk(&self.pub_seed, index, &pk);
+        for (level, sibling) in signature.auth_path.iter().enumerate() {
+            let parent_index = index >> (level + 1);
+            node = if (index >> level) & 1 == 0 {
+                tree_node(&self.pub_seed, level as u32 + 1, parent_index, &node, sibling)
+            } else {
+                tree_node(&self.pub_seed, level as u32 + 1, parent_index, sibling, &node)};}
+        utils::constant_time_eq(&node, &self.root)}}
+
+impl XmssSignature {
+    pub fn encoded_len(height: u8) -> usize {
+        6 + N + WOTS_LEN * N + height as usize * N}
+
+    /// `[version][height][index u32 BE][randomness][WOTS+ chains][auth path, leaf level first]`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(XmssSignature::encoded_len(self.height));
+        out.push(XMSS_VERSION);
+        out.push(self.height);
+        out.extend_from_slice(&self.index.to_be_bytes());
+        out.extend_from_slice(&self.randomness);
+        for node in self.wots.iter().chain(self.auth_path.iter()) {
+            out.extend_from_slice(node);}
+        out}
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SignatureError> {
+        if bytes.len() < 6 {
+            return Err(SignatureError::InvalidEncoding("XMSS signature"));}
+        if bytes[0] != XMSS_VERSION {
+            return Err(SignatureError::UnsupportedVersion(bytes[0]));}
+        let height = bytes[1];
+        check_height(height)?;
+        if bytes.len() != XmssSignature::encoded_len(height) {
+            return Err(SignatureError::InvalidEncoding("XMSS signature"));}
+        let index = u32::from_be_bytes(bytes[2..6].try_into().unwrap());
+        let mut nodes = bytes[6..].chunks_exact(N).map(read_node);
+        let randomness = nodes.next().expect("length checked above");
+        let wots = nodes.by_ref().take(WOTS_LEN).collect();
+        let auth_path = nodes.collect();
+        Ok(XmssSignature { height, index, randomness, wots, auth_path })}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::qosmic_digest;
+
+    fn test_seed() -> [u8; 3 * N] {
+        std::array::from_fn(|i| i as u8)}
+
+    fn digest_prefix(bytes: &[u8]) -> String {
+        hex::encode(&qosmic_digest(bytes)[..32])}
+
+    #[test]
+    fn seeded_key_matches_pinned_vectors() {
+        let mut key = XmssPrivateKey::from_seed(4, &test_seed()).unwrap();
+        assert_eq!(hex::encode(key.public_key().to_bytes()), "0104404142434445464748494a4b4c4d4e4f505152535455565758595a5b5c5d5e5f5af924aeca67448757c1d90853c56cd06cdd9b75229a54ff0bc0e31e29f82d1a");
+        let first = key.sign(b"abc").unwrap();
+        assert_eq!(hex::encode(first.randomness), "2b4cb153232f0f822aee6b9b4e7fca8faa2d1c761fef4c286c963fd974bc1903");
+        assert_eq!(first.to_bytes().len(), 2310);
+        assert_eq!(digest_prefix(&first.to_bytes()), "a3565cc9dc3df90ee42d10cbf41a18ecd571da358e363dac093334b7dec657b3");
+        let second = key.sign(b"").unwrap();
+        assert_eq!(hex::encode(second.randomness), "dd4b86126b43fc47facd850242e3ada8b21949af472eceba001040c162ac966a");
+        assert_eq!(digest_prefix(&second.to_bytes()), "f93d3d66e0777d092bf387b28f6ec9020e4dfd55c76c81bf08d29da29d5d46c7");}
+
+    #[test]
+    fn signatures_verify_and_reject_tampering() {
+        let mut key = XmssPrivateKey::from_seed(4, &test_seed()).unwrap();
+        let pk = key.public_key();
+        let signature = key.sign(b"message").unwrap();
+        assert!(pk.verify(b"message", &signature));
+        assert!(!pk.verify(b"messagf", &signature));
+        let decoded = XmssSignature::from_bytes(&signature.to_bytes()).unwrap();
+        assert_eq!(decoded, signature);
+        let mut bytes = signature.to_bytes();
+        for position in [6, 6 + N, bytes.len() - 1] {
+            bytes[position] ^= 0x01;
+            assert!(!pk.verify(b"message", &XmssSignature::from_bytes(&bytes).unwrap()), "flip at {}", position);
+            bytes[position] ^= 0x01;}
+        let mut wrong_index = signature.clone();
+        wrong_index.index = 1;
+        assert!(!pk.verify(b"message", &wrong_index));}
+
+    #[test]
+    fn key_is_exhausted_after_every_leaf_is_used() {
+        let mut key = XmssPrivateKey::from_seed(4, &test_seed()).unwrap();
+        let pk = key.public_key();
+        for i in 0..16u32 {
+            let signature = key.sign(&i.to_be_bytes()).unwrap();
+            assert_eq!(signature.index, i);
+            assert!(pk.verify(&i.to_be_bytes(), &signature));}
+        assert_eq!(key.remaining_signatures(), 0);
+        assert_eq!(key.sign(b"one more"), Err(SignatureError::KeyExhausted));}
+
+    #[test]
+    fn state_file_advances_on_every_signature() {
+        let path = std::env::temp_dir().join(format!("qosmic-xmss-test-{}.key", std::process::id()));
+        let key = XmssPrivateKey::from_seed(4, &test_seed()).unwrap();
+        let pk = key.public_key();
+        write_state_atomically(&path, &key.to_bytes()).unwrap();
+        let (first, remaining) = sign_with_state_file(&path, b"one").unwrap();
+        assert_eq!((first.index, remaining), (0, 15));
+        let (second, remaining) = sign_with_state_file(&path, b"two").unwrap();
+        assert_eq!((second.index, remaining), (1, 14));
+        assert!(pk.verify(b"one", &first) && pk.verify(b"two", &second));
+        let stored = XmssPrivateKey::from_bytes(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(stored.next_index(), 2);
+        assert_eq!(stored.public_key(), pk);
+        fs::remove_file(&path).unwrap();}
+
+    #[test]
+    fn held_state_lock_blocks_signing() {
+        let path = std::env::temp_dir().join(format!("qosmic-xmss-lock-test-{}.key", std::process::id()));
+        let key = XmssPrivateKey::from_seed(4, &test_seed()).unwrap();
+        write_state_atomically(&path, &key.to_bytes()).unwrap();
+        let lock = StateLock::acquire(&path).unwrap();
+        let lock_path = format!("{}.lock", path.display());
+        assert_eq!(StateLock::acquire(&path).unwrap_err(), SignatureError::StateLocked(lock_path.clone()));
+        assert_eq!(sign_with_state_file(&path, b"blocked"), Err(SignatureError::StateLocked(lock_path.clone())));
+        assert_eq!(XmssPrivateKey::from_bytes(&fs::read(&path).unwrap()).unwrap().next_index(), 0);
+        drop(lock);
+        assert_eq!(sign_with_state_file(&path, b"free").unwrap().0.index, 0);
+        assert!(!Path::new(&lock_path).exists());
+        fs::remove_file(&path).unwrap();}}
@@ -0,0 +1,118 @@
+// src/xof.rs
+use crate::core::qosmic_digest;
+use rand::RngCore;
+use std::fmt;
+
+const KEY_LEN: usize = 32;
+const BLOCK_LEN: usize = 64;
+
+/// Extendable-output function built on qosmic in counter mode. The domain and seed are absorbed
+/// once into a 32-byte key, `K = qosmic(len(domain) || domain || seed)`, and block `i` of the output
+/// is `qosmic(K || i_be64)`. Each block input fits in a single qosmic chunk.
+///
+/// Output runs at roughly 2 MB/s, so it suits seeds, coins and noise rather than bulk data.
+/// It implements `RngCore`, so it can drive any sampler that takes an RNG, but the `lwe` samplers
+/// read bytes directly so their output does not depend on `rand`'s algorithms.
+#[derive(Clone)]
+pub struct QosmicXof {
+    key: [u8; KEY_LEN],
+    counter: u64,
+    block: [u8; BLOCK_LEN],
+    pos: usize,}
+
+impl fmt::Debug for QosmicXof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QosmicXof").field("counter", &self.counter).finish_non_exhaustive()}}
+
+impl QosmicXof {
+    /// Only constructed inside the crate, with the constant domains of each construction. Panics if
+    /// `domain` is longer than 255 bytes, since its length is absorbed as one byte and a longer
+    /// domain would be ambiguous with a shorter one.
+    pub(crate) fn new(domain: &[u8], seed: &[u8]) -> Self {
+        assert!(domain.len() <= u8::MAX as usize, "XOF domain must be at most 255 bytes, got {}", domain.len());
+        let mut input = Vec::with_capacity(1 + domain.len() + seed.len());
+        input.push(domain.len() as u8);
+        input.extend_from_slice(domain);
+        input.extend_from_slice(seed);
+        let mut key = [0u8; KEY_LEN];
+        key.copy_from_slice(&qosmic_digest(&input)[..KEY_LEN]);
+        QosmicXof { key, counter: 0, block: [0u8; BLOCK_LEN], pos: BLOCK_LEN }}
+
+    fn refill(&mut self) {
+        let mut input = [0u8; KEY_LEN + 8];
+        input[..KEY_LEN].copy_from_slice(&self.key);
+        input[KEY_LEN..].copy_from_slice(&self.counter.to_be_bytes());
+        self.block = qosmic_digest(&input);
+        self.counter += 1;
+        self.pos = 0;}
+
+    pub fn read(&mut self, out: &mut [u8]) {
+        let mut filled = 0;
+        while filled < out.len() {
+            if self.pos == BLOCK_LEN {
+                self.refill();}
+            let take = (BLOCK_LEN - self.pos).min(out.len() - filled);
+            out[filled..filled + take].copy_from_slice(&self.block[self.pos..self.pos + take]);
+            self.pos += take;
+            filled += take;}}
+
+    pub fn read_byte(&mut self) -> u8 {
+        let mut byte = [0u8; 1];
+        self.read(&mut byte);
+        byte[0]}
+
+    pub fn squeeze(&mut self, len: usize) -> Vec<u8> {
+        let mut out = vec![0u8; len];
+        self.read(&mut out);
+        out}}
+
+impl RngCore for QosmicXof {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.read(&mut bytes);
+        u32::from_be_bytes(bytes)}
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.read(&mut bytes);
+        u64::from_be_bytes(bytes)}
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.read(dest)}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_answer() {
+        let mut xof = QosmicXof::new(b"qosmic-xof test", b"abc");
+        assert_eq!(hex::encode(xof.squeeze(100)), "109c8976dc59ffdef2a40e3d18c32c9a0c16b9083e8f35d31b7b1d146902120463a0796a80d393dbb1893308e80d6b6331cfb0076e81ef3eb81ae3c9bb9ef42f4efeb88dcb1aab48894e9aa3367e2be3d91596cc32b446ba917122c41b170dc17b830029");
+        assert_eq!(hex::encode(QosmicXof::new(b"", b"").squeeze(16)), "4609bfdd8e27edb5ae2de9a1ca6bf92a");}
+
+    #[test]
+    fn output_follows_the_counter_mode_definition() {
+        let key = &qosmic_digest(b"\x03abcseed")[..KEY_LEN];
+        let mut block_1 = key.to_vec();
+        block_1.extend_from_slice(&1u64.to_be_bytes());
+        let mut xof = QosmicXof::new(b"abc", b"seed");
+        let output = xof.squeeze(2 * BLOCK_LEN);
+        assert_eq!(output[BLOCK_LEN..], qosmic_digest(&block_1));}
+
+    #[test]
+    fn split_reads_match_one_read() {
+        let whole = QosmicXof::new(b"d", b"s").squeeze(200);
+        let mut xof = QosmicXof::new(b"d", b"s");
+        let mut pieces = Vec::new();
+        for len in [1, 63, 1, 70, 65] {
+            pieces.extend_from_slice(&xof.squeeze(len));}
+        assert_eq!(pieces, whole);}
+
+    #[test]
+    fn domain_and_seed_are_separated() {
+        assert_ne!(QosmicXof::new(b"ab", b"c").squeeze(32), QosmicXof::new(b"a", b"bc").squeeze(32));}
+
+    #[test]
+    #[should_panic(expected = "at most 255 bytes")]
+    fn overlong_domain_panics() {
+        QosmicXof::new(&[0u8; 256], b"");}}